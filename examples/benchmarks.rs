@@ -18,7 +18,7 @@ use thresh_mpc::{
     func_mult::DklsMultPlayer,
     func_net::{AsyncNet, AsyncNetworkMgr},
     func_rand::FolkloreRandPlayer,
-    func_thresh::{AsyncThresh, GenericThreshPlayer, RstThreshPlayer},
+    func_thresh::{state::StateKey, AsyncThresh, GenericThreshPlayer, RstThreshPlayer},
     func_thresh_abit::RstTabitPlayer,
     party::{PartyId, PartyInfo},
     utils::parse_party_file,
@@ -59,6 +59,10 @@ struct RunInformation {
     /// A file to save/load state from
     state_file: Option<String>,
 
+    #[argh(option, short = 'k')]
+    /// a file holding the 32-byte key to encrypt the state file under
+    state_key_file: Option<String>,
+
     #[argh(option, short = 'p')]
     /// name of the file that gives the connection information for each party
     party_file: String,
@@ -136,7 +140,7 @@ async fn setup_systems(
     (
         Vec<PartyId>,
         Arc<impl AsyncNet>,
-        Arc<WrkMpcPlayer<F>>,
+        Arc<WrkMpcPlayer<F, impl AsyncNet>>,
         impl AsyncThresh,
         impl AsyncThresh,
     ),
@@ -152,6 +156,7 @@ async fn setup_systems(
         FuncId::Fcote,
         FuncId::Fmult,
         FuncId::Ftabit,
+        FuncId::Fmpc,
         FuncId::Fcontroller,
     ];
 
@@ -194,7 +199,8 @@ async fn setup_systems(
         com.clone(),
         net.clone(),
     )?);
-    let mpc: Arc<WrkMpcPlayer<F>> = Arc::new(WrkMpcPlayer::new(info.my_id, &party_info)?);
+    let mpc: Arc<WrkMpcPlayer<F, _>> =
+        Arc::new(WrkMpcPlayer::new(info.my_id, &party_info, net.clone())?);
     let comcomp = GenericThreshPlayer::new(info.my_id, num_parties, threshold, mpc.clone())?;
     let thresh: RstThreshPlayer<F, _, _> =
         RstThreshPlayer::new(info.my_id, num_parties, threshold, mpc.clone(), tabit)?;
@@ -352,7 +358,7 @@ async fn run_all(
     mut thresh: impl AsyncThresh,
     info: RunInformation,
     net: Arc<impl AsyncNet>,
-    mpc: Arc<WrkMpcPlayer<F>>,
+    mpc: Arc<WrkMpcPlayer<F, impl AsyncNet>>,
     all_parties: Vec<PartyId>,
 ) -> Result<(), UnexpectedError> {
     let nparties = all_parties.len();
@@ -383,6 +389,11 @@ async fn run_all(
 
     let mut needs_init = true;
 
+    if let Some(key_file) = info.state_key_file.as_ref() {
+        let key = StateKey::from_key_file(key_file).expect("failed to read state key file");
+        thresh.set_state_key(key);
+    }
+
     if let Some(state_file) = info.state_file.as_ref() {
         let r = thresh.resume_from_state_file(&state_file, false).await;
         if r.is_err() {
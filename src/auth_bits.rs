@@ -1,9 +1,11 @@
 use crate::{
-    circuits::CircuitElement,
-    field::{Field, Ring},
+    circuits::{elements::bit_basis, CircuitElement},
+    field::{BinaryField, Field, Ring},
     polynomial::lagrange_poly,
 };
 
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug)]
@@ -78,6 +80,18 @@ impl<F> Abits<F> {
 }
 
 impl<F: Ring> Abits<F> {
+    /// Reorder the bits so that bit i of the result is bit `perm[i]` of these
+    pub fn permute(&self, perm: &[usize]) -> Self {
+        assert!(perm.len() == self.len());
+        let select = |xs: &[F]| -> Vec<F> { perm.iter().map(|&i| xs[i].clone()).collect() };
+
+        Abits {
+            bits: perm.iter().map(|&i| self.bits[i]).collect(),
+            macs: self.macs.iter().map(|m| select(m)).collect(),
+            keys: self.keys.iter().map(|k| select(k)).collect(),
+        }
+    }
+
     pub fn add_assign(&mut self, other: &Self) {
         self.bits
             .iter_mut()
@@ -139,6 +153,50 @@ impl<F: Clone> From<&[Abit<F>]> for Abits<F> {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+/// A SPDZ-style authenticated share, i.e. an additive share of a value x
+/// along with an additive share of its MAC \alpha x under the global key \alpha
+pub struct AuthShare<F> {
+    pub share: F,
+    pub mac: F,
+}
+
+impl<F: Ring> AuthShare<F> {
+    pub fn add_assign(&mut self, other: &Self) {
+        self.share += &other.share;
+        self.mac += &other.mac;
+    }
+
+    pub fn sub_assign(&mut self, other: &Self) {
+        self.share -= &other.share;
+        self.mac -= &other.mac;
+    }
+
+    /// Multiply the shared value by a public constant
+    pub fn scale(&mut self, c: &F) {
+        self.share *= c;
+        self.mac *= c;
+    }
+
+    /// Add a public constant to the shared value
+    /// The first party adds the constant to their share and every party
+    /// adds their share of \alpha c to their MAC
+    pub fn add_const(&mut self, c: &F, alpha: &F, is_first: bool) {
+        if is_first {
+            self.share += c;
+        }
+        self.mac += alpha.clone() * c;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+/// An authenticated multiplication triple ([a], [b], [c]) where c = ab
+pub struct AuthTriple<F> {
+    pub a: AuthShare<F>,
+    pub b: AuthShare<F>,
+    pub c: AuthShare<F>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ThreshAbits<F> {
     // the number of actual bits encoded
@@ -149,6 +207,10 @@ pub struct ThreshAbits<F> {
     // #parties x #shares*F::BIT_SIZE
     macs: Vec<Vec<F>>,
     keys: Vec<Vec<F>>,
+    // the bit of the packed shares holding each of the nbits bits,
+    // where None is the dense layout with bit i in bit i of the shares
+    #[serde(default)]
+    layout: Option<Vec<usize>>,
 }
 
 impl<F> ThreshAbits<F> {
@@ -158,66 +220,200 @@ impl<F> ThreshAbits<F> {
             shares: Vec::new(),
             macs: Vec::new(),
             keys: Vec::new(),
+            layout: None,
         }
     }
+
+    /// The number of packed field elements backing the bits
+    pub fn num_shares(&self) -> usize {
+        self.shares.len()
+    }
+
+    /// The bit of the packed shares holding bit i
+    fn phys(&self, i: usize) -> usize {
+        self.layout.as_ref().map_or(i, |l| l[i])
+    }
+
+    fn set_layout(&mut self, layout: Vec<usize>) {
+        let dense = layout.iter().enumerate().all(|(i, &p)| i == p);
+        self.nbits = layout.len();
+        self.layout = if dense { None } else { Some(layout) };
+    }
 }
 
 impl<F: Field + CircuitElement + Copy> ThreshAbits<F> {
-    /*
-    pub fn add_assign(&mut self, other: &Self) {
-        self.share += other.share;
+    /// Add another set of threshold abits to these, bit by bit.
+    /// Shares are added as field elements, so this relies on F having characteristic 2,
+    /// and fails if some pair of bits sits at different offsets within their elements.
+    pub fn add_assign(&mut self, other: &Self) -> Result<(), &'static str> {
+        assert!(self.nbits == other.nbits);
+        assert!(self.macs.len() == other.macs.len());
+        let bsize = F::BIT_SIZE;
 
-        // technically this implementation is only correct for fields of characteristic 2
-        self.macs
-            .iter_mut()
-            .zip(other.macs.iter())
-            .for_each(|(m, o)| *m += o);
+        // every pair of elements holding a pair of bits becomes one new element
+        let mut pairs: Vec<(usize, usize)> = Vec::new();
+        let mut pair_idx: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut layout = Vec::with_capacity(self.nbits);
+        for i in 0..self.nbits {
+            let (p, q) = (self.phys(i), other.phys(i));
+            if p % bsize != q % bsize {
+                return Err("bits are not aligned within their elements");
+            }
+            let pair = (p / bsize, q / bsize);
+            let el = *pair_idx.entry(pair).or_insert_with(|| {
+                pairs.push(pair);
+                pairs.len() - 1
+            });
+            layout.push(el * bsize + p % bsize);
+        }
 
-        self.keys
-            .iter_mut()
+        let add_els = |s: &[F], o: &[F]| {
+            let mut xs = Vec::with_capacity(pairs.len() * bsize);
+            for &(i, j) in pairs.iter() {
+                let start = xs.len();
+                xs.extend_from_slice(&s[i * bsize..(i + 1) * bsize]);
+                F::add_slices(&mut xs[start..], &o[j * bsize..(j + 1) * bsize]);
+            }
+            xs
+        };
+
+        self.shares = pairs
+            .iter()
+            .map(|&(i, j)| self.shares[i] + other.shares[j])
+            .collect();
+        self.macs = self
+            .macs
+            .iter()
+            .zip(other.macs.iter())
+            .map(|(s, o)| add_els(s, o))
+            .collect();
+        self.keys = self
+            .keys
+            .iter()
             .zip(other.keys.iter())
-            .for_each(|(m, o)| *m += o);
+            .map(|(s, o)| add_els(s, o))
+            .collect();
+        self.set_layout(layout);
+
+        Ok(())
     }
-    */
 
     pub fn add_consts(&mut self, cs: &[bool], delta: F) {
         assert!(self.nbits == cs.len());
         let num_bits = F::BIT_SIZE;
 
-        cs.chunks_exact(num_bits)
-            .zip(self.shares.iter_mut())
-            .for_each(|(c, s)| {
-                let t = F::from_bits(c);
-                *s += t;
-            });
-
-        // if there is a remainder add to the last share
-        let r = cs.chunks_exact(num_bits).remainder();
-        if r.len() > 0 {
-            let mut c_bits = vec![false; num_bits];
-            c_bits[..r.len()].copy_from_slice(r);
-            let x = F::from_bits(&c_bits);
-            *self.shares.last_mut().unwrap() += x;
+        // place the constants in the bits of the shares that hold them,
+        // the remaining bits are 0 so won't need any modification
+        let mut c_bits = vec![false; self.shares.len() * num_bits];
+        for (i, &c) in cs.iter().enumerate() {
+            c_bits[self.phys(i)] ^= c;
         }
 
-        // implicitly the remaining bits are 0 so we won't need to many any modification to the remaining keys
-        self.keys.iter_mut().for_each(|ks| {
-            ks.iter_mut().zip(cs.iter()).for_each(|(k, c)| {
-                if *c {
-                    *k += delta;
-                }
-            });
-        });
+        let c_els: Vec<F> = c_bits.chunks_exact(num_bits).map(F::from_bits).collect();
+        F::add_slices(&mut self.shares, &c_els);
+
+        let c_deltas: Vec<F> = c_bits
+            .iter()
+            .map(|&c| if c { delta } else { F::zero() })
+            .collect();
+        self.keys
+            .iter_mut()
+            .for_each(|ks| F::add_slices(ks, &c_deltas));
 
         // no changes to the MACs
     }
 
+    /// The packed element of the shares holding bit i
+    pub fn element(&self, i: usize) -> usize {
+        self.phys(i) / F::BIT_SIZE
+    }
+
+    /// The bits at `idx`, in that order, dropping any elements no longer needed.
+    /// Indices must be distinct, as otherwise adding to one copy would change the other.
+    pub fn slice(&self, idx: &[usize]) -> Self {
+        assert!(idx.iter().all(|&i| i < self.nbits));
+        let mut seen = vec![false; self.nbits];
+        assert!(
+            idx.iter().all(|&i| !std::mem::replace(&mut seen[i], true)),
+            "duplicate bit index"
+        );
+        let bsize = F::BIT_SIZE;
+
+        let mut els: Vec<usize> = idx.iter().map(|&i| self.phys(i) / bsize).collect();
+        els.sort_unstable();
+        els.dedup();
+
+        let layout = idx
+            .iter()
+            .map(|&i| {
+                let p = self.phys(i);
+                els.binary_search(&(p / bsize)).unwrap() * bsize + p % bsize
+            })
+            .collect();
+
+        let select = |xs: &[F]| -> Vec<F> {
+            els.iter()
+                .flat_map(|&e| xs[e * bsize..(e + 1) * bsize].iter().copied())
+                .collect()
+        };
+
+        let mut tabits = ThreshAbits {
+            nbits: 0,
+            shares: els.iter().map(|&e| self.shares[e]).collect(),
+            macs: self.macs.iter().map(|m| select(m)).collect(),
+            keys: self.keys.iter().map(|k| select(k)).collect(),
+            layout: None,
+        };
+        tabits.set_layout(layout);
+        tabits
+    }
+
+    /// Reorder the bits so that bit i of the result is bit `perm[i]` of these
+    pub fn permute(&self, perm: &[usize]) -> Self {
+        assert!(perm.len() == self.nbits);
+        self.slice(perm)
+    }
+
+    /// Split into the first `at` bits and the rest
+    pub fn split(&self, at: usize) -> (Self, Self) {
+        assert!(at <= self.nbits);
+        let first: Vec<usize> = (0..at).collect();
+        let rest: Vec<usize> = (at..self.nbits).collect();
+        (self.slice(&first), self.slice(&rest))
+    }
+
+    /// Concatenate `other` onto the end of these bits
+    pub fn append(&mut self, mut other: Self) {
+        if self.shares.is_empty() {
+            assert!(self.nbits == 0);
+            *self = other;
+            return;
+        }
+        assert!(self.macs.len() == other.macs.len());
+
+        let offset = self.shares.len() * F::BIT_SIZE;
+        let layout = (0..self.nbits)
+            .map(|i| self.phys(i))
+            .chain((0..other.nbits).map(|i| offset + other.phys(i)))
+            .collect();
+
+        self.shares.append(&mut other.shares);
+        for (s, o) in self.macs.iter_mut().zip(other.macs.iter_mut()) {
+            s.append(o);
+        }
+        for (s, o) in self.keys.iter_mut().zip(other.keys.iter_mut()) {
+            s.append(o);
+        }
+        self.set_layout(layout);
+    }
+
     pub fn from_abits(nbits: usize, shares: Vec<F>, abits: Abits<F>) -> Self {
         ThreshAbits {
             nbits: nbits,
             shares: shares,
             macs: abits.macs,
             keys: abits.keys,
+            layout: None,
         }
     }
 
@@ -229,6 +425,7 @@ impl<F: Field + CircuitElement + Copy> ThreshAbits<F> {
         bit_idx: &[usize],
     ) -> Abits<F> {
         assert!(bit_idx.iter().all(|&i| i < self.nbits));
+        let bit_idx: Vec<usize> = bit_idx.iter().map(|&i| self.phys(i)).collect();
         let n = all_points.len();
         assert!(self.macs.len() == (n - 1));
         assert!(self.keys.len() == (n - 1));
@@ -239,15 +436,14 @@ impl<F: Field + CircuitElement + Copy> ThreshAbits<F> {
 
         let (sub_idx, lp_i, lp_bits) = Self::convert_pre(my_point, all_points, sub_points);
 
-        let mut add_share_bits = vec![false; bsize * self.shares.len()];
+        let mut add_shares = self.shares.clone();
+        F::scale_slice(&mut add_shares, &lp_i);
 
+        let mut add_share_bits = vec![false; bsize * self.shares.len()];
         add_share_bits
             .chunks_exact_mut(bsize)
-            .zip(self.shares.iter())
-            .for_each(|(c, s)| {
-                let ls = lp_i.clone() * s;
-                ls.to_bits(c);
-            });
+            .zip(add_shares.iter())
+            .for_each(|(c, s)| s.to_bits(c));
 
         let bits = bit_idx.iter().map(|&bi| add_share_bits[bi]).collect();
         let mut macs = Vec::with_capacity(sub_idx.len());
@@ -317,7 +513,7 @@ impl<F: Field + CircuitElement + Copy> ThreshAbits<F> {
         // eval at 0
         let lps: Vec<_> = sub_points
             .iter()
-            .map(|p| lagrange_poly(sub_points, p, |x| *x))
+            .map(|p| lagrange_poly(sub_points, p, |x| F::zero() - x))
             .collect();
 
         let lp_i = lps[my_idx].clone();
@@ -325,15 +521,7 @@ impl<F: Field + CircuitElement + Copy> ThreshAbits<F> {
         let bsize = F::BIT_SIZE;
 
         // for FF_{2^k} calculate X^0 .. X^{k-1}
-        let mut x = F::one();
-        let two: F = F::gen();
-        let mut pows = Vec::with_capacity(bsize);
-        pows.push(x);
-
-        for _ in 1..(bsize) {
-            x *= two;
-            pows.push(x);
-        }
+        let pows: Vec<F> = bit_basis();
 
         // for each coefficient, for each input bit calculate the effect on inclusion in output bit
         let lp_bits: Vec<Vec<Vec<bool>>> = lps
@@ -354,14 +542,250 @@ impl<F: Field + CircuitElement + Copy> ThreshAbits<F> {
     }
 }
 
+impl<F: BinaryField + CircuitElement + Copy> ThreshAbits<F> {
+    /// Re-randomize the shares by adding `zeros`, fresh threshold abits of all zero bits
+    /// covering every element of the shares, so the bits themselves are unchanged.
+    /// The bits are added by adding their elements, hence the binary field.
+    pub fn refresh(&mut self, zeros: &Self) {
+        assert!(zeros.layout.is_none() && zeros.shares.len() == self.shares.len());
+        assert!(zeros.macs.len() == self.macs.len());
+
+        F::add_slices(&mut self.shares, &zeros.shares);
+        for (m, z) in self.macs.iter_mut().zip(zeros.macs.iter()) {
+            F::add_slices(m, z);
+        }
+        for (k, z) in self.keys.iter_mut().zip(zeros.keys.iter()) {
+            F::add_slices(k, z);
+        }
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
     use crate::{
         ff2_128::FF2_128,
+        ff2_64::FF2_64,
+        ff2_8::FF2_8,
         field::RandElement,
         polynomial::{FixedPolynomial, Polynomial},
     };
+    use rand::Rng;
+
+    /// Deal threshold abits of `bits` to parties 1..=n with a degree t - 1 sharing,
+    /// where party i has MAC key `deltas[i - 1]`, returning each party's tabits and key
+    pub fn build_test_tabits<F>(deltas: &[F], t: usize, bits: &[bool]) -> Vec<(ThreshAbits<F>, F)>
+    where
+        F: Field + CircuitElement + RandElement + Copy,
+    {
+        let mut rng = rand::thread_rng();
+        let bsize = F::BIT_SIZE;
+        let n = deltas.len();
+
+        let mut padded = bits.to_vec();
+        padded.resize(bits.len().div_ceil(bsize) * bsize, false);
+        let shares: Vec<Vec<F>> = padded
+            .chunks_exact(bsize)
+            .map(|c| {
+                let mut poly = FixedPolynomial::rand_polynomial(&mut rng, t - 1);
+                let offset = F::from_bits(c) - poly.eval_zero();
+                poly = &poly + &FixedPolynomial::new(vec![offset]);
+                (1..=n).map(|p| poly.evaluate(&F::from(p as u64))).collect()
+            })
+            .collect();
+
+        let share_bits: Vec<Vec<bool>> = (0..n)
+            .map(|i| {
+                let mut bs = vec![false; padded.len()];
+                bs.chunks_exact_mut(bsize)
+                    .zip(shares.iter())
+                    .for_each(|(c, s)| s[i].to_bits(c));
+                bs
+            })
+            .collect();
+
+        // keys[j][i] is party j's keys on party i's bits
+        let keys: Vec<Vec<Vec<F>>> = (0..n)
+            .map(|_| {
+                (0..n)
+                    .map(|_| (0..padded.len()).map(|_| F::rand(&mut rng)).collect())
+                    .collect()
+            })
+            .collect();
+
+        (0..n)
+            .map(|i| {
+                let others = (0..n).filter(move |&j| j != i);
+                let macs = others
+                    .clone()
+                    .map(|j| {
+                        keys[j][i]
+                            .iter()
+                            .zip(share_bits[i].iter())
+                            .map(|(&k, &b)| if b { k + deltas[j] } else { k })
+                            .collect()
+                    })
+                    .collect();
+                let tabits = ThreshAbits {
+                    nbits: bits.len(),
+                    shares: shares.iter().map(|s| s[i]).collect(),
+                    macs,
+                    keys: others.map(|j| keys[i][j].clone()).collect(),
+                    layout: None,
+                };
+                (tabits, deltas[i])
+            })
+            .collect()
+    }
+
+    /// Convert the tabits of all parties, check the MACs and return the shared bits
+    pub fn open_test_tabits<F>(tabits: &[(ThreshAbits<F>, F)]) -> Vec<bool>
+    where
+        F: Field + CircuitElement + Copy,
+    {
+        let n = tabits.len();
+        let points: Vec<F> = (1..=n).map(|p| F::from(p as u64)).collect();
+        let idx: Vec<usize> = (0..tabits[0].0.nbits).collect();
+        let abits: Vec<_> = tabits
+            .iter()
+            .zip(points.iter())
+            .map(|((ts, _), p)| ts.convert(p, &points, &points, &idx))
+            .collect();
+
+        // the position of party j among the other parties of party i
+        let pos = |i: usize, j: usize| if j < i { j } else { j - 1 };
+        for i in 0..n {
+            for j in (0..n).filter(|&j| j != i) {
+                for (k, &b) in abits[i].bits.iter().enumerate() {
+                    let mut key = abits[j].keys[pos(j, i)][k];
+                    if b {
+                        key += tabits[j].1;
+                    }
+                    assert_eq!(abits[i].macs[pos(i, j)][k], key);
+                }
+            }
+        }
+
+        idx.iter()
+            .map(|&k| abits.iter().fold(false, |acc, a| acc ^ a.bits[k]))
+            .collect()
+    }
+
+    #[test]
+    fn test_tabit_linear_ops() {
+        let mut rng = rand::thread_rng();
+        let (n, t, nbits) = (3, 2, 21);
+        let xs: Vec<bool> = (0..nbits).map(|_| rng.gen()).collect();
+        let ys: Vec<bool> = (0..nbits).map(|_| rng.gen()).collect();
+        let cs: Vec<bool> = (0..nbits).map(|_| rng.gen()).collect();
+
+        let deltas: Vec<FF2_8> = (0..n).map(|_| FF2_8::rand(&mut rng)).collect();
+        let mut tx = build_test_tabits(&deltas, t, &xs);
+        let ty = build_test_tabits(&deltas, t, &ys);
+        assert_eq!(open_test_tabits(&tx), xs);
+
+        // x + y
+        let xor = |a: &[bool], b: &[bool]| -> Vec<bool> {
+            a.iter().zip(b.iter()).map(|(a, b)| a ^ b).collect()
+        };
+        for ((a, _), (b, _)) in tx.iter_mut().zip(ty.iter()) {
+            a.add_assign(b).unwrap();
+        }
+        let xy = xor(&xs, &ys);
+        assert_eq!(open_test_tabits(&tx), xy);
+
+        // x + y + c
+        for (a, d) in tx.iter_mut() {
+            a.add_consts(&cs, *d);
+        }
+        let xyc = xor(&xy, &cs);
+        assert_eq!(open_test_tabits(&tx), xyc);
+
+        // slicing keeps only the elements that are needed
+        let idx = [20, 3, 17, 4];
+        let sliced: Vec<_> = tx.iter().map(|(a, d)| (a.slice(&idx), *d)).collect();
+        assert_eq!(sliced[0].0.shares.len(), 2);
+        let els: Vec<_> = (0..4).map(|i| sliced[0].0.element(i)).collect();
+        assert_eq!(els, vec![1, 0, 1, 0]);
+        assert_eq!(
+            open_test_tabits(&sliced),
+            idx.iter().map(|&i| xyc[i]).collect::<Vec<_>>()
+        );
+
+        // split then concatenate back together
+        let parts: Vec<_> = tx.iter().map(|(a, d)| (a.split(5), *d)).collect();
+        let first: Vec<_> = parts.iter().map(|((a, _), d)| (a.clone(), *d)).collect();
+        let rest: Vec<_> = parts.iter().map(|((_, b), d)| (b.clone(), *d)).collect();
+        assert_eq!(open_test_tabits(&first), xyc[..5].to_vec());
+        assert_eq!(open_test_tabits(&rest), xyc[5..].to_vec());
+
+        let joined: Vec<_> = parts
+            .into_iter()
+            .map(|((mut a, b), d)| {
+                a.append(b);
+                (a, d)
+            })
+            .collect();
+        assert_eq!(open_test_tabits(&joined), xyc);
+
+        // a permutation, then adding constants and values through the new layout
+        let perm: Vec<usize> = (0..nbits).rev().collect();
+        let mut permuted: Vec<_> = joined.iter().map(|(a, d)| (a.permute(&perm), *d)).collect();
+        let pxyc: Vec<bool> = perm.iter().map(|&i| xyc[i]).collect();
+        assert_eq!(open_test_tabits(&permuted), pxyc);
+
+        for (a, d) in permuted.iter_mut() {
+            a.add_consts(&cs, *d);
+        }
+        let pxyc = xor(&pxyc, &cs);
+        assert_eq!(open_test_tabits(&permuted), pxyc);
+
+        // reversing moves bits within their elements, so they no longer line up
+        let (a, _) = &mut permuted[0];
+        assert!(a.add_assign(&tx[0].0).is_err());
+
+        // but bits at the same offset can be added, even from different elements
+        let shifted: Vec<usize> = (8..16).chain(0..8).collect();
+        let mut ta: Vec<_> = tx.iter().map(|(a, d)| (a.slice(&shifted), *d)).collect();
+        let tb: Vec<_> = tx
+            .iter()
+            .map(|(a, d)| (a.slice(&(0..16).collect::<Vec<_>>()), *d))
+            .collect();
+        for ((a, _), (b, _)) in ta.iter_mut().zip(tb.iter()) {
+            a.add_assign(b).unwrap();
+        }
+        let expected: Vec<bool> = (0..16).map(|i| xyc[shifted[i]] ^ xyc[i]).collect();
+        assert_eq!(open_test_tabits(&ta), expected);
+    }
+
+    fn tabit_refresh_test<F: BinaryField + CircuitElement + RandElement + Copy>() {
+        let mut rng = rand::thread_rng();
+        let (n, t, nbits) = (4, 2, 20);
+        let xs: Vec<bool> = (0..nbits).map(|_| rng.gen()).collect();
+        let deltas: Vec<F> = (0..n).map(|_| F::rand(&mut rng)).collect();
+
+        let tx = build_test_tabits(&deltas, t, &xs);
+        let perm: Vec<usize> = (0..nbits).rev().collect();
+        let mut permuted: Vec<_> = tx.iter().map(|(a, d)| (a.permute(&perm), *d)).collect();
+
+        let num_zeros = permuted[0].0.num_shares() * F::BIT_SIZE;
+        let zeros = build_test_tabits(&deltas, t, &vec![false; num_zeros]);
+        for ((a, _), (z, _)) in permuted.iter_mut().zip(zeros.iter()) {
+            let old = a.shares.clone();
+            a.refresh(z);
+            assert_ne!(a.shares, old);
+        }
+
+        let pxs: Vec<bool> = perm.iter().map(|&i| xs[i]).collect();
+        assert_eq!(open_test_tabits(&permuted), pxs);
+    }
+
+    #[test]
+    fn test_tabit_refresh() {
+        tabit_refresh_test::<FF2_8>();
+        tabit_refresh_test::<FF2_64>();
+        tabit_refresh_test::<FF2_128>();
+    }
 
     #[test]
     fn test_tabit_conversion() {
@@ -417,6 +841,7 @@ pub mod tests {
             shares: vec![s1.clone()],
             macs: vec![ms_1],
             keys: vec![ks_1],
+            layout: None,
         };
 
         let tbits_2 = ThreshAbits {
@@ -424,6 +849,7 @@ pub mod tests {
             shares: vec![s2.clone()],
             macs: vec![ms_2],
             keys: vec![ks_2],
+            layout: None,
         };
 
         let bit_idxs: Vec<_> = (0..128).collect();
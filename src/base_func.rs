@@ -13,6 +13,7 @@ pub enum FuncId {
     Fcote = 7,
     Fmult = 8,
     Fabit = 9,
+    Ftriple = 10,
 
     Fnet = 999,
     Ftest = 1000,
@@ -32,6 +33,7 @@ impl From<u16> for FuncId {
             7 => FuncId::Fcote,
             8 => FuncId::Fmult,
             9 => FuncId::Fabit,
+            10 => FuncId::Ftriple,
             999 => FuncId::Fnet,
             1000 => FuncId::Ftest,
             10000 => FuncId::Fcontroller,
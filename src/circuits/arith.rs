@@ -2,11 +2,16 @@ use crate::{
     circuits::{
         builder::new_builder,
         circuit::{Gate, TCircuit},
-        elements::CircuitElement,
+        elements::{bit_basis, CircuitElement},
         get_def_circuit, WireId,
     },
     ff2_128::FF2_128,
+    ff2_64::FF2_64,
+    ff2_8::FF2_8,
+    field::Ring,
+    fp127::FP127,
     rr2_128::RR2_128,
+    z2k::Z2k,
 };
 
 use std::marker::PhantomData;
@@ -234,6 +239,426 @@ impl CircuitRing for RR2_128 {
     }
 }
 
+/// Addition in a binary extension field, just xor
+fn gf2_add_circuit<T: CircuitElement>() -> TCircuit<(T, T), T> {
+    let s = T::BIT_SIZE;
+    let inputs: Vec<_> = (0..2 * s).collect();
+    let gates = (0..s).map(|i| Gate::Xor(i, s + i, 2 * s + i)).collect();
+    let outputs = (2 * s..3 * s).collect();
+
+    TCircuit::from_parts(inputs, gates, outputs)
+}
+
+/// Multiplication in a binary extension field whose elements are represented
+/// as polynomials in x, as a schoolbook carry-less product followed by reduction
+fn gf2_mul_circuit<T: CircuitElement + Ring + Copy>() -> TCircuit<(T, T), T> {
+    let s = T::BIT_SIZE;
+    let inputs: Vec<_> = (0..2 * s).collect();
+    let (a, b) = inputs.split_at(s);
+    let mut w = GateWriter::new(2 * s);
+
+    // p_k = \sum_{i + j = k} a_i b_j
+    let prod: Vec<_> = (0..2 * s - 1)
+        .map(|k| {
+            let terms: Vec<_> = (k.saturating_sub(s - 1)..=k.min(s - 1))
+                .map(|i| w.and(a[i], b[k - i]))
+                .collect();
+            w.xor_all(&terms)
+        })
+        .collect();
+
+    // reduction is linear, so add p_d * (x^d mod f) for each of the high coefficients
+    let basis = bit_basis::<T>();
+    let mut x_d = basis[s - 1] * basis[1];
+    let mut bits = vec![false; s];
+    let mut out_terms: Vec<Vec<_>> = prod[..s].iter().map(|&p| vec![p]).collect();
+    for &p in prod[s..].iter() {
+        x_d.to_bits(&mut bits);
+        for (r, _) in bits.iter().enumerate().filter(|(_, &b)| b) {
+            out_terms[r].push(p);
+        }
+        x_d *= basis[1];
+    }
+
+    let outputs = out_terms.iter().map(|ts| w.xor_all(ts)).collect();
+
+    TCircuit::from_parts(inputs, w.gates, outputs)
+}
+
+/// Polynomial evaluation on constants in a binary extension field.
+/// Multiplying by a constant is linear over F_2, so the whole evaluation is just xor gates
+fn gf2_const_poly_eval_circuit<T: CircuitElement + Ring + Copy>(
+    t: usize,
+    consts: &[T],
+    use_zero_coeff: bool,
+) -> TCircuit<Vec<T>, Vec<T>> {
+    let s = T::BIT_SIZE;
+    let n_coeffs = if use_zero_coeff { t } else { t - 1 };
+    let inputs: Vec<_> = (0..n_coeffs * s).collect();
+    let mut w = GateWriter::new(inputs.len());
+
+    let basis = bit_basis::<T>();
+    let mut bits = vec![false; s];
+    let mut outputs = Vec::with_capacity(s * consts.len());
+
+    for c in consts {
+        let mut out_terms = vec![Vec::new(); s];
+        // c^k where k is the degree of the current coefficient
+        let mut x = if use_zero_coeff { T::one() } else { *c };
+
+        for coeff in inputs.chunks_exact(s) {
+            // bit i of the coefficient contributes x * X^i to the output
+            for (&wire, e) in coeff.iter().zip(basis.iter()) {
+                (x * e).to_bits(&mut bits);
+                for (r, _) in bits.iter().enumerate().filter(|(_, &b)| b) {
+                    out_terms[r].push(wire);
+                }
+            }
+            x *= c;
+        }
+
+        outputs.extend(out_terms.iter().map(|ts| w.xor_all(ts)));
+    }
+
+    TCircuit {
+        _i: PhantomData,
+        _o: PhantomData,
+        inputs,
+        gates: w.gates,
+        outputs,
+    }
+    .well_formed()
+}
+
+impl CircuitRing for FF2_64 {
+    fn add_circuit() -> TCircuit<(Self, Self), Self> {
+        gf2_add_circuit()
+    }
+
+    fn mul_circuit() -> TCircuit<(Self, Self), Self> {
+        gf2_mul_circuit()
+    }
+
+    /// Produces a circuit that given a polynomial, evaluates it on each of a list of constant inputs.
+    /// if `use_zero_coeff` == false then the circuit will ignore the constant coefficient
+    fn const_poly_eval_circuit(
+        t: usize,
+        consts: &[Self],
+        use_zero_coeff: bool,
+    ) -> TCircuit<Vec<Self>, Vec<Self>> {
+        gf2_const_poly_eval_circuit(t, consts, use_zero_coeff)
+    }
+}
+
+impl CircuitRing for FF2_8 {
+    fn add_circuit() -> TCircuit<(Self, Self), Self> {
+        gf2_add_circuit()
+    }
+
+    fn mul_circuit() -> TCircuit<(Self, Self), Self> {
+        gf2_mul_circuit()
+    }
+
+    /// Produces a circuit that given a polynomial, evaluates it on each of a list of constant inputs.
+    /// if `use_zero_coeff` == false then the circuit will ignore the constant coefficient
+    fn const_poly_eval_circuit(
+        t: usize,
+        consts: &[Self],
+        use_zero_coeff: bool,
+    ) -> TCircuit<Vec<Self>, Vec<Self>> {
+        gf2_const_poly_eval_circuit(t, consts, use_zero_coeff)
+    }
+}
+
+/// Helper to write integer arithmetic over little-endian wires as gates,
+/// where new wires are allocated sequentially after the inputs
+struct GateWriter {
+    gates: Vec<Gate>,
+    next_id: WireId,
+    zero: WireId,
+}
+
+impl GateWriter {
+    /// Reserve the first `num_inputs` wires and derive a constant zero wire from the first input
+    fn new(num_inputs: usize) -> Self {
+        // without an input there is nothing to derive the zero wire from,
+        // e.g. a polynomial evaluation with t = 1 that ignores the constant coefficient
+        assert!(num_inputs > 0, "circuit must have at least one input wire");
+        GateWriter {
+            gates: vec![Gate::Xor(0, 0, num_inputs)],
+            next_id: num_inputs + 1,
+            zero: num_inputs,
+        }
+    }
+
+    fn xor(&mut self, x: WireId, y: WireId) -> WireId {
+        self.gates.push(Gate::Xor(x, y, self.next_id));
+        self.next_id += 1;
+        self.next_id - 1
+    }
+
+    /// The xor of all of `xs` on a fresh wire, which is 0 if `xs` is empty
+    fn xor_all(&mut self, xs: &[WireId]) -> WireId {
+        let zero = self.zero;
+        match xs {
+            [] => self.xor(zero, zero),
+            [x] => self.xor(*x, zero),
+            [x, rest @ ..] => rest.iter().fold(*x, |acc, &y| self.xor(acc, y)),
+        }
+    }
+
+    fn and(&mut self, x: WireId, y: WireId) -> WireId {
+        self.gates.push(Gate::And(x, y, self.next_id));
+        self.next_id += 1;
+        self.next_id - 1
+    }
+
+    /// Ripple-carry addition of `x` and `y` where `y` is no wider than `x`.
+    /// The output is one wire wider than `x`.
+    fn add(&mut self, x: &[WireId], y: &[WireId]) -> Vec<WireId> {
+        assert!(y.len() <= x.len());
+        let mut out = Vec::with_capacity(x.len() + 1);
+        let mut carry = None;
+
+        for (i, &xi) in x.iter().enumerate() {
+            match (y.get(i), carry) {
+                (Some(&yi), None) => {
+                    out.push(self.xor(xi, yi));
+                    carry = Some(self.and(xi, yi));
+                }
+                (Some(&yi), Some(c)) => {
+                    // s = x ^ y ^ c, c' = c ^ ((x ^ c) & (y ^ c))
+                    let xc = self.xor(xi, c);
+                    let yc = self.xor(yi, c);
+                    out.push(self.xor(xc, yi));
+                    let t = self.and(xc, yc);
+                    carry = Some(self.xor(c, t));
+                }
+                (None, Some(c)) => {
+                    out.push(self.xor(xi, c));
+                    carry = Some(self.and(xi, c));
+                }
+                (None, None) => out.push(xi),
+            }
+        }
+        out.push(carry.unwrap_or(self.zero));
+
+        out
+    }
+
+    /// Add `y` into the accumulator `acc` which after the addition is the sum of `terms` values
+    /// each less than 2^127, dropping the high wires that are known to be 0.
+    fn accumulate_p127(&mut self, acc: Vec<WireId>, y: &[WireId], terms: usize) -> Vec<WireId> {
+        if acc.is_empty() {
+            return y.to_vec();
+        }
+        let mut s = self.add(&acc, y);
+        s.truncate(127 + (usize::BITS - terms.leading_zeros()) as usize);
+        s
+    }
+
+    /// Reduce the integer x < 2^254 modulo 2^127 - 1 to its canonical representative
+    fn reduce_p127(&mut self, x: &[WireId]) -> Vec<WireId> {
+        assert!(x.len() <= 254);
+        let mut x = x.to_vec();
+        if x.len() < 127 {
+            x.resize(127, self.zero);
+        }
+
+        // as 2^127 = 1 mod p fold the high bits onto the low bits,
+        // the first fold gives a value <= 2^128 - 2 and the second a value <= p
+        if x.len() > 127 {
+            let (lo, hi) = x.split_at(127);
+            x = self.add(lo, hi);
+        }
+        if x.len() > 127 {
+            let (lo, hi) = x.split_at(127);
+            x = self.add(lo, hi);
+            x.truncate(127);
+        }
+
+        // p itself is all ones and should be mapped to 0
+        let mut all_ones = x[0];
+        for &w in x[1..].iter() {
+            all_ones = self.and(all_ones, w);
+        }
+
+        x.into_iter().map(|w| self.xor(w, all_ones)).collect()
+    }
+}
+
+/// The wires of `x` multiplied by 2^j mod 2^127 - 1, which is just a rotation
+fn rotate_p127(x: &[WireId], j: usize) -> Vec<WireId> {
+    let j = j % 127;
+    (0..127).map(|k| x[(k + 127 - j) % 127]).collect()
+}
+
+impl CircuitRing for FP127 {
+    fn add_circuit() -> TCircuit<(Self, Self), Self> {
+        let inputs: Vec<_> = (0..254).collect();
+        let mut w = GateWriter::new(254);
+
+        let s = w.add(&inputs[..127], &inputs[127..]);
+        let outputs = w.reduce_p127(&s);
+
+        TCircuit::from_parts(inputs, w.gates, outputs)
+    }
+
+    fn mul_circuit() -> TCircuit<(Self, Self), Self> {
+        let inputs: Vec<_> = (0..254).collect();
+        let (a, b) = inputs.split_at(127);
+        let mut w = GateWriter::new(254);
+
+        // a * b = \sum_i b_i a 2^i, where each a 2^i is a rotation of a mod p
+        let mut acc = Vec::new();
+        for (i, &bi) in b.iter().enumerate() {
+            let pp: Vec<_> = rotate_p127(a, i)
+                .into_iter()
+                .map(|aj| w.and(bi, aj))
+                .collect();
+            acc = w.accumulate_p127(acc, &pp, i + 1);
+        }
+        let outputs = w.reduce_p127(&acc);
+
+        TCircuit::from_parts(inputs, w.gates, outputs)
+    }
+
+    /// Produces a circuit that given a polynomial, evaluates it on each of a list of constant inputs.
+    /// if `use_zero_coeff` == false then the circuit will ignore the constant coefficient
+    fn const_poly_eval_circuit(
+        t: usize,
+        consts: &[Self],
+        use_zero_coeff: bool,
+    ) -> TCircuit<Vec<Self>, Vec<Self>> {
+        let s = Self::BIT_SIZE;
+        let n_coeffs = if use_zero_coeff { t } else { t - 1 };
+        let inputs: Vec<_> = (0..n_coeffs * s).collect();
+        let mut w = GateWriter::new(inputs.len());
+
+        let mut outputs = Vec::with_capacity(s * consts.len());
+        let mut bits = [false; Self::BIT_SIZE];
+
+        for c in consts {
+            let mut acc = Vec::new();
+            let mut terms = 0;
+
+            let start = if use_zero_coeff {
+                terms += 1;
+                acc = w.accumulate_p127(acc, &inputs[..s], terms);
+                1
+            } else {
+                0
+            };
+
+            // multiplying by the constant c^k is a sum of rotations of the coefficient
+            let mut x = *c;
+            for coeff in inputs.chunks_exact(s).skip(start) {
+                x.to_bits(&mut bits);
+                for (j, _) in bits.iter().enumerate().filter(|(_, &b)| b) {
+                    terms += 1;
+                    acc = w.accumulate_p127(acc, &rotate_p127(coeff, j), terms);
+                }
+                x *= c;
+            }
+
+            outputs.extend(w.reduce_p127(&acc));
+        }
+
+        TCircuit {
+            _i: PhantomData,
+            _o: PhantomData,
+            inputs,
+            gates: w.gates,
+            outputs,
+        }
+        .well_formed()
+    }
+}
+
+impl<const K: usize> CircuitRing for Z2k<K> {
+    fn add_circuit() -> TCircuit<(Self, Self), Self> {
+        let inputs: Vec<_> = (0..2 * K).collect();
+        let mut w = GateWriter::new(2 * K);
+
+        // ripple-carry adder dropping the final carry
+        let mut outputs = w.add(&inputs[..K], &inputs[K..]);
+        outputs.truncate(K);
+
+        TCircuit::from_parts(inputs, w.gates, outputs)
+    }
+
+    fn mul_circuit() -> TCircuit<(Self, Self), Self> {
+        let inputs: Vec<_> = (0..2 * K).collect();
+        let (a, b) = inputs.split_at(K);
+        let mut w = GateWriter::new(2 * K);
+
+        // schoolbook multiplication, a * b = \sum_i b_i a 2^i,
+        // only keeping the low K bits of each partial product
+        let mut acc: Vec<_> = a.iter().map(|&aj| w.and(b[0], aj)).collect();
+        for (i, &bi) in b.iter().enumerate().skip(1) {
+            let pp: Vec<_> = a[..K - i].iter().map(|&aj| w.and(bi, aj)).collect();
+            let mut s = w.add(&acc[i..], &pp);
+            s.truncate(K - i);
+            acc.truncate(i);
+            acc.extend(s);
+        }
+
+        TCircuit::from_parts(inputs, w.gates, acc)
+    }
+
+    /// Produces a circuit that given a polynomial, evaluates it on each of a list of constant inputs.
+    /// if `use_zero_coeff` == false then the circuit will ignore the constant coefficient
+    fn const_poly_eval_circuit(
+        t: usize,
+        consts: &[Self],
+        use_zero_coeff: bool,
+    ) -> TCircuit<Vec<Self>, Vec<Self>> {
+        let n_coeffs = if use_zero_coeff { t } else { t - 1 };
+        let inputs: Vec<_> = (0..n_coeffs * K).collect();
+        let mut w = GateWriter::new(inputs.len());
+
+        let mut outputs = Vec::with_capacity(K * consts.len());
+        let mut bits = vec![false; K];
+
+        for c in consts {
+            let mut acc = vec![w.zero; K];
+
+            let start = if use_zero_coeff {
+                acc.copy_from_slice(&inputs[..K]);
+                1
+            } else {
+                0
+            };
+
+            // multiplying by the constant c^k is a sum of shifts of the coefficient
+            let mut x = *c;
+            for coeff in inputs.chunks_exact(K).skip(start) {
+                x.to_bits(&mut bits);
+                for (j, _) in bits.iter().enumerate().filter(|(_, &b)| b) {
+                    let mut s = w.add(&acc[j..], &coeff[..K - j]);
+                    s.truncate(K - j);
+                    acc.truncate(j);
+                    acc.extend(s);
+                }
+                x *= c;
+            }
+
+            // copy to fresh wires so that outputs never alias inputs or each other
+            let zero = w.zero;
+            outputs.extend(acc.into_iter().map(|a| w.xor(a, zero)));
+        }
+
+        TCircuit {
+            _i: PhantomData,
+            _o: PhantomData,
+            inputs,
+            gates: w.gates,
+            outputs,
+        }
+        .well_formed()
+    }
+}
+
 /// Produces a circuit that given an array of inputs of size `num' outputs the sum
 /// of all of the inputs
 pub fn sum_circuit<T: CircuitRing>(num: usize) -> TCircuit<Vec<T>, T> {
@@ -6,6 +6,7 @@ use crate::{
 use std::{
     collections::{HashMap, HashSet},
     fs::File,
+    hash::{Hash, Hasher},
     io::{BufRead, BufReader, BufWriter, Write},
     marker::PhantomData,
     str::FromStr,
@@ -24,8 +25,9 @@ pub struct Circuit {
 /// and the array of output wires `outputs` can be parsed as `O`.
 #[derive(PartialEq, Eq, Debug)]
 pub struct TCircuit<I, O> {
-    pub(super) _i: PhantomData<I>,
-    pub(super) _o: PhantomData<O>,
+    // the circuit holds no I or O, so it can be shared whatever they are
+    pub(super) _i: PhantomData<fn() -> I>,
+    pub(super) _o: PhantomData<fn() -> O>,
 
     pub inputs: Vec<WireId>,
     pub gates: Vec<Gate>,
@@ -44,6 +46,14 @@ impl<I, O> Clone for TCircuit<I, O> {
     }
 }
 
+impl<I, O> Hash for TCircuit<I, O> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.inputs.hash(state);
+        self.gates.hash(state);
+        self.outputs.hash(state);
+    }
+}
+
 impl<I, O> TCircuit<I, O> {
     /// Convert to a circuit without type information
     pub fn type_erase(self) -> Circuit {
@@ -362,7 +372,7 @@ impl<I: CircuitElement, O: CircuitElement> TCircuit<I, O> {
 }
 
 /// Description of a gate in a circuit, as Op(inputs..., output_id)
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Gate {
     //ConstZero,
     //ConstOne,
@@ -5,8 +5,12 @@ use crate::{
         WireId,
     },
     ff2_128::FF2_128,
+    ff2_64::FF2_64,
+    ff2_8::FF2_8,
     field::ConstInt,
+    fp127::FP127,
     rr2_128::RR2_128,
+    z2k::Z2k,
 };
 
 use std::marker::PhantomData;
@@ -115,6 +119,54 @@ impl CircuitElement for FF2_128 {
     }
 }
 
+impl CircuitElement for FF2_64 {
+    const BIT_SIZE: usize = 64;
+
+    // writes to b as little-endian bits
+    fn to_bits(&self, b: &mut [bool]) {
+        assert!(b.len() >= 64);
+        let x = self.as_u64();
+        for (i, bit) in b[..64].iter_mut().enumerate() {
+            *bit = (x >> i) & 1 == 1;
+        }
+    }
+
+    // reads from b as little-endian bits
+    fn from_bits(b: &[bool]) -> Self {
+        assert!(b.len() >= 64);
+        let x = b[..64]
+            .iter()
+            .enumerate()
+            .fold(0u64, |acc, (i, &bit)| acc | (u64::from(bit) << i));
+
+        Self::new(x)
+    }
+}
+
+impl CircuitElement for FF2_8 {
+    const BIT_SIZE: usize = 8;
+
+    // writes to b as little-endian bits
+    fn to_bits(&self, b: &mut [bool]) {
+        assert!(b.len() >= 8);
+        let x = self.as_u8();
+        for (i, bit) in b[..8].iter_mut().enumerate() {
+            *bit = (x >> i) & 1 == 1;
+        }
+    }
+
+    // reads from b as little-endian bits
+    fn from_bits(b: &[bool]) -> Self {
+        assert!(b.len() >= 8);
+        let x = b[..8]
+            .iter()
+            .enumerate()
+            .fold(0u8, |acc, (i, &bit)| acc | (u8::from(bit) << i));
+
+        Self::new(x)
+    }
+}
+
 impl CircuitElement for RR2_128 {
     const BIT_SIZE: usize = 128;
 
@@ -147,6 +199,54 @@ impl CircuitElement for RR2_128 {
     }
 }
 
+impl CircuitElement for FP127 {
+    const BIT_SIZE: usize = 127;
+
+    // writes to b as little-endian bits of the canonical representative
+    fn to_bits(&self, b: &mut [bool]) {
+        assert!(b.len() >= 127);
+        let x = self.as_u128();
+        for (i, bit) in b[..127].iter_mut().enumerate() {
+            *bit = (x >> i) & 1 == 1;
+        }
+    }
+
+    // reads from b as little-endian bits, the all-ones string reduces to 0
+    fn from_bits(b: &[bool]) -> Self {
+        assert!(b.len() >= 127);
+        let x = b[..127]
+            .iter()
+            .enumerate()
+            .fold(0u128, |acc, (i, &bit)| acc | (u128::from(bit) << i));
+
+        Self::new(x)
+    }
+}
+
+impl<const K: usize> CircuitElement for Z2k<K> {
+    const BIT_SIZE: usize = K;
+
+    // writes to b as little-endian bits
+    fn to_bits(&self, b: &mut [bool]) {
+        assert!(b.len() >= K);
+        let x = self.as_u128();
+        for (i, bit) in b[..K].iter_mut().enumerate() {
+            *bit = (x >> i) & 1 == 1;
+        }
+    }
+
+    // reads from b as little-endian bits
+    fn from_bits(b: &[bool]) -> Self {
+        assert!(b.len() >= K);
+        let x = b[..K]
+            .iter()
+            .enumerate()
+            .fold(0u128, |acc, (i, &bit)| acc | (u128::from(bit) << i));
+
+        Self::new(x)
+    }
+}
+
 impl<T: CircuitElement> CircuitElement for Option<T> {
     const BIT_SIZE: usize = 1 + T::BIT_SIZE;
 
@@ -222,6 +322,19 @@ pub fn compare_all<T: CircuitElement>(num: usize) -> TCircuit<(Vec<T>, Vec<T>),
     }
 }
 
+/// The elements with exactly one bit set, i.e. the element whose bit representation
+/// is the i'th unit vector at index i. For binary extension fields this is X^0 .. X^{k-1}
+pub fn bit_basis<T: CircuitElement>() -> Vec<T> {
+    let mut bits = vec![false; T::BIT_SIZE];
+    (0..T::BIT_SIZE)
+        .map(|i| {
+            bits.fill(false);
+            bits[i] = true;
+            T::from_bits(&bits)
+        })
+        .collect()
+}
+
 /// Produces a circuit that just reinterprets its input as an array of bits
 /// instead of any particular integer
 pub fn bit_decompose<T: CircuitElement, const S: usize>() -> TCircuit<T, [bool; S]> {
@@ -296,3 +409,38 @@ pub fn hardcode_input<I: CircuitElement, I2: CircuitElement, O>(
 
     builder.refine_input().refine_output(&out).to_circuit()
 }
+
+/// Where an input of a circuit rewired by `wire_inputs` comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputWire {
+    /// The given input of the rewired circuit
+    Input(usize),
+    /// A constant bit
+    Const(bool),
+}
+
+/// Produces the circuit that takes `num_inputs` input bits and evaluates `circuit` with its
+/// i-th input taken from `wiring[i]`. Inputs can feed any number of positions, and the
+/// positions with constants no longer need an input, as in `hardcode_input`.
+pub fn wire_inputs<I, O>(
+    num_inputs: usize,
+    wiring: &[InputWire],
+    circuit: &TCircuit<I, O>,
+) -> TCircuit<Vec<bool>, O> {
+    assert!(wiring.len() == circuit.inputs.len());
+    let builder = new_builder().with_consts();
+    let consts = builder.get_const_wire_ids().unwrap();
+    let (builder, inputs) = builder.add_input_multi::<Vec<bool>>(num_inputs, None);
+
+    let wires: Vec<_> = wiring
+        .iter()
+        .map(|w| match *w {
+            InputWire::Input(i) => inputs[i],
+            InputWire::Const(b) => consts[if b { 1 } else { 0 }],
+        })
+        .collect();
+
+    let (builder, out) = builder.extend_circuit(&wires[..], circuit, None);
+
+    builder.refine_input().refine_output(&out).to_circuit()
+}
@@ -6,11 +6,18 @@ pub fn execute_circuit_inner<I, O, FI: Fn(&I, &mut [bool]), FO: Fn(&[bool]) -> O
     circuit: &TCircuit<I, O>,
     parse_fun: FO,
 ) -> O {
+    let mut bits = vec![false; circuit.inputs.len()];
+    encode_fun(input, bits.as_mut_slice());
+    parse_fun(execute_circuit_bits(&bits, circuit).as_slice())
+}
+
+/// Run `circuit` on its input wires `input`, giving its output wires
+pub fn execute_circuit_bits<I, O>(input: &[bool], circuit: &TCircuit<I, O>) -> Vec<bool> {
     let num_wires = circuit.inputs.len() + circuit.gates.len();
     let mut wires: Vec<bool> = Vec::with_capacity(num_wires);
     wires.resize(num_wires, false);
 
-    encode_fun(input, wires.as_mut_slice());
+    wires[..input.len()].copy_from_slice(input);
 
     for g in circuit.gates.iter() {
         match g {
@@ -26,8 +33,7 @@ pub fn execute_circuit_inner<I, O, FI: Fn(&I, &mut [bool]), FO: Fn(&[bool]) -> O
         };
     }
 
-    let outputs: Vec<bool> = circuit.outputs.iter().map(|&o| wires[o]).collect();
-    parse_fun(outputs.as_slice())
+    circuit.outputs.iter().map(|&o| wires[o]).collect()
 }
 
 pub fn execute_circuit<I: CircuitElement, O: CircuitElement>(
@@ -70,9 +76,13 @@ mod tests {
             },
         },
         ff2_128::FF2_128,
+        ff2_64::FF2_64,
+        ff2_8::FF2_8,
         field::{ConstInt, RandElement},
-        polynomial::{InterpolationPolynomial, Polynomial},
+        fp127::FP127,
+        polynomial::{FixedPolynomial, InterpolationPolynomial, Polynomial},
         rr2_128::RR2_128,
+        z2k::{Z2k, Z2_64},
     };
 
     use rand;
@@ -129,6 +139,191 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_ff2_64_cir() {
+        let add = FF2_64::add_circuit();
+        let mul = FF2_64::mul_circuit();
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..20 {
+            let x = FF2_64::rand(&mut rng);
+            let y = FF2_64::rand(&mut rng);
+            assert_eq!(x + y, execute_circuit(&(x, y), &add));
+            assert_eq!(x * y, execute_circuit(&(x, y), &mul));
+        }
+    }
+
+    #[test]
+    fn test_ff2_8_cir() {
+        let add = FF2_8::add_circuit();
+        let mul = FF2_8::mul_circuit();
+
+        // exhaustive for a sample of x
+        for x in (0..=255).step_by(7) {
+            for y in 0..=255 {
+                let (x, y) = (FF2_8::new(x), FF2_8::new(y));
+                assert_eq!(x + y, execute_circuit(&(x, y), &add));
+                assert_eq!(x * y, execute_circuit(&(x, y), &mul));
+            }
+        }
+    }
+
+    #[test]
+    fn test_ff2_small_const_poly_eval_cir() {
+        let mut rng = rand::thread_rng();
+        let t = 3;
+
+        let points: Vec<_> = (1..=4).map(|i| FF2_64::from(i)).collect();
+        let coeffs: Vec<_> = (0..t).map(|_| FF2_64::rand(&mut rng)).collect();
+        for use_zero_coeff in [true, false] {
+            let c = FF2_64::const_poly_eval_circuit(t, &points, use_zero_coeff);
+            let start = if use_zero_coeff { 0 } else { 1 };
+            let ins = coeffs[start..].to_vec();
+            let zs = execute_circuit_mxm(&ins, ins.len(), &c, points.len());
+
+            for (x, z) in points.iter().zip(zs.iter()) {
+                let mut p = FixedPolynomial::new(coeffs.clone()).evaluate(x);
+                if !use_zero_coeff {
+                    p -= coeffs[0];
+                }
+                assert_eq!(p, *z);
+            }
+        }
+
+        let points: Vec<_> = (1..=4).map(|i| FF2_8::from(i)).collect();
+        let coeffs: Vec<_> = (0..t).map(|_| FF2_8::rand(&mut rng)).collect();
+        for use_zero_coeff in [true, false] {
+            let c = FF2_8::const_poly_eval_circuit(t, &points, use_zero_coeff);
+            let start = if use_zero_coeff { 0 } else { 1 };
+            let ins = coeffs[start..].to_vec();
+            let zs = execute_circuit_mxm(&ins, ins.len(), &c, points.len());
+
+            for (x, z) in points.iter().zip(zs.iter()) {
+                let mut p = FixedPolynomial::new(coeffs.clone()).evaluate(x);
+                if !use_zero_coeff {
+                    p -= coeffs[0];
+                }
+                assert_eq!(p, *z);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fp127_add_cir() {
+        let c = FP127::add_circuit();
+        let mut rng = rand::thread_rng();
+
+        let m = FP127::zero() - FP127::one();
+        let mut cases = vec![(m, m), (m, FP127::one()), (FP127::zero(), FP127::zero())];
+        cases.extend((0..20).map(|_| (FP127::rand(&mut rng), FP127::rand(&mut rng))));
+
+        for (x, y) in cases {
+            let z = execute_circuit(&(x, y), &c);
+            assert_eq!(x + y, z);
+        }
+    }
+
+    #[test]
+    fn test_fp127_mul_cir() {
+        let c = FP127::mul_circuit();
+        let mut rng = rand::thread_rng();
+
+        let m = FP127::zero() - FP127::one();
+        let mut cases = vec![(m, m), (m, FP127::one()), (FP127::zero(), m)];
+        cases.extend((0..20).map(|_| (FP127::rand(&mut rng), FP127::rand(&mut rng))));
+
+        for (x, y) in cases {
+            let z = execute_circuit(&(x, y), &c);
+            assert_eq!(x * y, z);
+        }
+    }
+
+    #[test]
+    fn test_fp127_const_poly_eval_cir() {
+        let mut rng = rand::thread_rng();
+        let t = 3;
+        let points: Vec<_> = (1..=4).map(|i| FP127::from(i)).collect();
+        let coeffs: Vec<_> = (0..t).map(|_| FP127::rand(&mut rng)).collect();
+
+        for use_zero_coeff in [true, false] {
+            let c = FP127::const_poly_eval_circuit(t, &points, use_zero_coeff);
+            let ins = if use_zero_coeff {
+                coeffs.clone()
+            } else {
+                coeffs[1..].to_vec()
+            };
+            let zs = execute_circuit_mxm(&ins, ins.len(), &c, points.len());
+
+            for (x, z) in points.iter().zip(zs.iter()) {
+                let mut p = FixedPolynomial::new(coeffs.clone()).evaluate(x);
+                if !use_zero_coeff {
+                    p -= coeffs[0];
+                }
+                assert_eq!(p, *z);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one input wire")]
+    fn test_fp127_const_poly_eval_cir_no_inputs() {
+        // t = 1 without the constant coefficient leaves no input to build the circuit from
+        let points: Vec<_> = (1..=4).map(|i| FP127::from(i)).collect();
+        let _ = FP127::const_poly_eval_circuit(1, &points, false);
+    }
+
+    #[test]
+    fn test_z2k_add_mul_cir() {
+        let mut rng = rand::thread_rng();
+
+        let m = Z2_64::zero() - Z2_64::one();
+        let mut cases = vec![(m, m), (m, Z2_64::one()), (Z2_64::zero(), m)];
+        cases.extend((0..20).map(|_| (Z2_64::rand(&mut rng), Z2_64::rand(&mut rng))));
+
+        let add = Z2_64::add_circuit();
+        let mul = Z2_64::mul_circuit();
+        for (x, y) in cases {
+            assert_eq!(x + y, execute_circuit(&(x, y), &add));
+            assert_eq!(x * y, execute_circuit(&(x, y), &mul));
+        }
+
+        // an odd sized ring
+        let add = Z2k::<13>::add_circuit();
+        let mul = Z2k::<13>::mul_circuit();
+        for _ in 0..20 {
+            let x = Z2k::<13>::rand(&mut rng);
+            let y = Z2k::<13>::rand(&mut rng);
+            assert_eq!(x + y, execute_circuit(&(x, y), &add));
+            assert_eq!(x * y, execute_circuit(&(x, y), &mul));
+        }
+    }
+
+    #[test]
+    fn test_z2k_const_poly_eval_cir() {
+        let mut rng = rand::thread_rng();
+        let t = 3;
+        let points: Vec<_> = (1..=4).map(|i| Z2_64::from(i)).collect();
+        let coeffs: Vec<_> = (0..t).map(|_| Z2_64::rand(&mut rng)).collect();
+
+        for use_zero_coeff in [true, false] {
+            let c = Z2_64::const_poly_eval_circuit(t, &points, use_zero_coeff);
+            let ins = if use_zero_coeff {
+                coeffs.clone()
+            } else {
+                coeffs[1..].to_vec()
+            };
+            let zs = execute_circuit_mxm(&ins, ins.len(), &c, points.len());
+
+            for (x, z) in points.iter().zip(zs.iter()) {
+                let start = if use_zero_coeff { 0 } else { 1 };
+                let p: Z2_64 = (start..t)
+                    .map(|k| coeffs[k] * (0..k).map(|_| *x).product::<Z2_64>())
+                    .sum();
+                assert_eq!(p, *z);
+            }
+        }
+    }
+
     #[test]
     fn test_sum_circuit() {
         let c = sum_circuit::<FF2_128>(10);
@@ -42,6 +42,34 @@ fn input_sample_inner<T: CircuitRing, I, O>(
     let sum_c = sum_circuit::<T>(num_parties);
     let (builder, alpha) = builder.extend_circuit(&alphas, &sum_c, Some("alpha"));
 
+    let (builder, outs) = auth_share_inner(
+        builder,
+        num_parties,
+        num_shares,
+        threshold,
+        points,
+        alpha,
+        xs,
+        zs_s,
+    );
+
+    builder
+        .refine_output::<Vec<Vec<(T, T)>>>(&outs)
+        .to_circuit()
+}
+
+/// Secret share each of `xs` and its MAC under `alpha` using the zero polynomials
+/// summed from `zs_s`, returning the output wires with each party's shares together
+fn auth_share_inner<T: CircuitRing, I, O>(
+    builder: CircuitBuilder<I, O>,
+    num_parties: usize,
+    num_shares: usize,
+    threshold: usize,
+    points: &[T],
+    alpha: Vec<WireId>,
+    xs: &[WireId],
+    zs_s: &[WireId],
+) -> (CircuitBuilder<I, ()>, Vec<WireId>) {
     // sum zs
     let sum_zs_c = sum_many_circuit::<T>(2 * num_shares * (threshold - 1), num_parties);
     let (builder, fs) = builder.extend_circuit(&zs_s, &sum_zs_c, None);
@@ -103,11 +131,13 @@ fn input_sample_inner<T: CircuitRing, I, O>(
         }
     }
 
-    let builder = builder.refine_output::<Vec<Vec<(T, T)>>>(&new_outs);
-
-    builder.to_circuit()
+    (builder.refine_output::<()>(&[]), new_outs)
 }
 
+/// The inputs of the circuit followed by each party's share of alpha and zero polynomials,
+/// then each party's authenticated shares of the outputs
+pub type SetupCircuit<T, I> = TCircuit<(I, Vec<(T, Vec<T>)>), Vec<Vec<(T, T)>>>;
+
 /// The circuit used for producing authenticated shares in the `setup` instruction
 /// of Fthresh using the generic threshold protocol
 pub fn setup_circuit<T: CircuitRing, I, O: CircuitElement>(
@@ -116,11 +146,23 @@ pub fn setup_circuit<T: CircuitRing, I, O: CircuitElement>(
     threshold: usize,
     points: &[T],
     circuit: &TCircuit<I, O>,
-) -> TCircuit<(I, Vec<(T, Vec<T>)>), Vec<Vec<(T, T)>>> {
+) -> SetupCircuit<T, I> {
+    setup_circuit_with_sizes(&vec![num_inputs; num_parties], threshold, points, circuit)
+}
+
+/// The `setup` circuit where the `i`th party provides `input_sizes[i]` of the inputs,
+/// which may be none at all
+pub fn setup_circuit_with_sizes<T: CircuitRing, I, O: CircuitElement>(
+    input_sizes: &[usize],
+    threshold: usize,
+    points: &[T],
+    circuit: &TCircuit<I, O>,
+) -> SetupCircuit<T, I> {
+    let num_parties = input_sizes.len();
     let builder = new_builder().with_consts();
     let consts = builder.get_const_wire_ids().unwrap();
 
-    let (builder, xs) = builder.add_input_multi::<Vec<bool>>(num_parties * num_inputs, None);
+    let (builder, xs) = builder.add_input_multi::<Vec<bool>>(input_sizes.iter().sum(), None);
     let mut builder = builder.refine_input::<(I, Vec<(T, Vec<T>)>)>();
     // each party inputs its share of alpha, 2*num_inputs*num_parties shares of zero
     let out_size = O::BIT_SIZE;
@@ -198,43 +240,66 @@ pub fn sample_circuit<T: CircuitRing>(
     )
 }
 
-/// Takes an arbitrary circuit with field element inputs with arbitrary output
-/// then creates a circuit that takes in authenticated inputs, verifies the inputs,
-/// and then returns an additional bit whether the input verification passed.
-pub fn add_validation_project<T: CircuitRing, I, O>(
+/// The circuit used for re-randomizing shares in the `refresh` instruction of Fthresh
+/// using the generic threshold protocol. Each party inputs random coefficients and gets its
+/// shares of 1 + 2 * num_shares fresh sharings of zero, for alpha and for each (x, ax) pair.
+pub fn refresh_circuit<T: CircuitRing>(
     num_parties: usize,
     num_shares: usize,
-    bit_selection: &[usize],
-    c: &TCircuit<I, O>,
-) -> TCircuit<Vec<(T, Vec<T>, Vec<T>)>, Option<O>> {
+    threshold: usize,
+    points: &[T],
+) -> TCircuit<Vec<Vec<T>>, Vec<Vec<T>>> {
+    let num_zeros = 1 + 2 * num_shares;
     let builder = new_builder();
-    // the values are input are (\shares{(a, \vec{xs}, \vec{m})}_1, \shares{(...)}_2, ...)
-    // where each party has all of its input wires adjacent.
-    let mut builder = builder.refine_input::<Vec<(T, Vec<T>, Vec<T>)>>();
-    let mut alpha_shares_ids = Vec::with_capacity(T::BIT_SIZE * num_parties);
-    let mut inputs_shares_ids = Vec::with_capacity(T::BIT_SIZE * num_parties * num_shares);
-    let mut macs_shares_ids = Vec::with_capacity(T::BIT_SIZE * num_parties * num_shares);
-    for _ in 0..num_parties {
-        let (b2, alpha_i) = builder.add_input::<T>(None);
-        let (b2, xs_i) = b2.add_input_multi::<Vec<T>>(num_shares, None);
-        let (b2, macs_i) = b2.add_input_multi::<Vec<T>>(num_shares, None);
-        // coalesce the input
-        builder = b2.refine_input::<Vec<(T, Vec<T>, Vec<T>)>>();
+    // each party inputs the non-constant coefficients of its zero polynomials
+    let (builder, zs_s) =
+        builder.add_input_multi::<Vec<T>>(num_parties * num_zeros * (threshold - 1), None);
 
-        alpha_shares_ids.extend(alpha_i);
-        inputs_shares_ids.extend(xs_i);
-        macs_shares_ids.extend(macs_i);
+    let sum_zs_c = sum_many_circuit::<T>(num_zeros * (threshold - 1), num_parties);
+    let (builder, fs) = builder.extend_circuit(&zs_s, &sum_zs_c, None);
+
+    let const_poly_eval_c = T::const_poly_eval_circuit(threshold, points, false);
+    let (builder, zs) = builder.map_circuit(
+        &fs,
+        &const_poly_eval_c,
+        num_zeros,
+        |chunk| chunk.to_vec(),
+        None,
+    );
+
+    // zs is [0_1]_1 || [0_1]_2 || ... want all of a party's shares together
+    let mut outs = Vec::with_capacity(zs.len());
+    let stride = T::BIT_SIZE * num_parties;
+    for i in 0..num_parties {
+        for j in 0..num_zeros {
+            let start = j * stride + i * T::BIT_SIZE;
+            outs.extend(&zs[start..start + T::BIT_SIZE]);
+        }
     }
 
+    builder.refine_input().refine_output(&outs).to_circuit()
+}
+
+/// Reconstruct alpha, the xs and their MACs from the parties' additive shares and check the
+/// MACs, returning the wires of alpha, of the xs and of a bit that is set if all of the MACs
+/// are valid
+fn validate_inner<T: CircuitRing, I, O>(
+    builder: CircuitBuilder<I, O>,
+    num_parties: usize,
+    num_shares: usize,
+    alpha_shares_ids: &[WireId],
+    inputs_shares_ids: &[WireId],
+    macs_shares_ids: &[WireId],
+) -> (CircuitBuilder<I, O>, Vec<WireId>, Vec<WireId>, Vec<WireId>) {
     // Calculate the value of the mac key alpha by summing all of the shares
     let sum_p = sum_circuit::<T>(num_parties);
-    let (builder, alpha_ids) = builder.extend_circuit(&alpha_shares_ids, &sum_p, Some("alpha"));
+    let (builder, alpha_ids) = builder.extend_circuit(alpha_shares_ids, &sum_p, Some("alpha"));
 
     // Reconstruct all of the values of the xs and all of the values of the macs
     let sum_x = sum_many_circuit::<T>(num_shares, num_parties);
 
-    let (builder, xs_ids) = builder.extend_circuit(&inputs_shares_ids, &sum_x, Some("xs"));
-    let (builder, macs_ids) = builder.extend_circuit(&macs_shares_ids, &sum_x, Some("macs"));
+    let (builder, xs_ids) = builder.extend_circuit(inputs_shares_ids, &sum_x, Some("xs"));
+    let (builder, macs_ids) = builder.extend_circuit(macs_shares_ids, &sum_x, Some("macs"));
 
     // Re-calculate the MAC alpha * \vec{xs}
     let lin_x = linear::<T>(num_shares);
@@ -247,24 +312,204 @@ pub fn add_validation_project<T: CircuitRing, I, O>(
     let (builder, linear_ids) = builder.extend_circuit(&alpha_xs_ids, &lin_x, Some("check_macs"));
 
     // Check that all of the MACs are valid
-    let checks_ids: Vec<_> = linear_ids.iter().cloned().chain(macs_ids.clone()).collect();
+    let checks_ids: Vec<_> = linear_ids.iter().cloned().chain(macs_ids).collect();
     let compare_n = compare_all::<T>(num_shares);
 
     let (builder, check_id) = builder.extend_circuit(&checks_ids, &compare_n, Some("valid_bit"));
 
-    // gather the bits of input from all of the field elements
-    let proj_xs: Vec<_> = bit_selection.iter().map(|&i| xs_ids[i].clone()).collect();
+    (builder, alpha_ids, xs_ids, check_id)
+}
 
-    // Actually compute the original circuit
-    let (builder, original_output) = builder.extend_circuit(&proj_xs, c, Some("original_output"));
+/// The circuit used for moving authenticated shares to a new committee in the `reshare`
+/// instruction of Fthresh using the generic threshold protocol. The old parties input their
+/// shares as in `add_validation_project`, and each new party inputs a random polynomial for
+/// the new MAC key and 2 * l zero polynomials, where l is the number of elements needed to
+/// pack the `bit_selection` bits of the xs. The output is whether validation passed followed by,
+/// for each new party, its share of the new MAC key and its authenticated shares of the selected
+/// bits, with everything but the validity bit zeroed if validation failed.
+pub fn reshare_circuit<T: CircuitRing>(
+    num_old: usize,
+    num_shares: usize,
+    bit_selection: &[usize],
+    new_threshold: usize,
+    new_points: &[T],
+) -> ReshareCircuit<T> {
+    let num_new = new_points.len();
+    let num_new_shares = bit_selection.len().div_ceil(T::BIT_SIZE);
 
-    // Mask the output if the MAC check failed
+    let builder = new_builder().with_consts();
+    let consts = builder.get_const_wire_ids().unwrap();
+    let mut builder = builder.refine_input::<ReshareInput<T>>();
+
+    let mut alpha_shares_ids = Vec::with_capacity(T::BIT_SIZE * num_old);
+    let mut inputs_shares_ids = Vec::with_capacity(T::BIT_SIZE * num_old * num_shares);
+    let mut macs_shares_ids = Vec::with_capacity(T::BIT_SIZE * num_old * num_shares);
+    for _ in 0..num_old {
+        let (b2, alpha_i) = builder.add_input::<T>(None);
+        let (b2, xs_i) = b2.add_input_multi::<Vec<T>>(num_shares, None);
+        let (b2, macs_i) = b2.add_input_multi::<Vec<T>>(num_shares, None);
+        builder = b2.refine_input();
+
+        alpha_shares_ids.extend(alpha_i);
+        inputs_shares_ids.extend(xs_i);
+        macs_shares_ids.extend(macs_i);
+    }
+
+    let num_zs = 2 * num_new_shares * (new_threshold - 1);
+    let mut new_alpha_fs = Vec::with_capacity(T::BIT_SIZE * num_new * new_threshold);
+    let mut zs_s = Vec::with_capacity(T::BIT_SIZE * num_new * num_zs);
+    for _ in 0..num_new {
+        let (b2, fs_i) = builder.add_input_multi::<Vec<T>>(new_threshold, None);
+        let (b2, zs_i) = b2.add_input_multi::<Vec<T>>(num_zs, None);
+        builder = b2.refine_input();
+
+        new_alpha_fs.extend(fs_i);
+        zs_s.extend(zs_i);
+    }
+
+    let (builder, _, xs_ids, check_id) = validate_inner::<T, _, _>(
+        builder,
+        num_old,
+        num_shares,
+        &alpha_shares_ids,
+        &inputs_shares_ids,
+        &macs_shares_ids,
+    );
+
+    // the new MAC key is the constant coefficient of the summed polynomials
+    let sum_fs_c = sum_many_circuit::<T>(new_threshold, num_new);
+    let (builder, fs) = builder.extend_circuit(&new_alpha_fs, &sum_fs_c, None);
+    let const_poly_eval_c = T::const_poly_eval_circuit(new_threshold, new_points, true);
+    let (builder, new_alpha_shares) = builder.extend_circuit(&fs, &const_poly_eval_c, None);
+    let new_alpha = fs[..T::BIT_SIZE].to_vec();
+
+    // gather the selected bits into new densely packed elements
+    let mut ys: Vec<_> = bit_selection.iter().map(|&i| xs_ids[i]).collect();
+    ys.resize(T::BIT_SIZE * num_new_shares, consts[0]);
+
+    let (builder, outs) = auth_share_inner(
+        builder,
+        num_new,
+        num_new_shares,
+        new_threshold,
+        new_points,
+        new_alpha,
+        &ys,
+        &zs_s,
+    );
+
+    // every new party gets its share of the new MAC key followed by its shares
+    let party_outs = 2 * T::BIT_SIZE * num_new_shares;
     let mask_ids: Vec<_> = check_id
         .iter()
         .cloned()
-        .chain(original_output.into_iter())
+        .chain(
+            new_alpha_shares
+                .chunks_exact(T::BIT_SIZE)
+                .zip(outs.chunks_exact(party_outs))
+                .flat_map(|(alpha_i, outs_i)| alpha_i.iter().chain(outs_i.iter()).cloned()),
+        )
         .collect();
 
+    let mask_c = mask::<Vec<(T, Vec<(T, T)>)>>(mask_ids.len() - 1);
+    let (builder, masked_output_ids) =
+        builder.extend_circuit(&mask_ids, &mask_c, Some("masked_output"));
+
+    builder.refine_output(&masked_output_ids).to_circuit()
+}
+
+/// The circuit used to check that parties agree on some bits before revealing them, e.g. the
+/// layout of the shares being moved by `reshare` to the new committee. Each of the
+/// `num_parties` parties inputs `num_bits` bits, and the output is the first party's bits
+/// if every party input the same bits, and `None` otherwise.
+pub fn layout_circuit(
+    num_parties: usize,
+    num_bits: usize,
+) -> TCircuit<Vec<Vec<bool>>, Option<Vec<bool>>> {
+    let builder = new_builder().with_consts();
+    let consts = builder.get_const_wire_ids().unwrap();
+    let (builder, ids) = builder.add_input_multi::<Vec<bool>>(num_parties * num_bits, None);
+    let mut builder = builder.refine_input::<Vec<Vec<bool>>>();
+
+    let compare_c = compare_all::<bool>(num_bits);
+    let and_c = mask::<bool>(1);
+    let first = &ids[..num_bits];
+
+    // AND together whether each party agrees with the first
+    let mut valid = consts[1];
+    for other in ids.chunks_exact(num_bits).skip(1) {
+        let compare_ids: Vec<_> = first.iter().chain(other.iter()).cloned().collect();
+        let (b2, same) = builder.extend_circuit(&compare_ids, &compare_c, None);
+        let (b2, and) = b2.extend_circuit(&[same[0], valid], &and_c, None);
+        builder = b2;
+        valid = and[1];
+    }
+
+    let mask_ids: Vec<_> = [valid].iter().chain(first.iter()).cloned().collect();
+    let mask_c = mask::<Vec<bool>>(num_bits);
+    let (builder, masked_output_ids) =
+        builder.extend_circuit(&mask_ids, &mask_c, Some("masked_output"));
+
+    builder.refine_output(&masked_output_ids).to_circuit()
+}
+
+/// The inputs of the old parties then those of the new parties to `reshare_circuit`
+pub type ReshareInput<T> = (Vec<(T, Vec<T>, Vec<T>)>, Vec<(Vec<T>, Vec<T>)>);
+
+pub type ReshareCircuit<T> = TCircuit<ReshareInput<T>, Option<Vec<(T, Vec<(T, T)>)>>>;
+
+/// Takes an arbitrary circuit with field element inputs with arbitrary output
+/// then creates a circuit that takes in authenticated inputs, verifies the inputs,
+/// and then returns an additional bit whether the input verification passed.
+pub fn add_validation_project<T: CircuitRing, I, O>(
+    num_parties: usize,
+    num_shares: usize,
+    bit_selection: &[usize],
+    c: &TCircuit<I, O>,
+) -> TCircuit<Vec<(T, Vec<T>, Vec<T>)>, Option<O>> {
+    let (builder, xs_ids, check_id) = validated_inputs::<T>(num_parties, num_shares);
+
+    project_validated(builder, &xs_ids, &check_id, bit_selection, &[], c)
+}
+
+/// Like `add_validation_project`, but `c` also takes `num_plain` unauthenticated input bits
+/// after the selected bits, which are input after all of the authenticated shares
+pub fn add_validation_project_plain<T: CircuitRing, I, O>(
+    num_parties: usize,
+    num_shares: usize,
+    bit_selection: &[usize],
+    num_plain: usize,
+    c: &TCircuit<I, O>,
+) -> TCircuit<(AuthInput<T>, Vec<bool>), Option<O>> {
+    let (builder, xs_ids, check_id) = validated_inputs::<T>(num_parties, num_shares);
+    let (builder, plain_ids) = builder.add_input_multi::<Vec<bool>>(num_plain, None);
+
+    project_validated(builder, &xs_ids, &check_id, bit_selection, &plain_ids, c)
+}
+
+/// Compute `c` over the `bit_selection` bits of the validated xs followed by `plain_ids`,
+/// masking the output if the MAC check failed
+fn project_validated<I2, I, O>(
+    builder: CircuitBuilder<I2, ()>,
+    xs_ids: &[WireId],
+    check_id: &[WireId],
+    bit_selection: &[usize],
+    plain_ids: &[WireId],
+    c: &TCircuit<I, O>,
+) -> TCircuit<I2, Option<O>> {
+    // gather the bits of input from all of the field elements
+    let proj_xs: Vec<_> = bit_selection
+        .iter()
+        .map(|&i| xs_ids[i])
+        .chain(plain_ids.iter().cloned())
+        .collect();
+
+    // Actually compute the original circuit
+    let (builder, original_output) = builder.extend_circuit(&proj_xs, c, Some("original_output"));
+
+    // Mask the output if the MAC check failed
+    let mask_ids: Vec<_> = check_id.iter().cloned().chain(original_output).collect();
+
     let mask_c = mask::<O>(mask_ids.len() - 1);
     let (builder, masked_output_ids) =
         builder.extend_circuit(&mask_ids, &mask_c, Some("masked_output"));
@@ -275,14 +520,175 @@ pub fn add_validation_project<T: CircuitRing, I, O>(
     builder.to_circuit()
 }
 
+/// Each party's shares of the MAC key, of the inputs and of their MACs
+pub type AuthInput<T> = Vec<(T, Vec<T>, Vec<T>)>;
+
+/// Like `add_validation_project`, but outputs the selected bits themselves rather than
+/// the output of a circuit over them
+pub fn reveal_circuit<T: CircuitRing>(
+    num_parties: usize,
+    num_shares: usize,
+    bit_selection: &[usize],
+) -> TCircuit<AuthInput<T>, Option<Vec<bool>>> {
+    let (builder, xs_ids, check_id) = validated_inputs::<T>(num_parties, num_shares);
+
+    // masking also keeps the input wires apart from the output wires
+    let mask_ids: Vec<_> = check_id
+        .iter()
+        .chain(bit_selection.iter().map(|&i| &xs_ids[i]))
+        .cloned()
+        .collect();
+
+    let mask_c = mask::<Vec<bool>>(bit_selection.len());
+    let (builder, masked_output_ids) =
+        builder.extend_circuit(&mask_ids, &mask_c, Some("masked_output"));
+
+    builder.refine_output(&masked_output_ids).to_circuit()
+}
+
+/// The inputs of the evaluating parties then the zero polynomials of every party to
+/// `eval_state_circuit`
+pub type EvalStateInput<T> = (AuthInput<T>, Vec<Vec<T>>);
+
+pub type EvalStateCircuit<T> = TCircuit<EvalStateInput<T>, Option<Vec<Vec<(T, T)>>>>;
+
+/// The circuit used for evaluating a circuit over stored inputs into new authenticated
+/// shares in the `eval_to_state` instruction of Fthresh using the generic threshold protocol.
+/// The `num_evals` evaluating parties input their shares as in `add_validation_project`, and
+/// each of the parties at `points` inputs 2 * l zero polynomials, where l is the number of
+/// elements needed to pack the outputs of `c`. The output is whether validation passed
+/// followed by each party's authenticated shares of the outputs, with everything but the
+/// validity bit zeroed if validation failed.
+pub fn eval_state_circuit<T: CircuitRing, I, O>(
+    num_evals: usize,
+    num_shares: usize,
+    bit_selection: &[usize],
+    threshold: usize,
+    points: &[T],
+    c: &TCircuit<I, O>,
+) -> EvalStateCircuit<T> {
+    let num_parties = points.len();
+    let num_out_shares = c.outputs.len().div_ceil(T::BIT_SIZE);
+
+    let builder = new_builder().with_consts();
+    let consts = builder.get_const_wire_ids().unwrap();
+    let mut builder = builder.refine_input::<EvalStateInput<T>>();
+
+    let mut alpha_shares_ids = Vec::with_capacity(T::BIT_SIZE * num_evals);
+    let mut inputs_shares_ids = Vec::with_capacity(T::BIT_SIZE * num_evals * num_shares);
+    let mut macs_shares_ids = Vec::with_capacity(T::BIT_SIZE * num_evals * num_shares);
+    for _ in 0..num_evals {
+        let (b2, alpha_i) = builder.add_input::<T>(None);
+        let (b2, xs_i) = b2.add_input_multi::<Vec<T>>(num_shares, None);
+        let (b2, macs_i) = b2.add_input_multi::<Vec<T>>(num_shares, None);
+        builder = b2.refine_input();
+
+        alpha_shares_ids.extend(alpha_i);
+        inputs_shares_ids.extend(xs_i);
+        macs_shares_ids.extend(macs_i);
+    }
+
+    let num_zs = 2 * num_out_shares * (threshold - 1);
+    let mut zs_s = Vec::with_capacity(T::BIT_SIZE * num_parties * num_zs);
+    for _ in 0..num_parties {
+        let (b2, zs_i) = builder.add_input_multi::<Vec<T>>(num_zs, None);
+        builder = b2.refine_input();
+
+        zs_s.extend(zs_i);
+    }
+
+    let (builder, alpha, xs_ids, check_id) = validate_inner::<T, _, _>(
+        builder,
+        num_evals,
+        num_shares,
+        &alpha_shares_ids,
+        &inputs_shares_ids,
+        &macs_shares_ids,
+    );
+
+    // compute the original circuit over the selected bits, then pack its outputs
+    let proj_xs: Vec<_> = bit_selection.iter().map(|&i| xs_ids[i]).collect();
+    let (builder, mut ys) = builder.extend_circuit(&proj_xs, c, Some("original_output"));
+    ys.resize(T::BIT_SIZE * num_out_shares, consts[0]);
+
+    let (builder, outs) = auth_share_inner(
+        builder,
+        num_parties,
+        num_out_shares,
+        threshold,
+        points,
+        alpha,
+        &ys,
+        &zs_s,
+    );
+
+    let mask_ids: Vec<_> = check_id.iter().chain(outs.iter()).cloned().collect();
+    let mask_c = mask::<Vec<Vec<(T, T)>>>(mask_ids.len() - 1);
+    let (builder, masked_output_ids) =
+        builder.extend_circuit(&mask_ids, &mask_c, Some("masked_output"));
+
+    builder.refine_output(&masked_output_ids).to_circuit()
+}
+
+/// Add each party's authenticated shares (a, xs, macs) as inputs, with all of a party's
+/// input wires adjacent, and validate them. Gives the bits of the reconstructed xs and
+/// the bit of whether the MAC check passed.
+fn validated_inputs<T: CircuitRing>(
+    num_parties: usize,
+    num_shares: usize,
+) -> (CircuitBuilder<AuthInput<T>, ()>, Vec<WireId>, Vec<WireId>) {
+    let builder = new_builder();
+    // the values are input are (\shares{(a, \vec{xs}, \vec{m})}_1, \shares{(...)}_2, ...)
+    // where each party has all of its input wires adjacent.
+    let mut builder = builder.refine_input::<Vec<(T, Vec<T>, Vec<T>)>>();
+    let mut alpha_shares_ids = Vec::with_capacity(T::BIT_SIZE * num_parties);
+    let mut inputs_shares_ids = Vec::with_capacity(T::BIT_SIZE * num_parties * num_shares);
+    let mut macs_shares_ids = Vec::with_capacity(T::BIT_SIZE * num_parties * num_shares);
+    for _ in 0..num_parties {
+        let (b2, alpha_i) = builder.add_input::<T>(None);
+        let (b2, xs_i) = b2.add_input_multi::<Vec<T>>(num_shares, None);
+        let (b2, macs_i) = b2.add_input_multi::<Vec<T>>(num_shares, None);
+        // coalesce the input
+        builder = b2.refine_input::<Vec<(T, Vec<T>, Vec<T>)>>();
+
+        alpha_shares_ids.extend(alpha_i);
+        inputs_shares_ids.extend(xs_i);
+        macs_shares_ids.extend(macs_i);
+    }
+
+    let (builder, _, xs_ids, check_id) = validate_inner::<T, _, _>(
+        builder,
+        num_parties,
+        num_shares,
+        &alpha_shares_ids,
+        &inputs_shares_ids,
+        &macs_shares_ids,
+    );
+
+    (builder, xs_ids, check_id)
+}
+
 #[cfg(test)]
-mod tests {
+pub mod tests {
     use super::*;
     use crate::{
         circuits::{aes::aes_key_schedule, *},
         ff2_128::*,
     };
 
+    /// A circuit that copies its input through two rounds of `Not`, so a single party can
+    /// bring in a value it holds by itself
+    pub fn copy_circuit<T: CircuitElement>() -> TCircuit<T, T> {
+        let n = T::BIT_SIZE;
+        let inputs = (0..n).collect();
+        let gates = (0..n)
+            .map(|i| Gate::Not(i, n + i))
+            .chain((0..n).map(|i| Gate::Not(n + i, 2 * n + i)))
+            .collect();
+        let outputs = (2 * n..3 * n).collect();
+        TCircuit::from_parts(inputs, gates, outputs)
+    }
+
     fn example_setup_circuit<I: CircuitRing, O>(
         np: usize,
         cir: &TCircuit<I, O>,
@@ -307,9 +713,33 @@ mod tests {
         // deliberately not divisible by 128 inputs
         sample_circuit(5, 129, 3, &points).well_formed();
 
+        refresh_circuit(5, 3, 3, &points).well_formed();
+
+        let bits: Vec<_> = (0..130).rev().collect();
+        reshare_circuit(3, 2, &bits, 2, &points[..4]).well_formed();
+        layout_circuit(3, 64).well_formed();
+        layout_circuit(1, 64).well_formed();
+        reveal_circuit::<FF2_128>(5, 2, &[3, 200, 3, 0]).well_formed();
+        let all_bits: Vec<_> = (0..256).rev().collect();
+        add_validation_project_plain::<FF2_128, _, _>(
+            3,
+            2,
+            &all_bits[..200],
+            56,
+            &sum_circuit::<FF2_128>(2),
+        )
+        .well_formed();
+        eval_state_circuit(3, 2, &all_bits, 3, &points, &sum_circuit::<FF2_128>(2)).well_formed();
+
         let aes = aes_key_schedule();
 
         let c = setup_circuit(5, 128, 3, &points, &example_setup_circuit(5, &aes)).well_formed();
         println!("{}", c.describe());
+
+        // a single dealer provides all of the inputs
+        let copy_c = copy_circuit::<FF2_128>();
+        setup_circuit_with_sizes(&[0, 0, 128, 0, 0], 3, &points, &copy_c).well_formed();
+        setup_circuit_with_sizes(&[128, 0, 256, 0, 0], 3, &points, &sum_circuit::<FF2_128>(3))
+            .well_formed();
     }
 }
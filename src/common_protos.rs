@@ -6,13 +6,14 @@ use crate::{
     field::{Field, RandElement, Ring},
     func_com::{AsyncCom, DecomError},
     func_net::AsyncNet,
+    func_rand::AsyncRand,
     party::PartyId,
     polynomial::{FixedPolynomial, Polynomial},
 };
 
 use std::sync::Arc;
 
-use anyhow::Context;
+use anyhow::{anyhow, Context};
 
 use futures::stream::FuturesUnordered;
 use futures::StreamExt;
@@ -33,6 +34,66 @@ pub async fn random_shares<T: Field + RandElement, FN: AsyncNet>(
             .collect()
     };
 
+    sum_dealt_shares(polys, my_id, parties, caller, net).await
+}
+
+/// Like `random_shares`, but packs k random secrets into each sharing, placed at
+/// `packed_secret_points(parties, k)`. Returns `num.div_ceil(k)` shares of polynomials of
+/// degree t + k - 1, so t + k parties are needed to reconstruct.
+pub async fn packed_random_shares<T: Field + RandElement, FN: AsyncNet>(
+    num: usize,
+    k: usize,
+    my_id: PartyId,
+    parties: &[PartyId],
+    t: usize,
+    caller: FuncId,
+    net: Arc<FN>,
+) -> Result<Vec<T>, UnexpectedError> {
+    assert!(k > 0 && t + k <= parties.len());
+    let polys: Vec<_> = {
+        let mut rng = rand::thread_rng();
+        (0..num.div_ceil(k))
+            .map(|_| FixedPolynomial::rand_polynomial(&mut rng, t + k - 1))
+            .collect()
+    };
+
+    sum_dealt_shares(polys, my_id, parties, caller, net).await
+}
+
+/// Like `random_shares`, but every sharing is of zero, for re-randomizing existing shares
+pub async fn random_zero_shares<T: Field + RandElement, FN: AsyncNet>(
+    num: usize,
+    my_id: PartyId,
+    parties: &[PartyId],
+    t: usize,
+    caller: FuncId,
+    net: Arc<FN>,
+) -> Result<Vec<T>, UnexpectedError> {
+    let polys: Vec<_> = {
+        let mut rng = rand::thread_rng();
+        (0..num)
+            .map(|_| {
+                let coefficients = std::iter::once(T::zero())
+                    .chain((1..t).map(|_| T::rand(&mut rng)))
+                    .collect();
+                FixedPolynomial::new(coefficients)
+            })
+            .collect()
+    };
+
+    sum_dealt_shares(polys, my_id, parties, caller, net).await
+}
+
+// Deal shares of our polynomials to every other party, and sum them with the shares we receive
+async fn sum_dealt_shares<T: Field, FN: AsyncNet>(
+    polys: Vec<FixedPolynomial<T>>,
+    my_id: PartyId,
+    parties: &[PartyId],
+    caller: FuncId,
+    net: Arc<FN>,
+) -> Result<Vec<T>, UnexpectedError> {
+    let num = polys.len();
+
     let mut send_set = JoinSet::new();
     let mut recv_set = JoinSet::new();
     for &p in parties.iter().filter(|&p| *p != my_id) {
@@ -152,51 +213,113 @@ pub async fn open_abits<F: Ring, FN: AsyncNet>(
     delta: &F,
     my_id: PartyId,
     parties: &[PartyId],
-    dst: FuncId,
+    ssid: SessionId,
 ) -> Result<Vec<bool>, CheatOrUnexpectedError> {
-    let mut send_set = JoinSet::new();
-    let mut recv_set = JoinSet::new();
+    let msg_len = abits.len() * (F::BYTES + 1);
+    let openings = exchange_messages(net, my_id, parties, ssid, msg_len, |j| {
+        abits_opening(abits, j)
+    })
+    .await?;
+
+    let mut bits: Vec<bool> = abits.bits.clone();
+    for (j, (p, buf)) in openings.into_iter().enumerate() {
+        let pbs = check_abits_opening(abits, &buf, j, p, delta, my_id, ssid)?;
+        bits.iter_mut().zip(pbs).for_each(|(b, pb)| *b ^= pb);
+    }
+
+    Ok(bits)
+}
+
+/// Like `open_abits`, but only sends the bits and then a single MAC per party, which is a
+/// random linear combination of its MACs. The coefficients are drawn from `rand`, which must
+/// be initialized for `ssid`, once every party's bits have arrived, so checking the combination
+/// against the same combination of our keys catches a wrong bit except with probability 1/|F|,
+/// and names the party that sent it.
+/// For fields smaller than 128 bits that is not negligible, so they fall back to `open_abits`.
+pub async fn open_abits_batched<F: Ring + RandElement, FN: AsyncNet, FR: AsyncRand>(
+    abits: &Abits<F>,
+    net: Arc<FN>,
+    rand: &FR,
+    delta: &F,
+    my_id: PartyId,
+    parties: &[PartyId],
+    ssid: SessionId,
+) -> Result<Vec<bool>, CheatOrUnexpectedError> {
+    if F::BYTES < 16 {
+        return open_abits(abits, net, delta, my_id, parties, ssid).await;
+    }
 
     let nbits = abits.len();
+    let sbuf: Arc<[u8]> = abits.bits.iter().map(|&b| b.into()).collect();
+    let openings =
+        exchange_messages(net.clone(), my_id, parties, ssid, nbits, |_| sbuf.clone()).await?;
+
+    // every party is now bound to its bits, so the coefficients cannot depend on them
+    let coeffs: Vec<F> = rand
+        .rand(ssid, nbits * F::BYTES)
+        .await
+        .context("Failed to sample the MAC coefficients")?
+        .chunks_exact(F::BYTES)
+        .map(F::from_bytes)
+        .collect();
+
+    let macs = exchange_messages(net, my_id, parties, ssid, F::BYTES, |j| {
+        let mut mac = vec![0; F::BYTES];
+        F::inner_product(&coeffs, &abits.macs[j]).to_bytes(&mut mac);
+        mac.into()
+    })
+    .await?;
 
-    for (j, &p) in parties.iter().filter(|&p| *p != my_id).enumerate() {
-        let mut sbuf =
-            unsafe { Arc::<[u8]>::new_zeroed_slice(nbits * (F::BYTES + 1)).assume_init() };
+    let mut bits: Vec<bool> = abits.bits.clone();
+    let ctx = FuncContext {
+        party: my_id,
+        func: ssid.parent,
+        sid: ssid,
+    };
+    for (j, ((p, buf), (_, mac))) in openings.into_iter().zip(macs).enumerate() {
+        let keys = &abits.keys[j];
+        let pbs = check_batched_abits_opening(keys, &buf, &mac, &coeffs, p, delta, ctx.clone())?;
+        bits.iter_mut().zip(pbs).for_each(|(b, pb)| *b ^= pb);
+    }
 
-        {
-            let b = Arc::get_mut(&mut sbuf).unwrap();
+    Ok(bits)
+}
 
-            for (x, &y) in b[..nbits].iter_mut().zip(abits.bits.iter()) {
-                *x = y.into();
-            }
+/// Send each other party `p` the message `encode(j)`, where `j` is the index of `p` among the
+/// other parties, and receive a message of `msg_len` bytes from each, returned in that order
+async fn exchange_messages<FN, E>(
+    net: Arc<FN>,
+    my_id: PartyId,
+    parties: &[PartyId],
+    ssid: SessionId,
+    msg_len: usize,
+    encode: E,
+) -> Result<Vec<(PartyId, Vec<u8>)>, UnexpectedError>
+where
+    FN: AsyncNet,
+    E: Fn(usize) -> Arc<[u8]>,
+{
+    let mut send_set = JoinSet::new();
+    let mut recv_set = JoinSet::new();
 
-            // if we wanted to be unsafe we could probably cast abits.macs[j] as [u8] and just
-            // copy_from_slice
-            b[nbits..]
-                .chunks_exact_mut(F::BYTES)
-                .zip(abits.macs[j].iter())
-                .for_each(|(c, m)| {
-                    m.to_bytes(c);
-                });
-        }
+    for (j, &p) in parties.iter().filter(|&p| *p != my_id).enumerate() {
+        let sbuf = encode(j);
 
         let net2 = net.clone();
         send_set.spawn(async move {
-            net2.send_to(p, dst, sbuf)
+            net2.send_to_session(p, ssid, sbuf)
                 .await
-                .context("Failed to send abits to {p}")
+                .with_context(|| format!("Failed to send abits to {p}"))
         });
 
-        let rbuf =
-            unsafe { Arc::<[u8]>::new_zeroed_slice(abits.len() * (F::BYTES + 1)).assume_init() };
         let net3 = net.clone();
         recv_set.spawn(async move {
             (
                 j,
                 p,
-                net3.recv_from(p, dst, rbuf)
+                net3.recv_from_session(p, ssid)
                     .await
-                    .context("Failed to receive abits from {p}"),
+                    .with_context(|| format!("Failed to receive abits from {p}")),
             )
         });
     }
@@ -205,54 +328,333 @@ pub async fn open_abits<F: Ring, FN: AsyncNet>(
         let _ = s.unwrap()?;
     }
 
+    let mut msgs = Vec::with_capacity(recv_set.len());
+    while let Some(r) = recv_set.join_next().await {
+        let (j, p, buf) = r.unwrap();
+        let buf = buf?;
+        if buf.len() != msg_len {
+            return Err(anyhow!("Opening from {p} has the wrong size {}", buf.len()).into());
+        }
+        msgs.push((j, p, buf));
+    }
+    msgs.sort_by_key(|m| m.0);
+
+    Ok(msgs.into_iter().map(|(_, p, buf)| (p, buf)).collect())
+}
+
+/// Like `open_abits`, but only `target` receives the bits and MACs of the other parties
+/// and learns the opened bits, which are `None` for everyone else
+pub async fn open_abits_to<F: Ring, FN: AsyncNet>(
+    abits: &Abits<F>,
+    net: Arc<FN>,
+    delta: &F,
+    my_id: PartyId,
+    parties: &[PartyId],
+    target: PartyId,
+    ssid: SessionId,
+) -> Result<Option<Vec<bool>>, CheatOrUnexpectedError> {
+    let nbits = abits.len();
+    let others = parties.iter().filter(|&p| *p != my_id);
+
+    if my_id != target {
+        let j = others
+            .clone()
+            .position(|&p| p == target)
+            .with_context(|| format!("Target {target} is not one of the parties"))?;
+        let sbuf = abits_opening(abits, j);
+        net.send_to_session(target, ssid, sbuf)
+            .await
+            .with_context(|| format!("Failed to send abits to {target}"))?;
+        return Ok(None);
+    }
+
+    let mut recv_set = JoinSet::new();
+    for (j, &p) in others.enumerate() {
+        let net2 = net.clone();
+        recv_set.spawn(async move {
+            (
+                j,
+                p,
+                net2.recv_from_session(p, ssid)
+                    .await
+                    .context("Failed to receive abits from {p}"),
+            )
+        });
+    }
+
     let mut bits: Vec<bool> = abits.bits.clone();
 
     while let Some(r) = recv_set.join_next().await {
         let r = r.unwrap();
-        let j = r.0;
-        let p = r.1;
-        let (buf, count) = r.2?;
-
-        assert!(count == abits.len() * (F::BYTES + 1));
-        assert!(buf.len() >= count);
-
-        let mut i = 0;
-
-        buf[..nbits]
-            .iter()
-            .zip(bits.iter_mut())
-            .zip(abits.keys[j].iter())
-            .zip(buf[nbits..].chunks_exact(F::BYTES))
-            .try_for_each(|(((&nb, b), k), m)| {
-                let pb = nb == 1;
-                let m = F::from_bytes(m);
-                let mut emac = k.clone();
-
-                if pb {
-                    emac += delta;
-                }
+        let buf = r.2?;
+        if buf.len() != nbits * (F::BYTES + 1) {
+            return Err(anyhow!("Opening from {} has the wrong size {}", r.1, buf.len()).into());
+        }
+
+        let pbs = check_abits_opening(abits, &buf, r.0, r.1, delta, my_id, ssid)?;
+        bits.iter_mut().zip(pbs).for_each(|(b, pb)| *b ^= pb);
+    }
+
+    Ok(Some(bits))
+}
+
+/// Our bits followed by our MACs for the `j`th other party, to open `abits` to them
+fn abits_opening<F: Ring>(abits: &Abits<F>, j: usize) -> Arc<[u8]> {
+    let nbits = abits.len();
+    let mut sbuf = unsafe { Arc::<[u8]>::new_zeroed_slice(nbits * (F::BYTES + 1)).assume_init() };
 
-                *b ^= pb;
-
-                if m != emac {
-                    // TODO: include an actual sid
-                    let sid = SessionId::new(dst);
-                    let ctx = FuncContext {
-                        party: my_id,
-                        func: dst,
-                        sid: sid,
-                    };
-                    Err(CheatDetectedError::new(
-                        ctx,
-                        Some(p),
-                        format!("check mac {} failed ({:?} != {:?})", i, m, emac),
-                    ))
-                } else {
-                    i += 1;
-                    Ok(())
+    {
+        let b = Arc::get_mut(&mut sbuf).unwrap();
+
+        for (x, &y) in b[..nbits].iter_mut().zip(abits.bits.iter()) {
+            *x = y.into();
+        }
+
+        // if we wanted to be unsafe we could probably cast abits.macs[j] as [u8] and just
+        // copy_from_slice
+        b[nbits..]
+            .chunks_exact_mut(F::BYTES)
+            .zip(abits.macs[j].iter())
+            .for_each(|(c, m)| {
+                m.to_bytes(c);
+            });
+    }
+
+    sbuf
+}
+
+/// Check the bits and MACs in `buf` from `p`, the `j`th other party, against our keys
+/// and return their bits
+fn check_abits_opening<F: Ring>(
+    abits: &Abits<F>,
+    buf: &[u8],
+    j: usize,
+    p: PartyId,
+    delta: &F,
+    my_id: PartyId,
+    ssid: SessionId,
+) -> Result<Vec<bool>, CheatOrUnexpectedError> {
+    let nbits = abits.len();
+    assert!(buf.len() == nbits * (F::BYTES + 1));
+
+    let pbs: Vec<bool> = buf[..nbits].iter().map(|&nb| nb == 1).collect();
+
+    // expected macs are key + bit * delta
+    let bad = pbs
+        .iter()
+        .zip(abits.keys[j].iter())
+        .zip(buf[nbits..].chunks_exact(F::BYTES))
+        .enumerate()
+        .find_map(|(i, ((&pb, k), m))| {
+            let m = F::from_bytes(m);
+            let mut emac = k.clone();
+            if pb {
+                emac += delta;
+            }
+            (m != emac).then_some((i, m, emac))
+        });
+
+    if let Some((i, m, emac)) = bad {
+        let ctx = FuncContext {
+            party: my_id,
+            func: ssid.parent,
+            sid: ssid,
+        };
+        return Err(CheatDetectedError::new(
+            ctx,
+            Some(p),
+            format!("check mac {} failed ({:?} != {:?})", i, m, emac),
+        )
+        .into());
+    }
+
+    Ok(pbs)
+}
+
+/// Check the bits `buf` from `p` and their MAC `mac` combined with `coeffs` against the same
+/// combination of our `keys` for `p` and return their bits, blaming `p` in `ctx` if it fails
+fn check_batched_abits_opening<F: Ring>(
+    keys: &[F],
+    buf: &[u8],
+    mac: &[u8],
+    coeffs: &[F],
+    p: PartyId,
+    delta: &F,
+    ctx: FuncContext,
+) -> Result<Vec<bool>, CheatOrUnexpectedError> {
+    let nbits = keys.len();
+    assert!(buf.len() == nbits && mac.len() == F::BYTES);
+
+    let pbs: Vec<bool> = buf.iter().map(|&nb| nb == 1).collect();
+
+    // expected mac is \sum_k c_k (key_k + bit_k * delta)
+    let bit_coeffs: F = coeffs
+        .iter()
+        .zip(pbs.iter())
+        .filter(|(_, &pb)| pb)
+        .map(|(c, _)| c.clone())
+        .sum();
+    let mut emac = F::inner_product(coeffs, keys);
+    emac += &(bit_coeffs * delta);
+
+    let mac = F::from_bytes(mac);
+
+    if mac != emac {
+        return Err(CheatDetectedError::new(
+            ctx,
+            Some(p),
+            format!("batched mac check of {nbits} bits failed ({mac:?} != {emac:?})"),
+        )
+        .into());
+    }
+
+    Ok(pbs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ff2_128::FF2_128,
+        fp127::FP127,
+        func_com::tests::build_test_coms,
+        func_net::tests::{build_test_nets, get_test_party_infos},
+        func_rand::tests::build_test_rands,
+        polynomial::{packed_secret_points, InterpolationPolynomial},
+    };
+    use rand::Rng;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 5)]
+    async fn test_packed_random_shares() {
+        let (n, t, k, num) = (5, 2, 3, 7);
+        let party_info = get_test_party_infos(n);
+        let nets = build_test_nets(&party_info, vec![FuncId::Ftest]).await;
+        let parties: Vec<PartyId> = (1..=n).collect();
+
+        let mut set = JoinSet::new();
+        for (i, net) in nets.into_iter().enumerate() {
+            let parties = parties.clone();
+            set.spawn(async move {
+                let shares = packed_random_shares::<FP127, _>(
+                    num,
+                    k,
+                    parties[i],
+                    &parties,
+                    t,
+                    FuncId::Ftest,
+                    net,
+                )
+                .await
+                .unwrap();
+                (parties[i], shares)
+            });
+        }
+
+        let mut results = Vec::new();
+        while let Some(r) = set.join_next().await {
+            results.push(r.unwrap());
+        }
+        results.sort_by_key(|(p, _)| *p);
+        assert!(results.iter().all(|(_, s)| s.len() == num.div_ceil(k)));
+
+        let points: Vec<FP127> = parties.iter().map(|&p| FP127::from(p as u64)).collect();
+        let secret_points = packed_secret_points::<FP127>(&parties, k);
+        for j in 0..num.div_ceil(k) {
+            let vals: Vec<_> = results.iter().map(|(_, s)| s[j]).collect();
+            let poly = InterpolationPolynomial::new(&points, &vals).unwrap();
+
+            // all shares lie on one polynomial of degree t + k - 1
+            let secrets = poly.packed_reconstruct(t, &secret_points).unwrap();
+            let subset = InterpolationPolynomial::new(&points[..t + k], &vals[..t + k]).unwrap();
+            assert_eq!(subset.packed_reconstruct(t, &secret_points), Ok(secrets));
+        }
+    }
+
+    /// Deal `nbits` random abits between `n` parties with random MAC keys, giving each
+    /// party's delta and abits
+    fn deal_test_abits(n: usize, nbits: usize) -> Vec<(FF2_128, Abits<FF2_128>)> {
+        let mut rng = rand::thread_rng();
+        let deltas: Vec<FF2_128> = (0..n).map(|_| FF2_128::rand(&mut rng)).collect();
+        let mut abits: Vec<_> = (0..n)
+            .map(|_| Abits {
+                bits: (0..nbits).map(|_| rng.gen()).collect(),
+                macs: vec![Vec::new(); n - 1],
+                keys: vec![Vec::new(); n - 1],
+            })
+            .collect();
+
+        for i in 0..n {
+            for j in (0..n).filter(|&j| j != i) {
+                // i's index among j's other parties and vice versa
+                let (ii, jj) = (if i < j { i } else { i - 1 }, if j < i { j } else { j - 1 });
+                for k in 0..nbits {
+                    let key = FF2_128::rand(&mut rng);
+                    let mut mac = key;
+                    if abits[i].bits[k] {
+                        mac += &deltas[j];
+                    }
+                    abits[j].keys[ii].push(key);
+                    abits[i].macs[jj].push(mac);
                 }
-            })?;
+            }
+        }
+
+        deltas.into_iter().zip(abits).collect()
     }
 
-    Ok(bits)
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_open_abits_batched() {
+        let (n, nbits) = (3, 300);
+        let party_info = get_test_party_infos(n as PartyId);
+        let nets = build_test_nets(&party_info, vec![FuncId::Ftest, FuncId::Fcom]).await;
+        let rands = build_test_rands(&build_test_coms(&nets));
+        let parties: Vec<PartyId> = (1..=n as PartyId).collect();
+
+        // an honest opening, then one where party 2 flips a bit
+        for cheat in [false, true] {
+            let dealt = deal_test_abits(n, nbits);
+            let expected: Vec<bool> = (0..nbits)
+                .map(|k| dealt.iter().fold(false, |b, (_, a)| b ^ a.bits[k]))
+                .collect();
+
+            let mut set = JoinSet::new();
+            for (i, (net, (delta, mut abits))) in nets.iter().zip(dealt).enumerate() {
+                let (net, rand, parties) = (net.clone(), rands[i].clone(), parties.clone());
+                if cheat && parties[i] == 2 {
+                    abits.bits[7] ^= true;
+                }
+                set.spawn(async move {
+                    let sid = SessionId::new(FuncId::Ftest);
+                    rand.init(sid).await.unwrap();
+                    let r = open_abits_batched(
+                        &abits,
+                        net,
+                        rand.as_ref(),
+                        &delta,
+                        parties[i],
+                        &parties,
+                        sid,
+                    )
+                    .await;
+                    (parties[i], r)
+                });
+            }
+
+            while let Some(r) = set.join_next().await {
+                match r.unwrap() {
+                    (p, Ok(bits)) => {
+                        assert!(!cheat || p == 2);
+                        if !cheat {
+                            assert_eq!(bits, expected);
+                        }
+                    }
+                    (_, Err(CheatOrUnexpectedError::CheatDetected(e))) => {
+                        assert!(cheat);
+                        assert!(e.to_string().contains("Some(2)"));
+                    }
+                    (p, Err(e)) => panic!("{p}: unexpected error {e:?}"),
+                }
+            }
+        }
+    }
 }
@@ -1,9 +1,11 @@
-use core::arch::x86_64::*;
-
 use std::ops::{Add, AddAssign, Mul, MulAssign, Sub, SubAssign};
 
-use crate::field::{
-    impl_arith_assign, impl_sum_prod, Adds, ConstInt, Field, Muls, RandElement, Ring, Subs,
+use crate::{
+    block::Block,
+    field::{
+        impl_arith_assign, impl_sum_prod, Adds, BinaryField, ConstInt, Field, Muls, RandElement,
+        Ring, Subs,
+    },
 };
 use rand::Rng;
 use serde::de::{self, Visitor};
@@ -12,7 +14,7 @@ use serde::{Deserialize, Deserializer, Serialize, Serializer};
 /// An element in the field F_2[x] / (x^128 + x^7 + x^2 + x + 1)
 #[derive(Copy, Clone)]
 pub struct FF2_128 {
-    i: __m128i,
+    i: Block,
 }
 
 impl Serialize for FF2_128 {
@@ -57,13 +59,13 @@ impl<'d> Visitor<'d> for FF2Visitor {
 
 impl<'d> Deserialize<'d> for FF2_128 {
     fn deserialize<D: Deserializer<'d>>(deserializer: D) -> Result<Self, D::Error> {
-        deserializer.deserialize_seq(FF2Visitor)
+        deserializer.deserialize_tuple(16, FF2Visitor)
     }
 }
 
 impl std::fmt::Debug for FF2_128 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let limbs: &[u64; 2] = unsafe { std::mem::transmute(self) };
+        let limbs = self.i.limbs();
 
         write!(f, "FF2_128({:#x}, {:#x})", limbs[0], limbs[1])
     }
@@ -71,82 +73,27 @@ impl std::fmt::Debug for FF2_128 {
 
 impl PartialEq for FF2_128 {
     fn eq(&self, other: &Self) -> bool {
-        unsafe {
-            let t = _mm_xor_si128(self.i, other.i);
-            let r = _mm_test_all_zeros(t, t);
-            return r == 1;
-        }
+        self.i == other.i
     }
 }
 
 impl FF2_128 {
     pub fn new(high: u64, low: u64) -> Self {
-        unsafe {
-            return Self {
-                i: _mm_set_epi64x(high as i64, low as i64),
-            };
+        Self {
+            i: Block::new(high, low),
         }
     }
 
     pub fn add_assign(&mut self, other: Self) {
-        unsafe { self.i = _mm_xor_si128(self.i, other.i) }
+        self.i = self.i.xor(other.i);
     }
 
     pub fn sub_assign(&mut self, other: Self) {
-        unsafe { self.i = _mm_xor_si128(self.i, other.i) }
+        self.i = self.i.xor(other.i);
     }
 
     pub fn mul_assign(&mut self, other: Self) {
-        // karatsuba-style mult
-        // This is me attempting to use AVX-like instructions, but of course I haven't
-        // benchmarked anything nor have much experience so this is probably not optimal.
-        unsafe {
-            let n2 = _mm_clmulepi64_si128(self.i, other.i, 0x11);
-            let n0 = _mm_clmulepi64_si128(self.i, other.i, 0x00);
-
-            /*
-            let n11 = _mm_clmulepi64_si128(self.i, other.i, 0x10);
-            let n12 = _mm_clmulepi64_si128(self.i, other.i, 0x01);
-            let n1 = _mm_xor_si128(n11, n12);
-            */
-
-            // Karatsuba style
-            // Tentative benchmarks has this ~30% faster than the naive version above
-            let mut n1 = n2.clone();
-            // Want to create a register that is (self[1] ^ self[0]) || (other[1] ^ other[0])
-            let l = _mm_unpackhi_epi64(other.i, self.i);
-            let r = _mm_unpacklo_epi64(other.i, self.i);
-            let m = _mm_xor_si128(l, r);
-            // (self[1] ^ self[0]) * (other[1] ^ other[0])
-            let mid = _mm_clmulepi64_si128(m, m, 0x10);
-            n1 = _mm_xor_si128(n1, mid);
-            n1 = _mm_xor_si128(n1, n0);
-
-            // need to calculate n2 * 2^128 + n1*2^64 + n0
-            let t1 = _mm_xor_si128(n2, _mm_srli_si128(n1, 8));
-            let t0 = _mm_xor_si128(n0, _mm_slli_si128(n1, 8));
-
-            // reduce [t1 t0] by the polynomial
-            // It would be too easy if you could just shift the whole vector by a number of bits
-            let t1h = _mm_extract_epi64(t1, 1);
-            let t1l = _mm_extract_epi64(t1, 0);
-            fn mul_high(y: i64) -> i64 {
-                let x = y as u64;
-                let z = (x >> 57) ^ (x >> 62) ^ (x >> 63);
-                z as i64
-            }
-            fn mul_low(y: i64) -> i64 {
-                let x = y as u64;
-                let z = x ^ (x << 1) ^ (x << 2) ^ (x << 7);
-                z as i64
-            }
-            let t1l = t1l ^ mul_high(t1h);
-            let mh = mul_low(t1h) ^ mul_high(t1l);
-            let ml = mul_low(t1l);
-            let mask = _mm_set_epi64x(mh, ml);
-
-            self.i = _mm_xor_si128(t0, mask);
-        }
+        self.i = self.i.gf_mul(other.i);
     }
 
     pub fn inv(&self) -> Option<Self> {
@@ -219,9 +166,7 @@ impl ConstInt for FF2_128 {
     }
 
     fn is_zero(&self) -> bool {
-        unsafe {
-            return _mm_test_all_zeros(self.i, self.i) == 1;
-        }
+        self.i.is_zero()
     }
 
     fn num_bytes(&self) -> usize {
@@ -231,8 +176,7 @@ impl ConstInt for FF2_128 {
     fn to_bytes(&self, b: &mut [u8]) -> usize {
         assert!(b.len() >= 16);
 
-        let bytes: &[u8; 16] = unsafe { std::mem::transmute(self) };
-        b[..16].copy_from_slice(bytes);
+        b[..16].copy_from_slice(&self.i.to_bytes());
 
         return 16;
     }
@@ -246,7 +190,24 @@ impl ConstInt for FF2_128 {
     }
 }
 
-impl Ring for FF2_128 {}
+impl Ring for FF2_128 {
+    /// Multiply-accumulate with a single reduction at the end, as reduction is linear
+    fn inner_product(xs: &[Self], ys: &[Self]) -> Self {
+        assert_eq!(xs.len(), ys.len());
+        let zero = Block::new(0, 0);
+        let (lo, hi) = xs
+            .iter()
+            .zip(ys.iter())
+            .fold((zero, zero), |(lo, hi), (x, y)| {
+                let (l, h) = x.i.clmul(y.i);
+                (lo.xor(l), hi.xor(h))
+            });
+
+        Self {
+            i: Block::reduce(lo, hi),
+        }
+    }
+}
 
 impl Field for FF2_128 {
     fn gen() -> Self {
@@ -258,6 +219,8 @@ impl Field for FF2_128 {
     }
 }
 
+impl BinaryField for FF2_128 {}
+
 impl RandElement for FF2_128 {
     fn rand<R: Rng + ?Sized>(rng: &mut R) -> Self {
         let mut r = [0u64; 2];
@@ -364,6 +327,17 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_inner_product() {
+        let mut rng = rand::thread_rng();
+        let xs: Vec<_> = (0..100).map(|_| FF2_128::rand(&mut rng)).collect();
+        let ys: Vec<_> = (0..100).map(|_| FF2_128::rand(&mut rng)).collect();
+
+        let naive: FF2_128 = xs.iter().zip(ys.iter()).map(|(x, y)| x * y).sum();
+        assert_eq!(FF2_128::inner_product(&xs, &ys), naive);
+        assert_eq!(FF2_128::inner_product(&[], &[]), FF2_128::zero());
+    }
+
     #[test]
     fn test_serialize_round_trip() {
         let mut bytes = [0; 16];
@@ -0,0 +1,208 @@
+use std::ops::{Add, AddAssign, Mul, MulAssign, Sub, SubAssign};
+
+use crate::{
+    block::clmul64,
+    field::{
+        impl_arith_assign, impl_sum_prod, Adds, BinaryField, ConstInt, Field, Muls, RandElement,
+        Ring, Subs,
+    },
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// The low bits of the reduction polynomial x^64 + x^4 + x^3 + x + 1
+const POLY: u64 = 0b11011;
+
+/// An element in the field F_2[x] / (x^64 + x^4 + x^3 + x + 1)
+#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct FF2_64 {
+    i: u64,
+}
+
+impl std::fmt::Debug for FF2_64 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "FF2_64({:#x})", self.i)
+    }
+}
+
+/// Reduce a polynomial of degree < 128 modulo x^64 + x^4 + x^3 + x + 1
+fn reduce(x: u128) -> u64 {
+    // x^64 = x^4 + x^3 + x + 1, so fold the high half down twice,
+    // the first fold leaves at most 4 bits above x^64
+    let t = clmul64((x >> 64) as u64, POLY);
+    let u = clmul64((t >> 64) as u64, POLY);
+    (x as u64) ^ (t as u64) ^ (u as u64)
+}
+
+impl FF2_64 {
+    pub fn new(x: u64) -> Self {
+        Self { i: x }
+    }
+
+    pub fn as_u64(&self) -> u64 {
+        self.i
+    }
+
+    pub fn add_assign(&mut self, other: Self) {
+        self.i ^= other.i;
+    }
+
+    pub fn sub_assign(&mut self, other: Self) {
+        self.i ^= other.i;
+    }
+
+    pub fn mul_assign(&mut self, other: Self) {
+        self.i = reduce(clmul64(self.i, other.i));
+    }
+
+    pub fn inv(&self) -> Option<Self> {
+        if self.is_zero() {
+            return None;
+        }
+        // Calculate a^{-1} = a^{2^64 - 2}, i.e. with the bit pattern 1111...1110
+        let mut i = *self;
+        for _ in 0..62 {
+            i *= i;
+            i *= self;
+        }
+        // i is now a^{2^63 - 1}
+        i *= i;
+
+        Some(i)
+    }
+}
+
+impl_arith_assign!(FF2_64, Add, add, AddAssign, add_assign);
+impl_arith_assign!(FF2_64, Sub, sub, SubAssign, sub_assign);
+impl_arith_assign!(FF2_64, Mul, mul, MulAssign, mul_assign);
+impl_sum_prod!(FF2_64);
+
+impl Adds for FF2_64 {}
+
+impl Subs for FF2_64 {}
+
+impl Muls for FF2_64 {}
+
+impl From<u64> for FF2_64 {
+    fn from(other: u64) -> FF2_64 {
+        FF2_64::new(other)
+    }
+}
+
+impl ConstInt for FF2_64 {
+    const BYTES: usize = 8;
+
+    fn zero() -> Self {
+        Self::new(0)
+    }
+
+    fn one() -> Self {
+        Self::new(1)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.i == 0
+    }
+
+    fn num_bytes(&self) -> usize {
+        8
+    }
+
+    fn to_bytes(&self, b: &mut [u8]) -> usize {
+        assert!(b.len() >= 8);
+        b[..8].copy_from_slice(&self.i.to_le_bytes());
+        8
+    }
+
+    fn from_bytes(b: &[u8]) -> Self {
+        assert!(b.len() >= 8);
+        Self::new(u64::from_le_bytes(b[..8].try_into().unwrap()))
+    }
+}
+
+impl Ring for FF2_64 {}
+
+impl Field for FF2_64 {
+    fn gen() -> Self {
+        // the reduction polynomial is primitive, so x generates the multiplicative group
+        Self::new(2)
+    }
+
+    fn inv(&self) -> Option<Self> {
+        FF2_64::inv(self)
+    }
+}
+
+impl BinaryField for FF2_64 {}
+
+impl RandElement for FF2_64 {
+    fn rand<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Self::new(rng.gen())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand;
+
+    #[test]
+    fn test_mul() {
+        // random multiplications from python
+        let cases = [
+            (
+                17485029721327973432,
+                7283207964119141687,
+                13172665695322795976,
+            ),
+            (
+                890727360438182992,
+                15149836622520594227,
+                9434865798318473588,
+            ),
+            (
+                1736392818365009963,
+                10750541312280087032,
+                1137508816463845345,
+            ),
+        ];
+
+        for (x, y, z) in cases {
+            assert_eq!(FF2_64::new(x) * FF2_64::new(y), FF2_64::new(z));
+        }
+    }
+
+    #[test]
+    fn test_inv() {
+        {
+            let x = FF2_64::new(16781078052021535861);
+            let y = FF2_64::new(10267453890484740245);
+            assert_eq!(x.inv(), Some(y));
+        }
+
+        {
+            let x = FF2_64::new(3960482443532127989);
+            let y = FF2_64::new(14868401175631250128);
+            assert_eq!(x.inv(), Some(y));
+        }
+
+        assert_eq!(FF2_64::zero().inv(), None);
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let mut bytes = [0; 8];
+        let mut rng = rand::thread_rng();
+        let val = FF2_64::rand(&mut rng);
+
+        val.to_bytes(&mut bytes);
+
+        let new_val = FF2_64::from_bytes(&bytes);
+        assert_eq!(val, new_val);
+
+        let s = serde_json::to_string(&val).unwrap();
+        let new_val: FF2_64 = serde_json::from_str(&s).unwrap();
+        assert_eq!(val, new_val);
+    }
+}
@@ -0,0 +1,189 @@
+use std::ops::{Add, AddAssign, Mul, MulAssign, Sub, SubAssign};
+
+use crate::field::{
+    impl_arith_assign, impl_sum_prod, Adds, BinaryField, ConstInt, Field, Muls, RandElement, Ring,
+    Subs,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// An element in the field F_2[x] / (x^8 + x^4 + x^3 + x + 1), the field used by AES
+#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct FF2_8 {
+    i: u8,
+}
+
+impl std::fmt::Debug for FF2_8 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "FF2_8({:#04x})", self.i)
+    }
+}
+
+impl FF2_8 {
+    pub fn new(x: u8) -> Self {
+        Self { i: x }
+    }
+
+    pub fn as_u8(&self) -> u8 {
+        self.i
+    }
+
+    pub fn add_assign(&mut self, other: Self) {
+        self.i ^= other.i;
+    }
+
+    pub fn sub_assign(&mut self, other: Self) {
+        self.i ^= other.i;
+    }
+
+    pub fn mul_assign(&mut self, other: Self) {
+        // shift-and-add, reducing by x^8 = x^4 + x^3 + x + 1 after every shift
+        let mut a = self.i;
+        let mut b = other.i;
+        let mut r = 0;
+        while b != 0 {
+            if b & 1 == 1 {
+                r ^= a;
+            }
+            let hi = a & 0x80;
+            a <<= 1;
+            if hi != 0 {
+                a ^= 0x1b;
+            }
+            b >>= 1;
+        }
+        self.i = r;
+    }
+
+    pub fn inv(&self) -> Option<Self> {
+        if self.is_zero() {
+            return None;
+        }
+        // Calculate a^{-1} = a^{254} = a^{0b11111110}
+        let mut i = *self;
+        for _ in 0..6 {
+            i *= i;
+            i *= self;
+        }
+        i *= i;
+
+        Some(i)
+    }
+}
+
+impl_arith_assign!(FF2_8, Add, add, AddAssign, add_assign);
+impl_arith_assign!(FF2_8, Sub, sub, SubAssign, sub_assign);
+impl_arith_assign!(FF2_8, Mul, mul, MulAssign, mul_assign);
+impl_sum_prod!(FF2_8);
+
+impl Adds for FF2_8 {}
+
+impl Subs for FF2_8 {}
+
+impl Muls for FF2_8 {}
+
+impl From<u64> for FF2_8 {
+    /// Panics if `other` does not fit in a byte, since silently truncating
+    /// would e.g. map distinct party ids to the same evaluation point
+    fn from(other: u64) -> FF2_8 {
+        FF2_8::new(u8::try_from(other).expect("value too large for FF2_8"))
+    }
+}
+
+impl ConstInt for FF2_8 {
+    const BYTES: usize = 1;
+
+    fn zero() -> Self {
+        Self::new(0)
+    }
+
+    fn one() -> Self {
+        Self::new(1)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.i == 0
+    }
+
+    fn num_bytes(&self) -> usize {
+        1
+    }
+
+    fn to_bytes(&self, b: &mut [u8]) -> usize {
+        assert!(!b.is_empty());
+        b[0] = self.i;
+        1
+    }
+
+    fn from_bytes(b: &[u8]) -> Self {
+        assert!(!b.is_empty());
+        Self::new(b[0])
+    }
+}
+
+impl Ring for FF2_8 {}
+
+impl Field for FF2_8 {
+    fn gen() -> Self {
+        // x is not primitive for the AES polynomial, but x + 1 is
+        Self::new(3)
+    }
+
+    fn inv(&self) -> Option<Self> {
+        FF2_8::inv(self)
+    }
+}
+
+impl BinaryField for FF2_8 {}
+
+impl RandElement for FF2_8 {
+    fn rand<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Self::new(rng.gen())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand;
+
+    #[test]
+    fn test_mul() {
+        // examples from FIPS-197
+        assert_eq!(FF2_8::new(0x57) * FF2_8::new(0x83), FF2_8::new(0xc1));
+        assert_eq!(FF2_8::new(0x57) * FF2_8::new(0x13), FF2_8::new(0xfe));
+    }
+
+    #[test]
+    fn test_inv() {
+        assert_eq!(FF2_8::new(0x53).inv(), Some(FF2_8::new(0xca)));
+        assert_eq!(FF2_8::zero().inv(), None);
+
+        for x in 1..=255 {
+            let x = FF2_8::new(x);
+            assert_eq!(x * x.inv().unwrap(), FF2_8::one());
+        }
+    }
+
+    #[test]
+    fn test_gen() {
+        let g = FF2_8::gen();
+        let mut x = g;
+        for _ in 1..255 {
+            assert_ne!(x, FF2_8::one());
+            x *= g;
+        }
+        assert_eq!(x, FF2_8::one());
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let mut rng = rand::thread_rng();
+        let val = FF2_8::rand(&mut rng);
+
+        let s = serde_json::to_string(&val).unwrap();
+        let new_val: FF2_8 = serde_json::from_str(&s).unwrap();
+        assert_eq!(val, new_val);
+    }
+}
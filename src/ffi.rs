@@ -1,4 +1,9 @@
-use crate::{base_func::FuncId, func_net::AsyncNet, party::PartyId};
+use crate::{
+    base_func::FuncId, circuits::CircuitElement, field::ConstInt, func_net::AsyncNet,
+    party::PartyId,
+};
+
+use rand::Rng;
 
 use std::sync::Arc;
 
@@ -204,3 +209,24 @@ pub fn spawn(f: cxx::UniquePtr<ffi::Function>) {
         ffi::call(f);
     });
 }
+
+/// emp-tool always works with 128-bit deltas, keys and MACs, and uses the delta both as the
+/// garbling offset and as the choice bits of its base OTs, so all 128 bits must be secret.
+/// Fabit places a narrower delta in the low bits and samples the remaining bits fresh: for
+/// fields where addition is xor the relation mac = key + bit * delta still holds on the low
+/// bytes, so the narrow keys and MACs can be read back with `from_bytes`. Fmpc only takes full
+/// 128-bit deltas, as the labels it garbles with depend on all of the bits.
+pub fn extend_delta<T: CircuitElement>(delta: &T) -> Vec<bool> {
+    assert!(T::BIT_SIZE <= 128);
+    let mut delta_b = vec![false; 128];
+    rand::thread_rng().fill(&mut delta_b[T::BIT_SIZE..]);
+    delta.to_bits(&mut delta_b);
+    delta_b
+}
+
+/// Zero-extend an element to a 128-bit block
+pub fn to_block<T: ConstInt>(x: &T) -> [u8; 16] {
+    let mut b = [0; 16];
+    x.to_bytes(&mut b);
+    b
+}
@@ -7,7 +7,7 @@ use std::{
 use rand::Rng;
 
 /// Base trait for an integer
-pub trait ConstInt: PartialEq + Clone + Send + From<u64> + Debug {
+pub trait ConstInt: PartialEq + Clone + Send + Sync + From<u64> + Debug {
     const BYTES: usize;
     fn zero() -> Self;
     fn one() -> Self;
@@ -55,13 +55,56 @@ pub trait Divs: Div<Output = Self> + DivAssign + Sized
 {
 }
 
-pub trait Ring: ConstInt + Adds + Subs + Muls {}
+pub trait Ring: ConstInt + Adds + Subs + Muls {
+    /// Elementwise xs[i] += ys[i]
+    fn add_slices(xs: &mut [Self], ys: &[Self]) {
+        assert_eq!(xs.len(), ys.len());
+        xs.iter_mut().zip(ys.iter()).for_each(|(x, y)| *x += y);
+    }
+
+    /// Elementwise xs[i] *= c
+    fn scale_slice(xs: &mut [Self], c: &Self) {
+        xs.iter_mut().for_each(|x| *x *= c);
+    }
+
+    /// Calculates \sum_i xs[i] * ys[i]
+    fn inner_product(xs: &[Self], ys: &[Self]) -> Self {
+        assert_eq!(xs.len(), ys.len());
+        xs.iter().zip(ys.iter()).map(|(x, y)| x.clone() * y).sum()
+    }
+}
 
 pub trait Field: Ring {
     fn gen() -> Self;
     fn inv(&self) -> Option<Self>;
+
+    /// Inverts every element of `xs` with a single inversion using Montgomery's trick,
+    /// returns None if any of the elements are zero
+    fn batch_inv(xs: &[Self]) -> Option<Vec<Self>> {
+        // prefix[i] = xs[0] * .. * xs[i - 1]
+        let mut prefix = Vec::with_capacity(xs.len());
+        let mut acc = Self::one();
+        for x in xs {
+            prefix.push(acc.clone());
+            acc *= x;
+        }
+
+        let mut inv = acc.inv()?;
+        let mut out = vec![Self::zero(); xs.len()];
+        for (i, x) in xs.iter().enumerate().rev() {
+            // inv = (xs[0] * .. * xs[i])^{-1}
+            out[i] = inv.clone() * &prefix[i];
+            inv *= x;
+        }
+
+        Some(out)
+    }
 }
 
+/// A field of characteristic 2, where adding two elements XORs their bits,
+/// so bits packed into elements can be added by adding the elements
+pub trait BinaryField: Field {}
+
 /// Allows sampling an element in the set
 pub trait RandElement {
     fn rand<R: Rng + ?Sized>(rng: &mut R) -> Self;
@@ -382,3 +425,47 @@ impl_vectorized_arith!(0; T0);
 impl_vectorized_arith!(0, 1; T0, T1);
 impl_vectorized_arith!(0, 1, 2; T0, T1, T2);
 impl_vectorized_arith!(0, 1, 2, 3; T0, T1, T2, T3);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ff2_128::FF2_128, fp127::FP127};
+
+    #[test]
+    fn test_slice_ops() {
+        let mut rng = rand::thread_rng();
+        let xs: Vec<_> = (0..20).map(|_| FP127::rand(&mut rng)).collect();
+        let ys: Vec<_> = (0..20).map(|_| FP127::rand(&mut rng)).collect();
+        let c = FP127::rand(&mut rng);
+
+        let mut zs = xs.clone();
+        FP127::add_slices(&mut zs, &ys);
+        assert!(zs
+            .iter()
+            .zip(xs.iter().zip(ys.iter()))
+            .all(|(z, (x, y))| *z == x + y));
+
+        let mut zs = xs.clone();
+        FP127::scale_slice(&mut zs, &c);
+        assert!(zs.iter().zip(xs.iter()).all(|(z, x)| *z == c * x));
+
+        let ip: FP127 = xs.iter().zip(ys.iter()).map(|(x, y)| x * y).sum();
+        assert_eq!(FP127::inner_product(&xs, &ys), ip);
+    }
+
+    #[test]
+    fn test_batch_inv() {
+        let mut rng = rand::thread_rng();
+        let xs: Vec<_> = (0..20).map(|_| FF2_128::rand(&mut rng)).collect();
+
+        let invs = FF2_128::batch_inv(&xs).unwrap();
+        for (x, i) in xs.iter().zip(invs.iter()) {
+            assert_eq!(x.inv().unwrap(), *i);
+        }
+
+        let mut with_zero = xs.clone();
+        with_zero[7] = FF2_128::zero();
+        assert_eq!(FF2_128::batch_inv(&with_zero), None);
+        assert_eq!(FF2_128::batch_inv(&[]), Some(vec![]));
+    }
+}
@@ -0,0 +1,303 @@
+use std::ops::{Add, AddAssign, Mul, MulAssign, Sub, SubAssign};
+
+use crate::field::{
+    impl_arith_assign, impl_sum_prod, Adds, ConstInt, Field, Muls, RandElement, Ring, Subs,
+};
+use rand::Rng;
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// The Mersenne prime p = 2^127 - 1
+const P: u128 = (1 << 127) - 1;
+
+/// An element in the prime field F_p for p = 2^127 - 1
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct FP127 {
+    // always kept reduced in [0, p)
+    i: u128,
+}
+
+impl Serialize for FP127 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut bytes = [0; 16];
+        self.to_bytes(&mut bytes);
+        bytes.serialize(serializer)
+    }
+}
+
+struct FPVisitor;
+
+impl<'d> Visitor<'d> for FPVisitor {
+    type Value = FP127;
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a 16-byte array")
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        if v.len() == 16 {
+            Ok(FP127::from_bytes(v))
+        } else {
+            Err(E::custom(format!("byte array not len 16: {}", v.len())))
+        }
+    }
+
+    fn visit_seq<A: de::SeqAccess<'d>>(self, mut v: A) -> Result<Self::Value, A::Error> {
+        let mut bytes = [0; 16];
+        for (i, b) in bytes.iter_mut().enumerate() {
+            if let Some(x) = v.next_element()? {
+                *b = x;
+            } else {
+                return Err(<A::Error as de::Error>::custom(format!(
+                    "expected to find {}'th element in array",
+                    i
+                )));
+            }
+        }
+        Ok(FP127::from_bytes(&bytes))
+    }
+}
+
+impl<'d> Deserialize<'d> for FP127 {
+    fn deserialize<D: Deserializer<'d>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_tuple(16, FPVisitor)
+    }
+}
+
+impl std::fmt::Debug for FP127 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "FP127({:#x})", self.i)
+    }
+}
+
+/// Reduce any 128-bit value modulo p, using that 2^127 = 1 mod p
+fn reduce(x: u128) -> u128 {
+    let r = (x & P) + (x >> 127);
+    if r >= P {
+        r - P
+    } else {
+        r
+    }
+}
+
+impl FP127 {
+    /// Create an element from an integer, reducing modulo p
+    pub fn new(x: u128) -> Self {
+        Self { i: reduce(x) }
+    }
+
+    /// The canonical integer representative in [0, p)
+    pub fn as_u128(&self) -> u128 {
+        self.i
+    }
+
+    pub fn add_assign(&mut self, other: Self) {
+        // both are < 2^127 so this cannot overflow
+        self.i = reduce(self.i + other.i);
+    }
+
+    pub fn sub_assign(&mut self, other: Self) {
+        self.i = if self.i >= other.i {
+            self.i - other.i
+        } else {
+            self.i + (P - other.i)
+        };
+    }
+
+    pub fn mul_assign(&mut self, other: Self) {
+        // schoolbook multiplication with 64-bit limbs to get the 254-bit product
+        let (a0, a1) = (self.i as u64 as u128, self.i >> 64);
+        let (b0, b1) = (other.i as u64 as u128, other.i >> 64);
+
+        let ll = a0 * b0;
+        // a1, b1 < 2^63 so the middle terms cannot overflow when summed
+        let mid = a0 * b1 + a1 * b0;
+        let hh = a1 * b1;
+
+        let (lo, carry) = ll.overflowing_add(mid << 64);
+        let hi = hh + (mid >> 64) + carry as u128;
+
+        // hi * 2^128 + lo = (hi * 2 + lo_127) * 2^127 + (lo mod 2^127)
+        // and since the product is < 2^254 the high part fits in 127 bits
+        let h = (hi << 1) | (lo >> 127);
+        self.i = reduce(h + (lo & P));
+    }
+
+    /// Calculate self^e
+    pub fn pow(&self, mut e: u128) -> Self {
+        let mut acc = Self::one();
+        let mut x = *self;
+        while e > 0 {
+            if e & 1 == 1 {
+                acc *= x;
+            }
+            x *= x;
+            e >>= 1;
+        }
+        acc
+    }
+
+    pub fn inv(&self) -> Option<Self> {
+        if self.is_zero() {
+            return None;
+        }
+        // Fermat's little theorem, a^{-1} = a^{p-2}
+        Some(self.pow(P - 2))
+    }
+}
+
+impl_arith_assign!(FP127, Add, add, AddAssign, add_assign);
+impl_arith_assign!(FP127, Sub, sub, SubAssign, sub_assign);
+impl_arith_assign!(FP127, Mul, mul, MulAssign, mul_assign);
+impl_sum_prod!(FP127);
+
+impl Adds for FP127 {}
+
+impl Subs for FP127 {}
+
+impl Muls for FP127 {}
+
+impl From<u64> for FP127 {
+    fn from(other: u64) -> FP127 {
+        FP127 { i: other.into() }
+    }
+}
+
+impl ConstInt for FP127 {
+    const BYTES: usize = 16;
+
+    fn zero() -> Self {
+        Self { i: 0 }
+    }
+
+    fn one() -> Self {
+        Self { i: 1 }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.i == 0
+    }
+
+    fn num_bytes(&self) -> usize {
+        16
+    }
+
+    fn to_bytes(&self, b: &mut [u8]) -> usize {
+        assert!(b.len() >= 16);
+        b[..16].copy_from_slice(&self.i.to_le_bytes());
+        16
+    }
+
+    /// Reads the first 16 bytes as a little-endian integer and reduces modulo p
+    fn from_bytes(b: &[u8]) -> Self {
+        assert!(b.len() >= 16);
+        Self::new(u128::from_le_bytes(b[..16].try_into().unwrap()))
+    }
+}
+
+impl Ring for FP127 {}
+
+impl Field for FP127 {
+    fn gen() -> Self {
+        // 43 is the smallest primitive root modulo 2^127 - 1
+        Self { i: 43 }
+    }
+
+    fn inv(&self) -> Option<Self> {
+        FP127::inv(self)
+    }
+}
+
+impl RandElement for FP127 {
+    fn rand<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        // rejection sample to stay uniform, p is the only excluded value
+        loop {
+            let x: u128 = rng.gen::<u128>() & P;
+            if x != P {
+                return Self { i: x };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand;
+
+    #[test]
+    fn test_add_sub() {
+        let x = FP127::new(P - 1);
+        let y = FP127::from(5);
+        assert_eq!(x + y, FP127::from(4));
+        assert_eq!(FP127::from(4) - y, x);
+        assert_eq!(FP127::zero() - FP127::one(), x);
+
+        let x = FP127::new(8437184370199981696255409511307478223);
+        let y = FP127::new(10525282092545323204305425830565082805);
+        let z = FP127::new(168053085738123890223637287396626501145);
+        assert_eq!(x - y, z);
+        assert_eq!(z + y, x);
+    }
+
+    #[test]
+    fn test_mul() {
+        // random multiplications modulo 2^127 - 1 from python
+        let cases = [
+            (
+                67175736680830244327067381487379067960,
+                139732579469944347347248239121883440208,
+                102240561247242910397153170192778278145,
+            ),
+            (
+                99156242095608550030600940513926287403,
+                36529003042230103456497810279788774517,
+                156138977323106739193685062052010784277,
+            ),
+            (
+                71148305700202293481619221837805867224,
+                93755831926350080389354533590571027343,
+                71253038927797212676437098279625529114,
+            ),
+        ];
+
+        for (x, y, z) in cases {
+            assert_eq!(FP127::new(x) * FP127::new(y), FP127::new(z));
+        }
+
+        let m = FP127::new(P - 1);
+        assert_eq!(m * m, FP127::one());
+    }
+
+    #[test]
+    fn test_inv() {
+        {
+            let x = FP127::new(96206961185274333709182621206344190502);
+            let y = FP127::new(118041438717070493795989608142228814309);
+            assert_eq!(x.inv(), Some(y));
+        }
+
+        {
+            let x = FP127::new(107295439796498568413649359420364258339);
+            let y = FP127::new(119653222350253865150908546567833944877);
+            assert_eq!(x.inv(), Some(y));
+        }
+
+        assert_eq!(FP127::zero().inv(), None);
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let mut bytes = [0; 16];
+        let mut rng = rand::thread_rng();
+        let val = FP127::rand(&mut rng);
+
+        val.to_bytes(&mut bytes);
+
+        let new_val = FP127::from_bytes(&bytes);
+        assert_eq!(val, new_val);
+
+        let s = serde_json::to_string(&val).unwrap();
+        let new_val: FP127 = serde_json::from_str(&s).unwrap();
+        assert_eq!(val, new_val);
+    }
+}
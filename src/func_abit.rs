@@ -2,8 +2,11 @@ use crate::{
     auth_bits::Abits,
     base_func::{BaseFunc, FuncId, SessionId, UnexpectedError},
     circuits::CircuitElement,
-    ffi::ffi::{
-        make_abit_player, make_network, EmpAbit, Network as FFI_Network, PartyInfo as FFI_Party,
+    ffi::{
+        extend_delta,
+        ffi::{
+            make_abit_player, make_network, EmpAbit, Network as FFI_Network, PartyInfo as FFI_Party,
+        },
     },
     field::Field,
     party::{PartyId, PartyInfo},
@@ -11,6 +14,7 @@ use crate::{
 
 use std::{
     collections::HashMap,
+    future::Future,
     sync::{Arc, OnceLock, RwLock},
 };
 
@@ -37,12 +41,20 @@ impl<T> BaseFunc for WrkAbitPlayer<T> {
 }
 
 /// Trait to represent authenticated bit generation
-pub trait AsyncAbit<T> {
+pub trait AsyncAbit<T>: Send + Sync {
     /// Start a new instance with `sid`
-    async fn init(&self, sid: SessionId, delta: T) -> Result<(), UnexpectedError>;
+    fn init(
+        &self,
+        sid: SessionId,
+        delta: T,
+    ) -> impl Future<Output = Result<(), UnexpectedError>> + Send;
 
     /// Authenticate shares from each party
-    async fn abit(&self, sid: SessionId, bits: Vec<bool>) -> Result<Abits<T>, UnexpectedError>;
+    fn abit(
+        &self,
+        sid: SessionId,
+        bits: Vec<bool>,
+    ) -> impl Future<Output = Result<Abits<T>, UnexpectedError>> + Send;
 }
 
 impl<T: Field + CircuitElement + 'static> AsyncAbit<T> for WrkAbitPlayer<T> {
@@ -58,8 +70,7 @@ impl<T: Field + CircuitElement + 'static> AsyncAbit<T> for WrkAbitPlayer<T> {
             })
             .collect();
 
-        let mut delta_b = vec![false; T::BIT_SIZE];
-        delta.to_bits(&mut delta_b);
+        let delta_b = extend_delta(&delta);
 
         let id = self.party_id;
         let net_lock = self.net.clone();
@@ -103,31 +114,32 @@ impl<T: Field + CircuitElement + 'static> AsyncAbit<T> for WrkAbitPlayer<T> {
             let full_len = nbits + 3 * 80;
             bits.resize(full_len, false);
             rng.fill(&mut bits[nbits..]);
-            let mut macs = vec![vec![T::zero(); full_len]; n - 1];
-            let mut keys = vec![vec![T::zero(); full_len]; n - 1];
-
-            let nbytes = unsafe {
-                let mut mrefs: Vec<&mut [T]> = macs.iter_mut().map(|m| m.as_mut_slice()).collect();
-                let mut krefs: Vec<&mut [T]> = keys.iter_mut().map(|k| k.as_mut_slice()).collect();
-                // Safety: T has alignment at least as strict as [u8; 16]
-                let mref =
-                    std::mem::transmute::<&mut [&mut [T]], &mut [&mut [[u8; 16]]]>(&mut mrefs);
-                let kref =
-                    std::mem::transmute::<&mut [&mut [T]], &mut [&mut [[u8; 16]]]>(&mut krefs);
-                let r = abit.create_abits(&bits, mref, kref);
+            let mut mblocks = vec![vec![[0u8; 16]; full_len]; n - 1];
+            let mut kblocks = vec![vec![[0u8; 16]; full_len]; n - 1];
+
+            let nbytes = {
+                let mut mrefs: Vec<&mut [[u8; 16]]> =
+                    mblocks.iter_mut().map(|m| m.as_mut_slice()).collect();
+                let mut krefs: Vec<&mut [[u8; 16]]> =
+                    kblocks.iter_mut().map(|k| k.as_mut_slice()).collect();
+                let r = abit.create_abits(&bits, &mut mrefs, &mut krefs);
                 if r.is_err() {
                     return Err(r.err().unwrap());
                 }
                 r.unwrap()
             };
 
+            // read back the (possibly narrower) elements, dropping the extra random elements
+            let from_blocks = |blocks: Vec<Vec<[u8; 16]>>| -> Vec<Vec<T>> {
+                blocks
+                    .iter()
+                    .map(|bs| bs[..nbits].iter().map(|b| T::from_bytes(b)).collect())
+                    .collect()
+            };
+            let macs = from_blocks(mblocks);
+            let keys = from_blocks(kblocks);
+
             bits.truncate(nbits);
-            for m in macs.iter_mut() {
-                m.truncate(nbits);
-            }
-            for k in keys.iter_mut() {
-                k.truncate(nbits);
-            }
 
             Ok((nbytes, Abits { bits, macs, keys }))
         })
@@ -148,11 +160,8 @@ impl<T: Field + CircuitElement + 'static> AsyncAbit<T> for WrkAbitPlayer<T> {
 
 impl<T: Field> WrkAbitPlayer<T> {
     pub fn new(party_id: PartyId, n: PartyId, party_info: &[PartyInfo]) -> Result<Self, ()> {
-        // underlying implementation only supports 128bit size
-        assert_eq!(T::BYTES, 16);
-        // We are going to transmute vectors of the field element as vectors of byte arrays
-        // and we need to ensure that the alignment is sufficient
-        assert!(std::mem::align_of::<T>() >= std::mem::align_of::<[u8; 16]>());
+        // underlying implementation only supports up to 128bit size
+        assert!(T::BYTES <= 16);
 
         Ok(WrkAbitPlayer {
             party_id: party_id,
@@ -169,7 +178,8 @@ pub mod tests {
     use super::*;
     use crate::{
         ff2_128::FF2_128,
-        field::{ConstInt, RandElement},
+        ff2_64::FF2_64,
+        field::RandElement,
         func_net::tests::{build_test_nets, get_test_party_infos},
     };
 
@@ -184,10 +194,11 @@ pub mod tests {
             .collect()
     }
 
-    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
-    async fn test_abit_creation() {
+    async fn check_abit_creation<
+        T: Field + CircuitElement + RandElement + Copy + Send + Sync + 'static,
+    >() {
         let parties = get_test_party_infos(3);
-        let abits = build_test_abits::<FF2_128>(&parties);
+        let abits = build_test_abits::<T>(&parties);
 
         let mut js = JoinSet::<Result<_, UnexpectedError>>::new();
         for (i, abit) in abits.into_iter().enumerate() {
@@ -195,7 +206,7 @@ pub mod tests {
                 let sid = SessionId::new(FuncId::Ftest);
                 let (delta, bits) = {
                     let mut rng = rand::thread_rng();
-                    let delta = FF2_128::rand(&mut rng);
+                    let delta = T::rand(&mut rng);
                     let mut bits = vec![false; 8];
                     rng.fill(&mut bits[..]);
                     (delta, bits)
@@ -218,7 +229,7 @@ pub mod tests {
             res.insert(id, (delta, a_s));
         }
 
-        let z = FF2_128::zero();
+        let z = T::zero();
         for i in 1..=3 {
             for j in i + 1..=3 {
                 let (i_d, i_a) = &res[&i];
@@ -233,4 +244,15 @@ pub mod tests {
             }
         }
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_abit_creation() {
+        check_abit_creation::<FF2_128>().await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_abit_creation_ff2_64() {
+        // the narrow MACs are the low bits of those under emp's full 128-bit delta
+        check_abit_creation::<FF2_64>().await;
+    }
 }
@@ -10,6 +10,7 @@ use crate::{
 
 use std::{
     collections::HashMap,
+    future::Future,
     sync::{Arc, Mutex, OnceLock},
 };
 
@@ -45,49 +46,49 @@ impl<FN> BaseFunc for KosCotePlayer<FN> {
 ///     --------
 /// Where a sender provides a list of correlations, a receiver provides choice bits
 /// the sender gets random output m, and the receiver learns m + ba
-pub trait AsyncCote {
+pub trait AsyncCote: Send + Sync {
     /// Start a new instance with `other` and `sid`
     /// Runs the setup to create the base OTs and correlation
-    async fn init(
+    fn init(
         &self,
         sid: SessionId,
         other: PartyId,
         is_sender: bool,
-    ) -> Result<(), UnexpectedError>;
+    ) -> impl Future<Output = Result<(), UnexpectedError>> + Send;
 
-    /// As the sender send \vec{alpha}, and receive \vec{omega}, such that the receiver learns \vec{omega} + \vec{beta} * \vec{alpha}
-    async fn send<T: Ring>(
+    /// As the sender send \vec{alpha}, and receive \vec{omega}, such that the receiver learns \vec{beta} * \vec{alpha} - \vec{omega}
+    fn send<T: Ring>(
         &self,
         sid: SessionId,
         other: PartyId,
         correlations: Vec<T>,
-    ) -> Result<Vec<T>, UnexpectedError>;
+    ) -> impl Future<Output = Result<Vec<T>, UnexpectedError>> + Send;
 
     /// Calculate as in send, but record the transcript with `trace_fn`
-    async fn send_trace<T: Ring, F: FnMut(&[u8])>(
+    fn send_trace<T: Ring, F: FnMut(&[u8]) + Send>(
         &self,
         sid: SessionId,
         other: PartyId,
         correlations: Vec<T>,
         trace_fn: F,
-    ) -> Result<Vec<T>, UnexpectedError>;
+    ) -> impl Future<Output = Result<Vec<T>, UnexpectedError>> + Send;
 
-    /// As the receiver send \vec{beta}, and receive \vec{omega} + \vec{beta} * \vec{alpha}
-    async fn recv<T: Ring>(
+    /// As the receiver send \vec{beta}, and receive \vec{beta} * \vec{alpha} - \vec{omega}
+    fn recv<T: Ring>(
         &self,
         sid: SessionId,
         other: PartyId,
         selections: Vec<bool>,
-    ) -> Result<Vec<T>, UnexpectedError>;
+    ) -> impl Future<Output = Result<Vec<T>, UnexpectedError>> + Send;
 
     /// Calculate as in recv, but record the transcript with `trace_fn`
-    async fn recv_trace<T: Ring, F: FnMut(&[u8])>(
+    fn recv_trace<T: Ring, F: FnMut(&[u8]) + Send>(
         &self,
         sid: SessionId,
         other: PartyId,
         selections: Vec<bool>,
         trace_fn: F,
-    ) -> Result<Vec<T>, UnexpectedError>;
+    ) -> impl Future<Output = Result<Vec<T>, UnexpectedError>> + Send;
 }
 
 //
@@ -131,7 +132,7 @@ impl<FN: AsyncNet> AsyncCote for KosCotePlayer<FN> {
         Ok(())
     }
 
-    /// As the sender send \vec{alpha}, and receive \vec{omega}, such that the receiver learns \vec{omega} + \vec{beta} * \vec{alpha}
+    /// As the sender send \vec{alpha}, and receive \vec{omega}, such that the receiver learns \vec{beta} * \vec{alpha} - \vec{omega}
     async fn send<T: Ring>(
         &self,
         sid: SessionId,
@@ -233,7 +234,7 @@ impl<FN: AsyncNet> AsyncCote for KosCotePlayer<FN> {
         Ok(ta)
     }
 
-    /// As the receiver send \vec{beta}, and receive \vec{omega} + \vec{beta} * \vec{alpha}
+    /// As the receiver send \vec{beta}, and receive \vec{beta} * \vec{alpha} - \vec{omega}
     async fn recv<T: Ring>(
         &self,
         sid: SessionId,
@@ -297,7 +298,7 @@ impl<FN: AsyncNet> AsyncCote for KosCotePlayer<FN> {
         trace_fn(&tao_bytes);
 
         // If our selection bit is 0, then we just output -H(j|| v_j) as our message
-        // which is the same as ta_j as calculated by the sender
+        // which is the negation of ta_j as calculated by the sender
         // Otherwise if our bit is 1 use the adjustment tao to create the share of the correlated value.
         let tb = out_blocks
             .into_iter()
@@ -309,7 +310,7 @@ impl<FN: AsyncNet> AsyncCote for KosCotePlayer<FN> {
                     .chain_update(&(i as u32).to_be_bytes())
                     .chain_update(&z)
                     .finalize();
-                let mut tb_i = T::from_bytes(&dig1);
+                let mut tb_i = T::zero() - T::from_bytes(&dig1);
 
                 if b {
                     tb_i += T::from_bytes(&tao_i);
@@ -2,16 +2,23 @@ use crate::{
     auth_bits::{Abit, Abits},
     base_func::{BaseFunc, FuncId, SessionId, UnexpectedError},
     circuits::{CircuitCollection, CircuitElement, TCircuit},
-    ffi::ffi::{
-        make_bristol_circuit, make_network, run_mpc, Abit as FFI_Abit, MpcOut,
-        Network as FFI_Network, PartyInfo as FFI_Party,
+    ffi::{
+        extend_delta,
+        ffi::{
+            make_bristol_circuit, make_network, run_mpc, Abit as FFI_Abit, MpcOut,
+            Network as FFI_Network, PartyInfo as FFI_Party,
+        },
+        to_block,
     },
     field::Field,
+    func_net::AsyncNet,
     party::{PartyId, PartyInfo},
 };
 
 use std::{
     collections::HashMap,
+    future::Future,
+    marker::PhantomData,
     sync::{Arc, Mutex, OnceLock},
     time::{Duration, Instant},
 };
@@ -19,17 +26,87 @@ use std::{
 use anyhow::Context;
 
 use log::{info, trace};
+use rand::Rng;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// The number of emp networks, each running one instance at a time, so up to `MPC_LANES`
+/// instances can be evaluated at once
+pub const MPC_LANES: usize = 4;
 
 #[derive(Debug)]
-pub struct WrkMpcPlayer<T> {
+pub struct WrkMpcPlayer<T, FN> {
     party_id: PartyId,
     //n: u16,
     party_info: Vec<PartyInfo>,
-    net: Arc<OnceLock<cxx::SharedPtr<FFI_Network>>>,
+    net: Arc<FN>,
+    nets: Arc<OnceLock<Vec<cxx::SharedPtr<FFI_Network>>>>,
+    lanes: Lanes,
     run_infos: Mutex<HashMap<SessionId, RunInfo<T>>>,
     execution_stats: Mutex<HashMap<SessionId, Stats>>,
 }
 
+/// The emp networks that are free to run an instance on. Every taken network holds a permit,
+/// so a permit always leaves a free one to take.
+#[derive(Debug)]
+struct Lanes {
+    free: Mutex<Vec<bool>>,
+    permits: Semaphore,
+}
+
+/// A network taken to run an instance on, freed when dropped
+struct Lane<'a> {
+    lanes: &'a Lanes,
+    lane: usize,
+    _permit: SemaphorePermit<'a>,
+}
+
+impl Lanes {
+    fn new() -> Self {
+        Lanes {
+            free: Mutex::new(vec![true; MPC_LANES]),
+            permits: Semaphore::new(MPC_LANES),
+        }
+    }
+
+    /// Take the first free network from `start` on, waiting for one to be freed if none are
+    async fn take_any(&self, start: usize) -> Lane<'_> {
+        let permit = self.permits.acquire().await.unwrap();
+        let mut free = self.free.lock().unwrap();
+        let lane = (0..MPC_LANES)
+            .map(|i| (start + i) % MPC_LANES)
+            .find(|&l| free[l])
+            .unwrap();
+        free[lane] = false;
+        Lane {
+            lanes: self,
+            lane,
+            _permit: permit,
+        }
+    }
+
+    /// Take the network `lane` if it is free
+    fn try_take(&self, lane: usize) -> Option<Lane<'_>> {
+        let mut free = self.free.lock().unwrap();
+        if !free.get(lane).copied().unwrap_or(false) {
+            return None;
+        }
+        let permit = self.permits.try_acquire().ok()?;
+        free[lane] = false;
+        Some(Lane {
+            lanes: self,
+            lane,
+            _permit: permit,
+        })
+    }
+}
+
+impl Drop for Lane<'_> {
+    fn drop(&mut self) {
+        // the network is marked free before the permit is given back
+        self.lanes.free.lock().unwrap()[self.lane] = true;
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Stats {
     pub time: Duration,
@@ -55,11 +132,37 @@ impl<T> RunInfo<T> {
             input_assignment: HashMap::new(),
         }
     }
+
+    /// Add `num_bits` input wires from `party`, whose values `bits` I give if I am `party`
+    fn add_input(&mut self, me: PartyId, party: PartyId, num_bits: usize, bits: Option<Vec<bool>>) {
+        let next_id = self.num_input_wires;
+        self.num_input_wires += num_bits;
+
+        // mark the next num_bits wires as being from party
+        self.input_assignment
+            .extend((next_id..next_id + num_bits).map(|l| (l, party as i32)));
+
+        if me == party {
+            // If I am the specified party, I should be providing an input
+            let bits = bits.unwrap();
+            assert_eq!(bits.len(), num_bits);
+            self.my_input.extend(bits);
+        }
+    }
+}
+
+/// The bits of `num` elements of `input`, if given
+fn input_bits<T: CircuitCollection>(num: usize, input: Option<T>) -> Option<Vec<bool>> {
+    input.map(|i| {
+        let mut bits = vec![false; T::total_size(num)];
+        i.to_bits(&mut bits);
+        bits
+    })
 }
 
 pub type InputId = u16;
 
-impl<T> BaseFunc for WrkMpcPlayer<T> {
+impl<T, FN> BaseFunc for WrkMpcPlayer<T, FN> {
     const FUNC_ID: FuncId = FuncId::Fmpc;
     const REQUIRED_FUNCS: &'static [FuncId] = &[];
 
@@ -69,74 +172,118 @@ impl<T> BaseFunc for WrkMpcPlayer<T> {
 }
 
 /// Trait to represent boolean-circuit F_MPC
-pub trait AsyncMpc<TF> {
+pub trait AsyncMpc<TF>: Send + Sync {
     /// Start a new instance with `sid`
-    async fn init(&self, sid: SessionId, delta: Option<TF>) -> Result<(), UnexpectedError>;
+    fn init(
+        &self,
+        sid: SessionId,
+        delta: Option<TF>,
+    ) -> impl Future<Output = Result<(), UnexpectedError>> + Send;
 
     /// Add an input from `party` to the `sid` instance
-    async fn input<T: CircuitElement>(
+    fn input<T: CircuitElement + Send>(
         &self,
         sid: SessionId,
         party: PartyId,
         input: Option<T>,
-    ) -> Result<(), UnexpectedError>;
+    ) -> impl Future<Output = Result<(), UnexpectedError>> + Send;
 
     /// Add `num` inputs from `party` to the `sid` instance
-    async fn input_multi<T: CircuitCollection>(
+    fn input_multi<T: CircuitCollection + Send>(
         &self,
         sid: SessionId,
         party: PartyId,
         num: usize,
         input: Option<T>,
-    ) -> Result<(), UnexpectedError>;
+    ) -> impl Future<Output = Result<(), UnexpectedError>> + Send;
 
     /// Add `num` inputs from `party` to the `sid` instance
-    async fn input_abit(&self, sid: SessionId, input: Abits<TF>) -> Result<(), UnexpectedError>;
+    fn input_abit(
+        &self,
+        sid: SessionId,
+        input: Abits<TF>,
+    ) -> impl Future<Output = Result<(), UnexpectedError>> + Send;
 
     /// Evaluate `circuit` using the inputs that were previously supplied
     /// giving the output of the circuit to all parties
-    async fn eval_pub<I, O: CircuitElement>(
+    fn eval_pub<I, O: CircuitElement>(
         &self,
         sid: SessionId,
         parties: &[PartyId],
         circuit: &TCircuit<I, O>,
-    ) -> Result<O, UnexpectedError>;
+    ) -> impl Future<Output = Result<O, UnexpectedError>> + Send;
 
     /// Evaluate `circuit` using the inputs that were previously supplied
     /// giving the i-th output of the circuit to party i.
-    async fn eval_priv<I, O: CircuitElement>(
+    fn eval_priv<I, O: CircuitElement>(
         &self,
         sid: SessionId,
         parties: &[PartyId],
         circuit: &TCircuit<I, Vec<O>>,
-    ) -> Result<O, UnexpectedError>;
+    ) -> impl Future<Output = Result<O, UnexpectedError>> + Send;
 
     /// Evaluate `circuit` using the inputs that were previously supplied
     /// giving authenticated output shares
-    async fn eval_abit<I, O: CircuitElement>(
+    fn eval_abit<I, O: CircuitElement>(
         &self,
         sid: SessionId,
         parties: &[PartyId],
         circuit: &TCircuit<I, O>,
-    ) -> Result<Abits<TF>, UnexpectedError>;
+    ) -> impl Future<Output = Result<Abits<TF>, UnexpectedError>> + Send;
 
     /// Evaluate `circuit` using the inputs that were previously supplied
     /// giving output wires to parties according to `output_assignment`
     /// and parsing the output with `parse_fn`
-    async fn eval_generic<I, O, O2, F: FnOnce(&[bool]) -> O2>(
+    fn eval_generic<I, O, O2, F: FnOnce(&[bool]) -> O2 + Send>(
         &self,
         sid: SessionId,
         parties: &[PartyId],
         circuit: &TCircuit<I, O>,
         output_assignment: HashMap<usize, i32>,
         parse_fn: F,
-    ) -> Result<O2, UnexpectedError>;
+    ) -> impl Future<Output = Result<O2, UnexpectedError>> + Send;
+
+    /// Drop the `sid` instance if it has not been evaluated
+    fn discard(&self, sid: SessionId);
+}
+
+/// An instance of Fmpc that is dropped when this goes out of scope, so one that fails before
+/// being evaluated is not left behind
+pub(crate) struct MpcInstance<TF, FM: AsyncMpc<TF>> {
+    mpc: Arc<FM>,
+    pub(crate) sid: SessionId,
+    _t: PhantomData<fn() -> TF>,
+}
+
+impl<TF, FM: AsyncMpc<TF>> MpcInstance<TF, FM> {
+    pub(crate) fn new(mpc: Arc<FM>, sid: SessionId) -> Self {
+        MpcInstance {
+            mpc,
+            sid,
+            _t: PhantomData,
+        }
+    }
+}
+
+impl<TF, FM: AsyncMpc<TF>> Drop for MpcInstance<TF, FM> {
+    fn drop(&mut self) {
+        self.mpc.discard(self.sid);
+    }
 }
 
 /// Currently just a thin-wrapper around the mpc_runner executable.
 /// Collects inputs from parties, and marshals the data to/from mpc_runner
-impl<TF: Field + CircuitElement + 'static> AsyncMpc<TF> for WrkMpcPlayer<TF> {
+impl<TF: Field + CircuitElement + 'static, FN: AsyncNet> AsyncMpc<TF> for WrkMpcPlayer<TF, FN> {
     async fn init(&self, sid: SessionId, delta: Option<TF>) -> Result<(), UnexpectedError> {
+        // emp-tool garbles under the delta, so a caller-chosen one must be a full 128 bits,
+        // a narrower one could not be extended while keeping the authenticated inputs valid
+        if delta.is_some() && TF::BIT_SIZE < 128 {
+            return Err(self.unexpected(
+                sid,
+                format!("A {}-bit delta is too short to garble with", TF::BIT_SIZE),
+            ));
+        }
+
         {
             let mut ri = self.run_infos.lock().unwrap();
             assert!(!ri.contains_key(&sid));
@@ -153,17 +300,29 @@ impl<TF: Field + CircuitElement + 'static> AsyncMpc<TF> for WrkMpcPlayer<TF> {
             })
             .collect();
 
-        let net_lock = self.net.clone();
+        // every party connects all of the networks together on its first instance, the first
+        // keeps its original ports and the others follow on after Fcote's
+        let net_lock = self.nets.clone();
         let my_id = self.party_id;
         let _ = tokio::task::spawn_blocking(move || {
-            let _ = net_lock.get_or_init(|| make_network(my_id, &party_info, 100).unwrap());
+            let _ = net_lock.get_or_init(|| {
+                (0..MPC_LANES)
+                    .map(|l| {
+                        let port_offset = match l {
+                            0 => 100,
+                            l => 400 + (l - 1) * 2 * (party_info.len() + 1),
+                        };
+                        make_network(my_id, &party_info, port_offset as u16).unwrap()
+                    })
+                    .collect()
+            });
         })
         .await;
 
         Ok(())
     }
 
-    async fn input<T: CircuitElement>(
+    async fn input<T: CircuitElement + Send>(
         &self,
         sid: SessionId,
         party: PartyId,
@@ -173,26 +332,13 @@ impl<TF: Field + CircuitElement + 'static> AsyncMpc<TF> for WrkMpcPlayer<TF> {
         let mut ris = self.run_infos.lock().unwrap();
         assert!(ris.contains_key(&sid));
         ris.entry(sid).and_modify(|ri| {
-            let next_id = ri.num_input_wires;
-            ri.num_input_wires += T::BIT_SIZE;
-
-            // mark the next T::BIT_SIZE wires as being from party
-            ri.input_assignment
-                .extend((next_id..next_id + T::BIT_SIZE).map(|l| (l, party as i32)));
-
-            if self.party_id == party {
-                // If I am the specified party, I should be providing an input
-                assert!(input.is_some());
-                let prev_len = ri.my_input.len();
-                ri.my_input.resize(prev_len + T::BIT_SIZE, false);
-                let i = input.unwrap();
-                i.to_bits(&mut ri.my_input.as_mut_slice()[prev_len..]);
-            }
+            let bits = input_bits(1, input.map(|i| vec![i]));
+            ri.add_input(self.party_id, party, T::BIT_SIZE, bits);
         });
         Ok(())
     }
 
-    async fn input_multi<T: CircuitCollection>(
+    async fn input_multi<T: CircuitCollection + Send>(
         &self,
         sid: SessionId,
         party: PartyId,
@@ -203,22 +349,8 @@ impl<TF: Field + CircuitElement + 'static> AsyncMpc<TF> for WrkMpcPlayer<TF> {
         let mut ris = self.run_infos.lock().unwrap();
         assert!(ris.contains_key(&sid));
         ris.entry(sid).and_modify(|ri| {
-            let num_bits = T::total_size(num);
-            let next_id = ri.num_input_wires;
-            ri.num_input_wires += num_bits;
-
-            // mark the next T::BIT_SIZE wires as being from party
-            ri.input_assignment
-                .extend((next_id..next_id + num_bits).map(|l| (l, party as i32)));
-
-            if self.party_id == party {
-                // If I am the specified party, I should be providing an input
-                assert!(input.is_some());
-                let prev_len = ri.my_input.len();
-                ri.my_input.resize(prev_len + num_bits, false);
-                let i = input.unwrap();
-                i.to_bits(&mut ri.my_input.as_mut_slice()[prev_len..]);
-            }
+            let bits = input_bits(num, input);
+            ri.add_input(self.party_id, party, T::total_size(num), bits);
         });
         Ok(())
     }
@@ -313,14 +445,22 @@ impl<TF: Field + CircuitElement + 'static> AsyncMpc<TF> for WrkMpcPlayer<TF> {
             .await?;
         Ok(out)
     }
+
+    fn discard(&self, sid: SessionId) {
+        self.run_infos.lock().unwrap().remove(&sid);
+    }
 }
 
-impl<T> WrkMpcPlayer<T> {
-    pub fn new(party_id: PartyId, party_info: &[PartyInfo]) -> Result<Self, ()> {
+impl<T, FN> WrkMpcPlayer<T, FN> {
+    /// `net` carries the messages agreeing on which emp network to evaluate each instance on,
+    /// so it needs an Fmpc channel
+    pub fn new(party_id: PartyId, party_info: &[PartyInfo], net: Arc<FN>) -> Result<Self, ()> {
         Ok(WrkMpcPlayer {
             party_id: party_id,
             party_info: party_info.to_vec(),
-            net: Arc::new(OnceLock::new()),
+            net,
+            nets: Arc::new(OnceLock::new()),
+            lanes: Lanes::new(),
             run_infos: Mutex::new(HashMap::new()),
             execution_stats: Mutex::new(HashMap::new()),
         })
@@ -331,7 +471,91 @@ impl<T> WrkMpcPlayer<T> {
     }
 }
 
-impl<T: Field + CircuitElement + 'static> WrkMpcPlayer<T> {
+impl<T: Field + CircuitElement + 'static, FN: AsyncNet> WrkMpcPlayer<T, FN> {
+    /// Agree with the other `parties` on a free network to evaluate the `sid` instance on.
+    /// The first of the parties proposes one that is free for it, which the others take if
+    /// it is free for them too, otherwise it is given back and the first party tries again.
+    async fn take_lane(
+        &self,
+        sid: SessionId,
+        parties: &[PartyId],
+    ) -> Result<Lane<'_>, UnexpectedError> {
+        let ssid = sid.derive_ssid(FuncId::Fmpc);
+        let leader = *parties.iter().min().unwrap();
+        let others: Vec<_> = parties
+            .iter()
+            .copied()
+            .filter(|&p| p != self.party_id)
+            .collect();
+
+        if self.party_id == leader {
+            loop {
+                // start from a different network to the other leaders, to clash less often
+                let lane = self.lanes.take_any(self.party_id as usize).await;
+                for &p in others.iter() {
+                    self.net
+                        .clone()
+                        .send_to_session(p, ssid, [lane.lane as u8])
+                        .await
+                        .with_context(|| self.err(sid, format!("Failed to propose to {p}")))?;
+                }
+                let mut taken = true;
+                for &p in others.iter() {
+                    let reply = self
+                        .net
+                        .clone()
+                        .recv_from_session(p, ssid)
+                        .await
+                        .with_context(|| self.err(sid, format!("Failed to hear from {p}")))?;
+                    taken &= reply == [1];
+                }
+                for &p in others.iter() {
+                    self.net
+                        .clone()
+                        .send_to_session(p, ssid, [taken as u8])
+                        .await
+                        .with_context(|| self.err(sid, format!("Failed to confirm to {p}")))?;
+                }
+                if taken {
+                    return Ok(lane);
+                }
+
+                drop(lane);
+                let backoff = rand::thread_rng().gen_range(1..20);
+                tokio::time::sleep(Duration::from_millis(backoff)).await;
+            }
+        } else {
+            loop {
+                let proposal = self
+                    .net
+                    .clone()
+                    .recv_from_session(leader, ssid)
+                    .await
+                    .with_context(|| self.err(sid, format!("Failed to hear from {leader}")))?;
+                let lane = match proposal[..] {
+                    [l] => self.lanes.try_take(l as usize),
+                    _ => return Err(self.unexpected(sid, "Malformed network proposal")),
+                };
+                self.net
+                    .clone()
+                    .send_to_session(leader, ssid, [lane.is_some() as u8])
+                    .await
+                    .with_context(|| self.err(sid, format!("Failed to reply to {leader}")))?;
+                let taken = self
+                    .net
+                    .clone()
+                    .recv_from_session(leader, ssid)
+                    .await
+                    .with_context(|| self.err(sid, format!("Failed to hear from {leader}")))?;
+                if taken == [1] {
+                    return lane.ok_or_else(|| {
+                        self.unexpected(sid, format!("{leader} took a network that is not free"))
+                    });
+                }
+            }
+        }
+    }
+
     async fn eval_inner<I, O, O2, F: FnOnce(&[bool]) -> O>(
         &self,
         sid: SessionId,
@@ -345,11 +569,15 @@ impl<T: Field + CircuitElement + 'static> WrkMpcPlayer<T> {
         let n_out = output_assignment.len();
         let output_assignment = (0..n_out).map(|i| output_assignment[&i]).collect();
 
-        let (delta, input_assignment, my_input, auth_input) = {
-            let ris = self.run_infos.lock().unwrap();
-            assert!(ris.contains_key(&sid));
+        // the instance is done with however the evaluation goes
+        let ri = self
+            .run_infos
+            .lock()
+            .unwrap()
+            .remove(&sid)
+            .ok_or_else(|| self.unexpected(sid, "No such instance to evaluate"))?;
 
-            let ri = &ris[&sid];
+        let (delta, input_assignment, my_input, auth_input) = {
             // make sure the circuit has the same number of input wires as we have received inputs
             assert_eq!(circuit.inputs.len(), ri.num_input_wires);
 
@@ -387,30 +615,35 @@ impl<T: Field + CircuitElement + 'static> WrkMpcPlayer<T> {
             circuit.describe()
         );
 
-        let net = self.net.get().unwrap().clone();
+        let lane = self.take_lane(sid, parties).await?;
+        trace!("{}: sid {} runs on network {}", my_id, sid, lane.lane);
+        let net = self.nets.get().unwrap()[lane.lane].clone();
 
         let pids = parties.to_vec();
 
         let h = tokio::task::spawn_blocking(move || {
             let ai: Vec<Abit<T>> = auth_input;
+            // emp-tool expects 128-bit macs and keys
+            let ai_blocks: Vec<(Vec<[u8; 16]>, Vec<[u8; 16]>)> = ai
+                .iter()
+                .map(|a| {
+                    (
+                        a.macs.iter().map(to_block).collect(),
+                        a.keys.iter().map(to_block).collect(),
+                    )
+                })
+                .collect();
             let ffi_ai = ai
                 .iter()
-                .map(|a| FFI_Abit {
+                .zip(ai_blocks.iter())
+                .map(|(a, (macs, keys))| FFI_Abit {
                     bit: a.bit,
-                    macs: unsafe { std::mem::transmute(a.macs.as_slice()) },
-                    keys: unsafe { std::mem::transmute(a.keys.as_slice()) },
+                    macs: macs.as_slice(),
+                    keys: keys.as_slice(),
                 })
                 .collect();
             let delta: Option<T> = delta;
-            let delta_b = {
-                if let Some(d) = delta {
-                    let mut delta_b = vec![false; T::BIT_SIZE];
-                    d.to_bits(&mut delta_b);
-                    delta_b
-                } else {
-                    Vec::new()
-                }
-            };
+            let delta_b = delta.map(|d| extend_delta(&d)).unwrap_or_default();
 
             trace!("{}: sid {} starting mpc", my_id, sid);
 
@@ -435,6 +668,7 @@ impl<T: Field + CircuitElement + 'static> WrkMpcPlayer<T> {
             .await
             .unwrap()
             .with_context(|| self.err(sid, "Failed to run the MPC"))?;
+        drop(lane);
 
         let stats = Stats {
             time: start.elapsed(),
@@ -448,11 +682,6 @@ impl<T: Field + CircuitElement + 'static> WrkMpcPlayer<T> {
             g.insert(sid, stats);
         }
 
-        {
-            let mut ris = self.run_infos.lock().unwrap();
-            ris.remove(&sid);
-        }
-
         let mut abits = Abits::empty(np - 1);
 
         // the return value has enough bytes for all parties, but we of course
@@ -476,27 +705,236 @@ pub mod tests {
     use super::*;
     use crate::{
         circuits::arith::sum_circuit,
-        circuits::Gate,
+        circuits::{executor::execute_circuit_bits, Gate},
         ff2_128::FF2_128,
-        field::RandElement,
-        func_net::tests::{build_test_nets, get_test_party_infos},
+        ff2_64::FF2_64,
+        field::{ConstInt, RandElement},
+        func_net::{
+            tests::{build_test_nets, get_test_party_infos},
+            AsyncNetworkMgr,
+        },
     };
+    use anyhow::anyhow;
     use std::sync::Arc;
-    use tokio::task::JoinSet;
+    use tokio::{net::TcpStream, sync::oneshot, task::JoinSet};
 
-    pub fn build_test_mpcs(party_info: &[PartyInfo]) -> Vec<Arc<WrkMpcPlayer<FF2_128>>> {
-        let num = party_info.len() as PartyId;
-        (1..=num)
-            .map(|i| Arc::new(WrkMpcPlayer::new(i, &party_info).unwrap()))
+    pub type TestMpc<T> = WrkMpcPlayer<T, AsyncNetworkMgr<TcpStream, TcpStream>>;
+
+    pub async fn build_test_mpcs<T: Field + CircuitElement>(
+        party_info: &[PartyInfo],
+    ) -> Vec<Arc<TestMpc<T>>> {
+        let nets = build_test_nets(party_info, vec![FuncId::Fmpc]).await;
+        nets.into_iter()
+            .zip(1..)
+            .map(|(net, i)| Arc::new(WrkMpcPlayer::new(i, party_info, net).unwrap()))
+            .collect()
+    }
+
+    /// An Fmpc that evaluates its circuits in the clear, so that the protocols built on it can
+    /// be tested without emp. It takes no abits.
+    pub struct IdealMpc<T> {
+        party_id: PartyId,
+        runs: Arc<Mutex<HashMap<SessionId, IdealRun>>>,
+        run_infos: Mutex<HashMap<SessionId, RunInfo<T>>>,
+    }
+
+    /// The input wires of an instance so far, and the parties waiting for its outputs
+    #[derive(Default)]
+    struct IdealRun {
+        inputs: HashMap<usize, bool>,
+        waiting: Vec<oneshot::Sender<Vec<bool>>>,
+    }
+
+    pub fn build_ideal_mpcs<T>(n: PartyId) -> Vec<Arc<IdealMpc<T>>> {
+        let runs = Arc::new(Mutex::new(HashMap::new()));
+        (1..=n)
+            .map(|party_id| {
+                Arc::new(IdealMpc {
+                    party_id,
+                    runs: runs.clone(),
+                    run_infos: Mutex::new(HashMap::new()),
+                })
+            })
             .collect()
     }
 
+    impl<T> IdealMpc<T> {
+        /// Add my inputs to the `sid` instance, and once all `parties` have, run `circuit` on
+        /// them, giving the public outputs and mine, in order, as emp does
+        async fn eval_ideal<I, O>(
+            &self,
+            sid: SessionId,
+            parties: &[PartyId],
+            circuit: &TCircuit<I, O>,
+            output_assignment: &HashMap<usize, i32>,
+        ) -> Result<Vec<bool>, UnexpectedError> {
+            let ri = self
+                .run_infos
+                .lock()
+                .unwrap()
+                .remove(&sid)
+                .ok_or_else(|| anyhow!("No such instance {sid} to evaluate"))?;
+            assert_eq!(circuit.inputs.len(), ri.num_input_wires);
+            if ri.auth_inputs.is_some() {
+                return Err(anyhow!("The ideal Fmpc takes no abits").into());
+            }
+
+            let mut my_wires: Vec<_> = ri
+                .input_assignment
+                .iter()
+                .filter(|&(_, &p)| p == self.party_id as i32)
+                .map(|(&w, _)| w)
+                .collect();
+            my_wires.sort_unstable();
+
+            let rx = {
+                let mut runs = self.runs.lock().unwrap();
+                let run = runs.entry(sid).or_default();
+                run.inputs.extend(my_wires.into_iter().zip(ri.my_input));
+                let (tx, rx) = oneshot::channel();
+                run.waiting.push(tx);
+
+                if run.waiting.len() == parties.len() {
+                    let run = runs.remove(&sid).unwrap();
+                    let bits: Vec<_> = (0..ri.num_input_wires).map(|w| run.inputs[&w]).collect();
+                    let outs = execute_circuit_bits(&bits, circuit);
+                    for tx in run.waiting {
+                        let _ = tx.send(outs.clone());
+                    }
+                }
+                rx
+            };
+
+            let outs = rx
+                .await
+                .map_err(|_| anyhow!("Instance {sid} was dropped"))?;
+            Ok(outs
+                .into_iter()
+                .enumerate()
+                .filter(|(w, _)| {
+                    matches!(output_assignment[w], 0)
+                        || output_assignment[w] == self.party_id as i32
+                })
+                .map(|(_, b)| b)
+                .collect())
+        }
+    }
+
+    impl<TF: Send + Sync> AsyncMpc<TF> for IdealMpc<TF> {
+        async fn init(&self, sid: SessionId, delta: Option<TF>) -> Result<(), UnexpectedError> {
+            let mut ri = self.run_infos.lock().unwrap();
+            assert!(!ri.contains_key(&sid));
+            ri.insert(sid, RunInfo::new(delta));
+            Ok(())
+        }
+
+        async fn input<T: CircuitElement + Send>(
+            &self,
+            sid: SessionId,
+            party: PartyId,
+            input: Option<T>,
+        ) -> Result<(), UnexpectedError> {
+            let bits = input_bits(1, input.map(|i| vec![i]));
+            let mut ris = self.run_infos.lock().unwrap();
+            let ri = ris.get_mut(&sid).unwrap();
+            ri.add_input(self.party_id, party, T::BIT_SIZE, bits);
+            Ok(())
+        }
+
+        async fn input_multi<T: CircuitCollection + Send>(
+            &self,
+            sid: SessionId,
+            party: PartyId,
+            num: usize,
+            input: Option<T>,
+        ) -> Result<(), UnexpectedError> {
+            let bits = input_bits(num, input);
+            let mut ris = self.run_infos.lock().unwrap();
+            let ri = ris.get_mut(&sid).unwrap();
+            ri.add_input(self.party_id, party, T::total_size(num), bits);
+            Ok(())
+        }
+
+        async fn input_abit(
+            &self,
+            sid: SessionId,
+            input: Abits<TF>,
+        ) -> Result<(), UnexpectedError> {
+            let mut ris = self.run_infos.lock().unwrap();
+            ris.get_mut(&sid).unwrap().auth_inputs = Some(input);
+            Ok(())
+        }
+
+        async fn eval_pub<I, O: CircuitElement>(
+            &self,
+            sid: SessionId,
+            parties: &[PartyId],
+            circuit: &TCircuit<I, O>,
+        ) -> Result<O, UnexpectedError> {
+            let output_assignment = (0..O::BIT_SIZE).map(|w| (w, 0)).collect();
+            let bits = self
+                .eval_ideal(sid, parties, circuit, &output_assignment)
+                .await?;
+            Ok(O::from_bits(&bits))
+        }
+
+        async fn eval_priv<I, O: CircuitElement>(
+            &self,
+            sid: SessionId,
+            parties: &[PartyId],
+            circuit: &TCircuit<I, Vec<O>>,
+        ) -> Result<O, UnexpectedError> {
+            let output_assignment = (0..circuit.outputs.len())
+                .map(|w| (w, parties[w / O::BIT_SIZE] as i32))
+                .collect();
+            let bits = self
+                .eval_ideal(sid, parties, circuit, &output_assignment)
+                .await?;
+            Ok(O::from_bits(&bits))
+        }
+
+        async fn eval_abit<I, O>(
+            &self,
+            sid: SessionId,
+            _parties: &[PartyId],
+            _circuit: &TCircuit<I, O>,
+        ) -> Result<Abits<TF>, UnexpectedError> {
+            self.discard(sid);
+            Err(anyhow!("The ideal Fmpc gives no abits").into())
+        }
+
+        async fn eval_generic<I, O, O2, F: FnOnce(&[bool]) -> O2>(
+            &self,
+            sid: SessionId,
+            parties: &[PartyId],
+            circuit: &TCircuit<I, O>,
+            output_assignment: HashMap<usize, i32>,
+            parse_fn: F,
+        ) -> Result<O2, UnexpectedError> {
+            let bits = self
+                .eval_ideal(sid, parties, circuit, &output_assignment)
+                .await?;
+            Ok(parse_fn(&bits))
+        }
+
+        fn discard(&self, sid: SessionId) {
+            self.run_infos.lock().unwrap().remove(&sid);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_narrow_delta_rejected() {
+        let party_info = get_test_party_infos(3);
+        let mpcs = build_test_mpcs::<FF2_64>(&party_info).await;
+        let sid = SessionId::new(FuncId::Ftest);
+        assert!(mpcs[0].init(sid, Some(FF2_64::one())).await.is_err());
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
     async fn test_pub_out() {
         let party_info = get_test_party_infos(3);
-        let mpcs: Vec<_> = (1..4)
-            .map(|i| (i, WrkMpcPlayer::<FF2_128>::new(i, &party_info).unwrap()))
-            .collect();
+        let mpcs = build_test_mpcs::<FF2_128>(&party_info).await;
+        let mpcs: Vec<_> = (1..4).zip(mpcs).collect();
 
         let mut js = JoinSet::<Result<_, UnexpectedError>>::new();
         for (id, m) in mpcs.into_iter() {
@@ -526,9 +964,8 @@ pub mod tests {
     #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
     async fn test_abit_in_out() {
         let party_info = get_test_party_infos(3);
-        let mpcs: Vec<_> = (1..4)
-            .map(|i| (i, WrkMpcPlayer::<FF2_128>::new(i, &party_info).unwrap()))
-            .collect();
+        let mpcs = build_test_mpcs::<FF2_128>(&party_info).await;
+        let mpcs: Vec<_> = (1..4).zip(mpcs).collect();
 
         let deltas: Vec<_> = {
             let mut rng = rand::thread_rng();
@@ -1,12 +1,13 @@
 use crate::{
     base_func::{BaseFunc, CheatOrUnexpectedError, FuncId, SessionId, UnexpectedError},
-    field::{FWrap, RandElement, Ring},
+    field::{ConstInt, FWrap, Field, RandElement, Ring},
     func_cote::AsyncCote,
     func_net::AsyncNet,
     party::PartyId,
+    z2k::Z2k,
 };
 
-use std::{marker::PhantomData, sync::Arc};
+use std::{future::Future, marker::PhantomData, sync::Arc};
 
 use anyhow::Context;
 
@@ -21,6 +22,55 @@ use sha2::{Digest, Sha256};
 
 use log::trace;
 
+/// A ring that Fmult can multiply in.
+///
+/// The consistency check on the sender's Fcote inputs relies on there being no zero divisors,
+/// over Z/2^k an offset of 2^{k-1} passes with probability 1/2. So as in SPDZ2k the pairwise
+/// multiplications are run in the larger ring `Lift` and only the outputs are reduced, an offset
+/// that matters mod 2^k then passes with probability at most 2^{-CHECK_BITS}.
+pub trait MultRing: Ring + RandElement {
+    type Lift: Ring + RandElement;
+
+    /// Bits of statistical security the check gets from the lift,
+    /// `usize::MAX` when the ring has no zero divisors to begin with
+    const CHECK_BITS: usize;
+
+    fn lift(&self) -> Self::Lift;
+    fn reduce(x: &Self::Lift) -> Self;
+}
+
+impl<T: Field + RandElement + Clone> MultRing for T {
+    type Lift = T;
+
+    const CHECK_BITS: usize = usize::MAX;
+
+    fn lift(&self) -> T {
+        self.clone()
+    }
+
+    fn reduce(x: &T) -> T {
+        x.clone()
+    }
+}
+
+// Z2k<128> holds the integers directly, so the lift is as wide as it can be
+// and a ring of K bits gets 128 - K bits of security
+impl<const K: usize> MultRing for Z2k<K> {
+    type Lift = Z2k<128>;
+
+    const CHECK_BITS: usize = 128 - K;
+
+    fn lift(&self) -> Z2k<128> {
+        Z2k::new(self.as_u128())
+    }
+
+    fn reduce(x: &Z2k<128>) -> Self {
+        Self::new(x.as_u128())
+    }
+}
+
+/// Multiplication from pairwise Gilboa-style OT multiplications, as in DKLS.
+/// The pairwise multiplications are done in `T::Lift`, see `MultRing`.
 #[derive(Debug)]
 pub struct DklsMultPlayer<T, FN, FC> {
     party_id: PartyId,
@@ -42,18 +92,30 @@ impl<T, FN, FC> BaseFunc for DklsMultPlayer<T, FN, FC> {
 
 /// Trait to represent the n-party multiplication functionality
 /// over a ring T.
-pub trait AsyncMult<T: Ring> {
+pub trait AsyncMult<T: Ring>: Send + Sync {
     /// Start a new instance with `sid`
-    async fn init(&self, sid: SessionId) -> Result<(), UnexpectedError>;
+    fn init(&self, sid: SessionId) -> impl Future<Output = Result<(), UnexpectedError>> + Send;
 
     /// calculate $\sum_i a_i * \sum_i b_i$ and give an additive share of the output
     /// to each party.
-    async fn mult(&self, sid: SessionId, a: T, b: T) -> Result<T, CheatOrUnexpectedError>;
+    fn mult(
+        &self,
+        sid: SessionId,
+        a: T,
+        b: T,
+    ) -> impl Future<Output = Result<T, CheatOrUnexpectedError>> + Send;
+
+    /// calculate $\sum_i a_{i,k} * \sum_i b_{i,k}$ for each k and give an additive share
+    /// of each output to each party.
+    fn mult_many(
+        &self,
+        sid: SessionId,
+        a: Vec<T>,
+        b: Vec<T>,
+    ) -> impl Future<Output = Result<Vec<T>, CheatOrUnexpectedError>> + Send;
 }
 
-impl<T: Ring + RandElement, FN: AsyncNet, FC: AsyncCote> AsyncMult<T>
-    for DklsMultPlayer<T, FN, FC>
-{
+impl<T: MultRing, FN: AsyncNet, FC: AsyncCote> AsyncMult<T> for DklsMultPlayer<T, FN, FC> {
     async fn init(&self, sid: SessionId) -> Result<(), UnexpectedError> {
         trace!("{}: init ({sid})", self.party_id);
         let ssid = sid.derive_ssid(FuncId::Fmult);
@@ -70,14 +132,27 @@ impl<T: Ring + RandElement, FN: AsyncNet, FC: AsyncCote> AsyncMult<T>
     }
 
     async fn mult(&self, sid: SessionId, a: T, b: T) -> Result<T, CheatOrUnexpectedError> {
+        let mut c = self.mult_many(sid, vec![a], vec![b]).await?;
+        Ok(c.pop().unwrap())
+    }
+
+    async fn mult_many(
+        &self,
+        sid: SessionId,
+        a: Vec<T>,
+        b: Vec<T>,
+    ) -> Result<Vec<T>, CheatOrUnexpectedError> {
+        assert_eq!(a.len(), b.len());
+
         let ssid = sid.derive_ssid(FuncId::Fmult);
+        let a_lift: Vec<T::Lift> = a.iter().map(|x| x.lift()).collect();
+        let b_lift: Vec<T::Lift> = b.iter().map(|x| x.lift()).collect();
         // Do a pairwise multiplication with each other party
         // to calculate the values a_i b_j , a_j b_i
         let futs: FuturesUnordered<_> = (1..=self.n as PartyId)
             .filter(|&i| i != self.party_id)
             .map(|i| {
-                let mut a = a.clone();
-                let mut b = b.clone();
+                let (mut a, mut b) = (&a_lift, &b_lift);
                 // i will provide (a,b) and we want to provide (b, a)
                 if i < self.party_id {
                     std::mem::swap(&mut a, &mut b);
@@ -92,18 +167,23 @@ impl<T: Ring + RandElement, FN: AsyncNet, FC: AsyncCote> AsyncMult<T>
 
         // Combine all of the pairwise multiplications to get shares of the full value
         // including our term a_i b_i
-        let mut c = a * &b;
+        let mut c: Vec<T> = a.iter().zip(b.iter()).map(|(x, y)| x.clone() * y).collect();
         while let Some(r) = futs.next().await {
             let (ab_i, ba_i) = r?;
-            c += ab_i;
-            c += ba_i;
+            c.iter_mut()
+                .zip(ab_i.into_iter().zip(ba_i))
+                .for_each(|(c, (x, y))| {
+                    *c += T::reduce(&x);
+                    *c += T::reduce(&y);
+                });
         }
 
         Ok(c)
     }
 }
 
-impl<T: Ring, FN, FC> DklsMultPlayer<T, FN, FC> {
+impl<T: MultRing, FN, FC> DklsMultPlayer<T, FN, FC> {
+    /// Errors if lifting T does not leave `s` bits of slack for the consistency check
     pub fn new(
         party_id: PartyId,
         n: usize,
@@ -112,7 +192,11 @@ impl<T: Ring, FN, FC> DklsMultPlayer<T, FN, FC> {
         cote: Arc<FC>,
     ) -> Result<Self, ()> {
         // Restriction for now because I haven't implemented hashing to more than 32 bytes of output
-        assert_eq!(T::BYTES, 16);
+        // and Fcote is run over pairs of elements
+        assert!(T::Lift::BYTES <= 16);
+        if T::CHECK_BITS < s {
+            return Err(());
+        }
 
         Ok(DklsMultPlayer {
             party_id: party_id,
@@ -125,26 +209,32 @@ impl<T: Ring, FN, FC> DklsMultPlayer<T, FN, FC> {
     }
 }
 
-impl<T: Ring + RandElement, FN: AsyncNet, FC: AsyncCote> DklsMultPlayer<T, FN, FC> {
-    // for parties i,j calculate shares of (a_i a_j, b_i b_j)
-    // could be easily adapted to take a Vec<T> input and calculate each componentwise mult
-    // or in box form
+impl<T: MultRing, FN: AsyncNet, FC: AsyncCote> DklsMultPlayer<T, FN, FC> {
+    // for parties i,j calculate shares of (a_{i,k} a_{j,k}, b_{i,k} b_{j,k}) for each k
+    // in box form
     //  a    --------  b
     // ----> |      | <---
     //[ab]_i | MUL  | [ab]_j
     // <---- |      | --->
     //       --------
-    async fn mul_2p(
+    // All of the multiplications in the batch share the gadget vector, a single
+    // OT extension, and a single consistency check message.
+    // It is called with L = T::Lift, see `MultRing`.
+    async fn mul_2p<L: Ring + RandElement>(
         &self,
         sid: SessionId,
         ssid: SessionId,
         other: PartyId,
-        a: T,
-        b: T,
-    ) -> Result<(T, T), CheatOrUnexpectedError> {
+        a: &[L],
+        b: &[L],
+    ) -> Result<(Vec<L>, Vec<L>), CheatOrUnexpectedError> {
         // zeta is the size of the encoding of the input in bits
         // the addition 2s bits are to hide Bob's input under selective failure attack
-        let zeta = T::BYTES * 8 + 2 * self.s;
+        let zeta = L::BYTES * 8 + 2 * self.s;
+        // each multiplication in the batch is two products, a_i b_j and b_i a_j
+        // so there are 2 blocks of zeta OTs per multiplication
+        let num = a.len();
+        let blocks = 2 * num;
 
         let is_sender = self.party_id < other;
 
@@ -175,9 +265,9 @@ impl<T: Ring + RandElement, FN: AsyncNet, FC: AsyncCote> DklsMultPlayer<T, FN, F
         };
 
         // zeta public random elements of the field
-        let gadget: Vec<T> = {
+        let gadget: Vec<L> = {
             let mut rng = ChaCha20Rng::from_seed(seed);
-            (0..zeta).map(|_| T::rand(&mut rng)).collect()
+            (0..zeta).map(|_| L::rand(&mut rng)).collect()
         };
 
         // We use RO calls to generate shared random values in the middle of the protocol
@@ -212,16 +302,28 @@ impl<T: Ring + RandElement, FN: AsyncNet, FC: AsyncCote> DklsMultPlayer<T, FN, F
             h2.update(bytes);
         };
 
-        // Consume the two hash instances to generate the elements chi needed
+        // Consume the two hash instances to generate the elements chi needed,
+        // one pair (\tilde{chi}_k, \hat{chi}_k) for each block of OTs
         let hash_chi = |h1: Sha256, h2: Sha256| {
-            let h1d = h1.finalize();
-            let h2d = h2.finalize();
-            let chi_t_1 = T::from_bytes(&h1d[..T::BYTES]);
-            let chi_t_2 = T::from_bytes(&h1d[T::BYTES..]);
-            let chi_h_1 = T::from_bytes(&h2d[..T::BYTES]);
-            let chi_h_2 = T::from_bytes(&h2d[T::BYTES..]);
-
-            [FWrap((chi_t_1, chi_h_1)), FWrap((chi_t_2, chi_h_2))]
+            let mut rng_t = ChaCha20Rng::from_seed(h1.finalize().into());
+            let mut rng_h = ChaCha20Rng::from_seed(h2.finalize().into());
+
+            (0..blocks)
+                .map(|_| FWrap((L::rand(&mut rng_t), L::rand(&mut rng_h))))
+                .collect::<Vec<_>>()
+        };
+
+        // Sum \gadget_j * z_j.0 for the k'th block of OT outputs
+        let gadget_sum = |zs: &[FWrap<(L, L)>], k: usize| {
+            gadget
+                .iter()
+                .zip(zs[k * zeta..(k + 1) * zeta].iter())
+                .map(|(g, z)| {
+                    let mut g = g.clone();
+                    g *= &z.0 .0;
+                    g
+                })
+                .sum::<L>()
         };
 
         // The protocol first performs a random multiplication using the COTe functionality
@@ -230,26 +332,27 @@ impl<T: Ring + RandElement, FN: AsyncNet, FC: AsyncCote> DklsMultPlayer<T, FN, F
 
         if is_sender {
             // I am the sender
-            // we are performing two multiplications using OT
+            // we are performing two multiplications using OT for each element of the batch
             // where we provide an input a multiple times , and bob provides
             // zeta selection bits, to learn m_i + a b_i
             // We do two such multiplications because we want to calculate a_i b_j and b_i a_j
-            let mut alphas = Vec::with_capacity(2 * zeta);
-            let (a1, a2) = {
+            // We do the multiplication with two values as the single input
+            // hereby tilde a and hat a so that we can check the correctness of
+            // each party's input to the COTe
+            let a_rands: Vec<FWrap<(L, L)>> = {
                 let mut rng = rand::thread_rng();
-                // We do the multiplication with two values as the single input
-                // hereby tilde a and hat a so that we can check the correctness of
-                // each party's input to the COTe
-                let a1 = FWrap::<(T, T)>::rand(&mut rng);
-                let a2 = FWrap::<(T, T)>::rand(&mut rng);
-                (a1, a2)
+                (0..blocks)
+                    .map(|_| FWrap::<(L, L)>::rand(&mut rng))
+                    .collect()
             };
 
-            // first zeta elements are a1, followed by zeta copies of a2
-            alphas.resize(zeta, a1.clone());
-            alphas.resize(2 * zeta, a2.clone());
+            // block k is zeta copies of a_rands[k]
+            let mut alphas = Vec::with_capacity(blocks * zeta);
+            for (k, a_k) in a_rands.iter().enumerate() {
+                alphas.resize((k + 1) * zeta, a_k.clone());
+            }
 
-            // learn za which is m_{1,i} || m_{2,i} for i \in zeta
+            // learn za which is m_{k,i} for i \in zeta for each block k
             let za = self
                 .cote
                 .send_trace(ssid, other, alphas, trace_fn)
@@ -257,41 +360,49 @@ impl<T: Ring + RandElement, FN: AsyncNet, FC: AsyncCote> DklsMultPlayer<T, FN, F
                 .with_context(|| self.err(sid, format!("ote send {other} failed")))?;
 
             // create the random elements from the transcript
-            // where chi_i = (\tilde{chi}_i, \hat{chi}_i)
+            // where chi_k = (\tilde{chi}_k, \hat{chi}_k)
             let chi = hash_chi(h1, h2);
 
             // vector of consistency check elements and adjustments, r || u || g
-            let mut rug_bytes = vec![0; T::BYTES * (zeta + 4)];
-
-            // First calculate r, which is r_j = \sum_l t-chi_l t-m_{l,j} + h-chi_l h-m_{l,j}
-            rug_bytes
-                .chunks_exact_mut(T::BYTES)
-                .zip(za[..zeta].iter())
-                .zip(za[zeta..].iter())
-                .for_each(|((r, za_1), za_2)| {
-                    let mut x = chi[0].clone() * za_1;
-                    x += chi[1].clone() * za_2;
+            let mut rug_bytes = vec![0; L::BYTES * (zeta + 2 * blocks)];
+
+            // First calculate r, which is r_j = \sum_k t-chi_k t-m_{k,j} + h-chi_k h-m_{k,j}
+            rug_bytes[..L::BYTES * zeta]
+                .chunks_exact_mut(L::BYTES)
+                .enumerate()
+                .for_each(|(j, r)| {
+                    let x = chi
+                        .iter()
+                        .enumerate()
+                        .map(|(k, c)| c.clone() * &za[k * zeta + j])
+                        .sum::<FWrap<(L, L)>>();
                     let r_j = x.0 .0 + x.0 .1;
                     r_j.to_bytes(r);
                 });
 
-            // u_l = t-chi_l t-a_l + h-chi_l h-a_l
+            // u_k = t-chi_k t-a_k + h-chi_k h-a_k
             // r,u are used by bob to confirm that alice put in the same value
-            // a_l for each of the zeta inputs
-            let x1 = chi[0].clone() * &a1;
-            let u1 = x1.0 .0 + x1.0 .1;
-            let x2 = chi[1].clone() * &a2;
-            let u2 = x2.0 .0 + x2.0 .1;
-
-            u1.to_bytes(&mut rug_bytes[T::BYTES * zeta..]);
-            u2.to_bytes(&mut rug_bytes[T::BYTES * (zeta + 1)..]);
+            // a_k for each of the zeta inputs
+            rug_bytes[L::BYTES * zeta..L::BYTES * (zeta + blocks)]
+                .chunks_exact_mut(L::BYTES)
+                .zip(chi.iter().zip(a_rands.iter()))
+                .for_each(|(u, (c, a_k))| {
+                    let x = c.clone() * a_k;
+                    let u_k = x.0 .0 + x.0 .1;
+                    u_k.to_bytes(u);
+                });
 
             // gamma is the adjustment value that uses the random multiplication to mask the intended inputs a,b
-            let g1 = a.clone() + &a1.0 .0;
-            let g2 = b.clone() + &a2.0 .0;
-
-            g1.to_bytes(&mut rug_bytes[T::BYTES * (zeta + 2)..]);
-            g2.to_bytes(&mut rug_bytes[T::BYTES * (zeta + 3)..]);
+            rug_bytes[L::BYTES * (zeta + blocks)..]
+                .chunks_exact_mut(2 * L::BYTES)
+                .zip(a.iter().zip(b.iter()))
+                .enumerate()
+                .for_each(|(l, (g, (a_l, b_l)))| {
+                    let g1 = a_l.clone() - &a_rands[2 * l].0 .0;
+                    let g2 = b_l.clone() - &a_rands[2 * l + 1].0 .0;
+                    g1.to_bytes(&mut g[..L::BYTES]);
+                    g2.to_bytes(&mut g[L::BYTES..]);
+                });
 
             let _ = self
                 .net
@@ -300,7 +411,7 @@ impl<T: Ring + RandElement, FN: AsyncNet, FC: AsyncCote> DklsMultPlayer<T, FN, F
                 .with_context(|| self.err(sid, format!("Failed to send r,u,gamma_a to {other}")))?;
 
             // receive bob's adjustment values, (b - t-b)
-            let gb_bytes = vec![0; 2 * T::BYTES];
+            let gb_bytes = vec![0; blocks * L::BYTES];
             let (gb_bytes, size) = self
                 .net
                 .recv_from_local(other, FuncId::Fmult, gb_bytes)
@@ -309,63 +420,54 @@ impl<T: Ring + RandElement, FN: AsyncNet, FC: AsyncCote> DklsMultPlayer<T, FN, F
                     self.err(sid, format!("Failed to receive gamma_b from {other}"))
                 })?;
 
-            assert_eq!(size, 2 * T::BYTES);
+            assert_eq!(size, blocks * L::BYTES);
 
-            let gb_1 = T::from_bytes(&gb_bytes);
-            let gb_2 = T::from_bytes(&gb_bytes[T::BYTES..]);
+            let gb: Vec<L> = gb_bytes
+                .chunks_exact(L::BYTES)
+                .map(|c| L::from_bytes(c))
+                .collect();
 
             // calculate the shares of the output
             // note that \sum_k t-za_k gad_k + \sum_k t-zb_k gad_k = (t-a_i t-b_i)
             // or the randomized product
-            // so za_adj_1 = (a (b + t-b)) + [(t-a t-b)]
-            let za_adj_1 = a * gb_1
-                + gadget
-                    .iter()
-                    .zip(za[..zeta].iter())
-                    .map(|(g, z)| {
-                        let mut g = g.clone();
-                        g *= &z.0 .0;
-                        g
-                    })
-                    .sum::<T>();
-
-            let za_adj_2 = b * gb_2
-                + gadget
-                    .iter()
-                    .zip(za[zeta..].iter())
-                    .map(|(g, z)| {
-                        let mut g = g.clone();
-                        g *= &z.0 .0;
-                        g
-                    })
-                    .sum::<T>();
+            // so za_adj_1 = (a (b - t-b)) + [(t-a t-b)]
+            let (za_adj_1, za_adj_2) = a
+                .iter()
+                .zip(b.iter())
+                .enumerate()
+                .map(|(l, (a_l, b_l))| {
+                    let za_adj_1 = a_l.clone() * &gb[2 * l] + gadget_sum(&za, 2 * l);
+                    let za_adj_2 = b_l.clone() * &gb[2 * l + 1] + gadget_sum(&za, 2 * l + 1);
+                    (za_adj_1, za_adj_2)
+                })
+                .unzip();
 
             Ok((za_adj_1, za_adj_2))
         } else {
             // I am the receiver
-            // Sample 2 zeta random bits as a randomized encoding
-            let mut betas: Vec<_> = vec![false; 2 * zeta];
+            // Sample 2 zeta random bits per multiplication as a randomized encoding
+            let mut betas: Vec<_> = vec![false; blocks * zeta];
             {
                 let mut rng = rand::thread_rng();
                 rng.fill(&mut betas[..]);
             }
 
             // Using the gadget vector calculate the elements t-b using the random bits
-            let mut bt1: T = T::zero();
-            betas[..zeta].iter().zip(gadget.iter()).for_each(|(&b, g)| {
-                if b {
-                    bt1 += g
-                }
-            });
-            let mut bt2: T = T::zero();
-            betas[zeta..].iter().zip(gadget.iter()).for_each(|(&b, g)| {
-                if b {
-                    bt2 += g
-                }
-            });
+            let bts: Vec<L> = betas
+                .chunks_exact(zeta)
+                .map(|bs| {
+                    let mut bt = L::zero();
+                    bs.iter().zip(gadget.iter()).for_each(|(&b, g)| {
+                        if b {
+                            bt += g
+                        }
+                    });
+                    bt
+                })
+                .collect();
 
             // receive share of the randomized product
-            let zb: Vec<FWrap<(T, T)>> = self
+            let zb: Vec<FWrap<(L, L)>> = self
                 .cote
                 .recv_trace(ssid, other, betas.clone(), trace_fn)
                 .await
@@ -374,7 +476,7 @@ impl<T: Ring + RandElement, FN: AsyncNet, FC: AsyncCote> DklsMultPlayer<T, FN, F
             let chi = hash_chi(h1, h2);
 
             // receive the check message (r, u) and adjustment value gamma_a from alice
-            let rug_bytes = vec![0; T::BYTES * (zeta + 4)];
+            let rug_bytes = vec![0; L::BYTES * (zeta + 2 * blocks)];
             let (rug_bytes, size) = self
                 .net
                 .recv_from_local(other, FuncId::Fmult, rug_bytes)
@@ -382,38 +484,36 @@ impl<T: Ring + RandElement, FN: AsyncNet, FC: AsyncCote> DklsMultPlayer<T, FN, F
                 .with_context(|| {
                     self.err(sid, format!("Failed to receive r,u, gamma_a from {other}"))
                 })?;
-            assert_eq!(size, T::BYTES * (zeta + 4));
+            assert_eq!(size, L::BYTES * (zeta + 2 * blocks));
 
-            let rug_els: Vec<T> = rug_bytes
-                .chunks_exact(T::BYTES)
-                .map(|b| T::from_bytes(b))
+            let rug_els: Vec<L> = rug_bytes
+                .chunks_exact(L::BYTES)
+                .map(|b| L::from_bytes(b))
                 .collect();
 
             let r = &rug_els[..zeta];
-            let u = &rug_els[zeta..zeta + 2];
-            let g = &rug_els[zeta + 2..zeta + 4];
+            let u = &rug_els[zeta..zeta + blocks];
+            let g = &rug_els[zeta + blocks..];
 
             // check that alice put in the same value in each of the zeta
-            // OT extensions
-            let check = r
-                .iter()
-                .zip(zb[..zeta].iter().zip(zb[zeta..].iter()))
-                .zip(betas[..zeta].iter().zip(betas[zeta..].iter()))
-                .all(|((r, (zb_1, zb_2)), (&b_1, &b_2))| {
-                    let mut x = chi[0].clone() * zb_1;
-                    x += chi[1].clone() * zb_2;
-                    let r_j = x.0 .0 + x.0 .1 + r;
-
-                    let mut bu_j = T::zero();
-                    if b_1 {
-                        bu_j += &u[0];
-                    }
-                    if b_2 {
-                        bu_j += &u[1];
+            // OT extensions of every block
+            let check = r.iter().enumerate().all(|(j, r)| {
+                let x = chi
+                    .iter()
+                    .enumerate()
+                    .map(|(k, c)| c.clone() * &zb[k * zeta + j])
+                    .sum::<FWrap<(L, L)>>();
+                let r_j = x.0 .0 + x.0 .1 + r;
+
+                let mut bu_j = L::zero();
+                for k in 0..blocks {
+                    if betas[k * zeta + j] {
+                        bu_j += &u[k];
                     }
+                }
 
-                    r_j == bu_j
-                });
+                r_j == bu_j
+            });
 
             if !check {
                 return Err(self
@@ -425,12 +525,18 @@ impl<T: Ring + RandElement, FN: AsyncNet, FC: AsyncCote> DklsMultPlayer<T, FN, F
                     .into());
             }
 
-            // Calculate our adjustment values (b + t-b)
-            let mut gb_bytes = vec![0; 2 * T::BYTES];
-            let gb1 = a + &bt1;
-            let gb2 = b + &bt2;
-            gb1.to_bytes(&mut gb_bytes[..T::BYTES]);
-            gb2.to_bytes(&mut gb_bytes[T::BYTES..]);
+            // Calculate our adjustment values (b - t-b)
+            let mut gb_bytes = vec![0; blocks * L::BYTES];
+            gb_bytes
+                .chunks_exact_mut(2 * L::BYTES)
+                .zip(a.iter().zip(b.iter()))
+                .enumerate()
+                .for_each(|(l, (gb, (a_l, b_l)))| {
+                    let gb1 = a_l.clone() - &bts[2 * l];
+                    let gb2 = b_l.clone() - &bts[2 * l + 1];
+                    gb1.to_bytes(&mut gb[..L::BYTES]);
+                    gb2.to_bytes(&mut gb[L::BYTES..]);
+                });
 
             let _ = self
                 .net
@@ -440,29 +546,16 @@ impl<T: Ring + RandElement, FN: AsyncNet, FC: AsyncCote> DklsMultPlayer<T, FN, F
 
             // calculate our output shares
             // like alice we have the \sum_k t-zb_k + gad_k
-            // but we add (t-b (a + t-a))
+            // but we add (t-b (a - t-a))
             // so that our combined sum is (a b)
-            let zb_adj_1 = bt1 * &g[0]
-                + gadget
-                    .iter()
-                    .zip(zb[..zeta].iter())
-                    .map(|(g, z)| {
-                        let mut g = g.clone();
-                        g *= &z.0 .0;
-                        g
-                    })
-                    .sum::<T>();
-
-            let zb_adj_2 = bt2 * &g[1]
-                + gadget
-                    .iter()
-                    .zip(zb[zeta..].iter())
-                    .map(|(g, z)| {
-                        let mut g = g.clone();
-                        g *= &z.0 .0;
-                        g
-                    })
-                    .sum::<T>();
+            let (zb_adj_1, zb_adj_2) = (0..num)
+                .map(|l| {
+                    let zb_adj_1 = bts[2 * l].clone() * &g[2 * l] + gadget_sum(&zb, 2 * l);
+                    let zb_adj_2 =
+                        bts[2 * l + 1].clone() * &g[2 * l + 1] + gadget_sum(&zb, 2 * l + 1);
+                    (zb_adj_1, zb_adj_2)
+                })
+                .unzip();
 
             Ok((zb_adj_1, zb_adj_2))
         }
@@ -477,11 +570,118 @@ pub mod tests {
         field::ConstInt,
         func_cote::tests::build_test_cotes,
         func_net::tests::{build_test_nets, get_test_party_infos},
+        z2k::Z2_64,
+    };
+
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
     };
 
-    use std::sync::Arc;
+    use anyhow::anyhow;
+    use tokio::{sync::oneshot, task::JoinSet};
 
-    use tokio::task::JoinSet;
+    /// An Fmult that multiplies in the clear, so that the protocols built on it can be tested
+    /// without OT. `tamper` may change this party's `a` inputs to a session before they are
+    /// used, to stand in for a corrupt party.
+    pub struct IdealMult<T> {
+        party_id: PartyId,
+        n: usize,
+        runs: Arc<Mutex<HashMap<(SessionId, usize), IdealRun<T>>>>,
+        calls: Mutex<HashMap<SessionId, usize>>,
+        pub tamper: Option<Box<dyn Fn(SessionId, &mut [T]) + Send + Sync>>,
+    }
+
+    /// The inputs of a call so far, by party, and the parties waiting for their shares
+    struct IdealRun<T> {
+        inputs: Vec<(Vec<T>, Vec<T>)>,
+        waiting: Vec<(PartyId, oneshot::Sender<Vec<T>>)>,
+    }
+
+    pub fn build_ideal_mults<T>(n: usize) -> Vec<IdealMult<T>> {
+        let runs = Arc::new(Mutex::new(HashMap::new()));
+        (1..=n as PartyId)
+            .map(|party_id| IdealMult {
+                party_id,
+                n,
+                runs: runs.clone(),
+                calls: Mutex::new(HashMap::new()),
+                tamper: None,
+            })
+            .collect()
+    }
+
+    impl<T: Ring + RandElement + Copy + Send> AsyncMult<T> for IdealMult<T> {
+        async fn init(&self, _sid: SessionId) -> Result<(), UnexpectedError> {
+            Ok(())
+        }
+
+        async fn mult(&self, sid: SessionId, a: T, b: T) -> Result<T, CheatOrUnexpectedError> {
+            let mut c = self.mult_many(sid, vec![a], vec![b]).await?;
+            Ok(c.pop().unwrap())
+        }
+
+        async fn mult_many(
+            &self,
+            sid: SessionId,
+            mut a: Vec<T>,
+            b: Vec<T>,
+        ) -> Result<Vec<T>, CheatOrUnexpectedError> {
+            if let Some(tamper) = &self.tamper {
+                tamper(sid, &mut a);
+            }
+
+            // the calls of a session are matched up in order
+            let call = {
+                let mut calls = self.calls.lock().unwrap();
+                let c = calls.entry(sid).or_default();
+                *c += 1;
+                *c
+            };
+
+            let rx = {
+                let mut runs = self.runs.lock().unwrap();
+                let run = runs.entry((sid, call)).or_insert_with(|| IdealRun {
+                    inputs: Vec::new(),
+                    waiting: Vec::new(),
+                });
+                run.inputs.push((a, b));
+                let (tx, rx) = oneshot::channel();
+                run.waiting.push((self.party_id, tx));
+
+                if run.waiting.len() == self.n {
+                    let run = runs.remove(&(sid, call)).unwrap();
+                    let sum = |f: fn(&(Vec<T>, Vec<T>)) -> &Vec<T>, k: usize| {
+                        run.inputs.iter().map(|x| f(x)[k]).sum::<T>()
+                    };
+                    let num = run.inputs[0].0.len();
+                    let products: Vec<T> = (0..num)
+                        .map(|k| sum(|x| &x.0, k) * sum(|x| &x.1, k))
+                        .collect();
+
+                    // random shares, with the last party making up the products
+                    let mut rng = rand::thread_rng();
+                    let mut last = products;
+                    let mut waiting = run.waiting;
+                    waiting.sort_by_key(|w| w.0);
+                    let (_, last_tx) = waiting.pop().unwrap();
+                    for (_, tx) in waiting {
+                        let shares: Vec<T> = (0..num).map(|_| T::rand(&mut rng)).collect();
+                        last.iter_mut()
+                            .zip(shares.iter())
+                            .for_each(|(l, s)| *l -= s);
+                        let _ = tx.send(shares);
+                    }
+                    let _ = last_tx.send(last);
+                }
+                rx
+            };
+
+            Ok(rx
+                .await
+                .map_err(|_| anyhow!("Call {call} of {sid} was dropped"))?)
+        }
+    }
 
     pub fn build_test_mults<FN, FC>(
         nets: &[Arc<FN>],
@@ -532,6 +732,83 @@ pub mod tests {
         assert_eq!(acc, FF2_128::new(0, 7) * FF2_128::new(7, 0));
     }
 
+    async fn mult_many_test<T: MultRing + Copy + 'static>(s: usize) {
+        let n = 3;
+        let num = 10;
+        let parties = get_test_party_infos(n);
+        let nets = build_test_nets(&parties, vec![FuncId::Fcote, FuncId::Fmult]).await;
+        let cotes = build_test_cotes(&nets, &parties);
+        let mults: Vec<_> = (1..=nets.len())
+            .map(|i| {
+                Arc::new(
+                    DklsMultPlayer::<T, _, _>::new(
+                        i as PartyId,
+                        nets.len(),
+                        s,
+                        nets[i - 1].clone(),
+                        cotes[i - 1].clone(),
+                    )
+                    .unwrap(),
+                )
+            })
+            .collect();
+
+        let (as_, bs): (Vec<Vec<_>>, Vec<Vec<_>>) = {
+            let mut rng = rand::thread_rng();
+            (0..n)
+                .map(|_| {
+                    let a = (0..num).map(|_| T::rand(&mut rng)).collect();
+                    let b = (0..num).map(|_| T::rand(&mut rng)).collect();
+                    (a, b)
+                })
+                .unzip()
+        };
+
+        let mut js = JoinSet::<Result<_, CheatOrUnexpectedError>>::new();
+        for (i, mult) in mults.into_iter().enumerate() {
+            let a = as_[i].clone();
+            let b = bs[i].clone();
+            js.spawn(async move {
+                let sid = SessionId::new(FuncId::Ftest);
+                mult.init(sid).await?;
+                mult.mult_many(sid, a, b).await
+            });
+        }
+
+        let mut acc = vec![T::zero(); num];
+
+        while let Some(r) = js.join_next().await {
+            let shares = r.unwrap().unwrap();
+            assert_eq!(shares.len(), num);
+            acc.iter_mut().zip(shares).for_each(|(a, s)| *a += s);
+        }
+
+        for k in 0..num {
+            let a: T = as_.iter().map(|a| a[k]).sum();
+            let b: T = bs.iter().map(|b| b[k]).sum();
+            assert_eq!(acc[k], a * b);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_mult_many() {
+        mult_many_test::<FF2_128>(80).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_mult_many_z2k() {
+        // 64 bits lifted to 128 leaves 64 bits for the check, as in SPDZ2k
+        mult_many_test::<Z2_64>(64).await;
+        mult_many_test::<Z2k<13>>(80).await;
+    }
+
+    #[test]
+    fn test_mult_rejects_short_lift() {
+        let new = |s| DklsMultPlayer::<Z2_64, (), ()>::new(1, 1, s, Arc::new(()), Arc::new(()));
+        assert!(new(64).is_ok());
+        assert!(new(65).is_err());
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
     async fn test_mult_is_zero() {
         let n = 3;
@@ -1,10 +1,10 @@
 use crate::{
-    base_func::{BaseFunc, FuncId},
+    base_func::{BaseFunc, FuncId, SessionId},
     party::PartyId,
 };
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     future::Future,
     sync::{
         atomic::{AtomicU64, Ordering},
@@ -24,6 +24,8 @@ pub struct AsyncNetworkMgr<I, O> {
     recvs: HashMap<(PartyId, FuncId), Mutex<I>>,
     sends: HashMap<(PartyId, FuncId), Mutex<O>>,
     net_bytes: HashMap<(PartyId, FuncId), AtomicU64>,
+    // messages read by a receiver for another session, until that session receives them
+    pending: std::sync::Mutex<HashMap<(PartyId, SessionId), VecDeque<Vec<u8>>>>,
 }
 
 impl<I, O> BaseFunc for AsyncNetworkMgr<I, O> {
@@ -58,20 +60,38 @@ pub trait AsyncNet: Send + Sync + 'static {
     ) -> impl Future<Output = io::Result<(Arc<[u8]>, usize)>> + Send;
 
     /// Sends a message to (`party`, `func`)
-    async fn send_to_local<B: AsRef<[u8]>>(
+    fn send_to_local<B: AsRef<[u8]> + Send>(
         self: &Self,
         party: PartyId,
         func: FuncId,
         data: B,
-    ) -> io::Result<()>;
+    ) -> impl Future<Output = io::Result<()>> + Send;
 
     /// Receives a message from (`party`, `func`)
-    async fn recv_from_local<B: AsMut<[u8]>>(
+    fn recv_from_local<B: AsMut<[u8]> + Send>(
         self: &Self,
         party: PartyId,
         func: FuncId,
         buf: B,
-    ) -> io::Result<(B, usize)>;
+    ) -> impl Future<Output = io::Result<(B, usize)>> + Send;
+
+    /// Sends a message to `party` in session `sid`, over the channel of `sid.parent`.
+    /// Concurrent sessions share the channel and their messages are told apart by their sid,
+    /// so the channel must not carry messages without one while sessions are using it.
+    fn send_to_session<B: AsRef<[u8]> + Send>(
+        self: Arc<Self>,
+        party: PartyId,
+        sid: SessionId,
+        data: B,
+    ) -> impl Future<Output = io::Result<()>> + Send;
+
+    /// Receives the next message from `party` in session `sid`, setting aside the messages
+    /// of other sessions that arrive first for those sessions to receive
+    fn recv_from_session(
+        self: Arc<Self>,
+        party: PartyId,
+        sid: SessionId,
+    ) -> impl Future<Output = io::Result<Vec<u8>>> + Send;
 
     fn reset_stats(self: &Self) -> HashMap<(PartyId, FuncId), u64>;
 
@@ -119,7 +139,7 @@ impl<I: AsyncRead + Unpin + Send + 'static, O: AsyncWrite + Unpin + Send + 'stat
         Ok((buf, s))
     }
 
-    async fn send_to_local<B: AsRef<[u8]>>(
+    async fn send_to_local<B: AsRef<[u8]> + Send>(
         self: &Self,
         party: PartyId,
         func: FuncId,
@@ -146,7 +166,7 @@ impl<I: AsyncRead + Unpin + Send + 'static, O: AsyncWrite + Unpin + Send + 'stat
         Ok(())
     }
 
-    async fn recv_from_local<B: AsMut<[u8]>>(
+    async fn recv_from_local<B: AsMut<[u8]> + Send>(
         self: &Self,
         party: PartyId,
         func: FuncId,
@@ -182,6 +202,82 @@ impl<I: AsyncRead + Unpin + Send + 'static, O: AsyncWrite + Unpin + Send + 'stat
 
         Ok((buf, size))
     }
+
+    async fn send_to_session<B: AsRef<[u8]> + Send>(
+        self: Arc<Self>,
+        party: PartyId,
+        sid: SessionId,
+        data: B,
+    ) -> io::Result<()> {
+        let data = data.as_ref();
+        let mut msg = Vec::with_capacity(8 + data.len());
+        msg.extend_from_slice(&sid.id.to_le_bytes());
+        msg.extend_from_slice(data);
+
+        self.send_to_local(party, sid.parent, msg).await
+    }
+
+    async fn recv_from_session(
+        self: Arc<Self>,
+        party: PartyId,
+        sid: SessionId,
+    ) -> io::Result<Vec<u8>> {
+        let func = sid.parent;
+        loop {
+            // the pending messages are checked while holding the channel, so none can be set
+            // aside for us after checking and before we read
+            let mut other = self.recvs[&(party, func)].lock().await;
+
+            let pending = self
+                .pending
+                .lock()
+                .unwrap()
+                .get_mut(&(party, sid))
+                .and_then(|msgs| msgs.pop_front());
+            if let Some(msg) = pending {
+                return Ok(msg);
+            }
+
+            let mut lb = [0u8; 4];
+            other.read_exact(&mut lb).await?;
+            let size: usize = u32::from_le_bytes(lb).try_into().unwrap();
+            if size < 8 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("message from ({func:?}, {party}) is missing its session"),
+                ));
+            }
+
+            let mut msg = vec![0u8; size];
+            other.read_exact(&mut msg).await?;
+            self.net_bytes[&(party, func)].fetch_add(size as u64, Ordering::SeqCst);
+
+            let id = u64::from_le_bytes(msg[..8].try_into().unwrap());
+            let data = msg.split_off(8);
+
+            trace!(
+                "{}: recv from ({:?}, {}) session {id}, size {}",
+                self.party_id,
+                func,
+                party,
+                data.len(),
+            );
+
+            if id == sid.id {
+                return Ok(data);
+            }
+
+            // let any receivers waiting on the channel pick up what was set aside for them
+            let other_sid = SessionId { parent: func, id };
+            self.pending
+                .lock()
+                .unwrap()
+                .entry((party, other_sid))
+                .or_default()
+                .push_back(data);
+        }
+    }
+
     fn reset_stats(self: &Self) -> HashMap<(PartyId, FuncId), u64> {
         // retrieve and reset stats
         self.net_bytes
@@ -264,6 +360,7 @@ impl<I: AsyncRead, O: AsyncWrite> AsyncNetworkMgr<I, O> {
                 .map(|(k, v)| (k, Mutex::new(v)))
                 .collect(),
             net_bytes: net_bytes,
+            pending: std::sync::Mutex::new(HashMap::new()),
         })
     }
 }
@@ -369,4 +466,32 @@ pub mod tests {
 
         Ok(())
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn send_recv_sessions() {
+        let party_info = get_test_party_infos(2);
+        let nets = build_test_nets(&party_info, vec![FuncId::Ftest]).await;
+        let sid = SessionId::new(FuncId::Ftest);
+
+        // sessions are sent in the opposite order to the one they are received in
+        for i in 0..3u8 {
+            let sid_i = SessionId {
+                id: i as u64,
+                ..sid
+            };
+            nets[0]
+                .clone()
+                .send_to_session(2, sid_i, [i; 4])
+                .await
+                .unwrap();
+        }
+        for i in (0..3u8).rev() {
+            let sid_i = SessionId {
+                id: i as u64,
+                ..sid
+            };
+            let data = nets[1].clone().recv_from_session(1, sid_i).await.unwrap();
+            assert_eq!(data, [i; 4]);
+        }
+    }
 }
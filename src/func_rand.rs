@@ -7,6 +7,7 @@ use crate::{
 
 use std::{
     collections::HashMap,
+    future::Future,
     sync::{Arc, Mutex},
 };
 
@@ -35,12 +36,16 @@ impl<FC> BaseFunc for FolkloreRandPlayer<FC> {
 
 /// Trait to represent coin tossing, where a group of parties
 /// will agree upon a shared random value.
-pub trait AsyncRand {
+pub trait AsyncRand: Send + Sync {
     /// Start a new instance with `sid`
-    async fn init(&self, sid: SessionId) -> Result<(), UnexpectedError>;
+    fn init(&self, sid: SessionId) -> impl Future<Output = Result<(), UnexpectedError>> + Send;
 
     /// Sample the next set of bytes
-    async fn rand(&self, sid: SessionId, num_bytes: usize) -> Result<Vec<u8>, UnexpectedError>;
+    fn rand(
+        &self,
+        sid: SessionId,
+        num_bytes: usize,
+    ) -> impl Future<Output = Result<Vec<u8>, UnexpectedError>> + Send;
 }
 
 impl<FC: AsyncCom> AsyncRand for FolkloreRandPlayer<FC> {
@@ -1,19 +1,28 @@
 use crate::{
     base_func::{BaseFunc, FuncId, SessionId, UnexpectedError},
     circuits::{
-        generic_thresh::{add_validation_project, init_circuit, sample_circuit, setup_circuit},
-        CircuitCollection, CircuitElement, CircuitRing, TCircuit,
+        generic_thresh::{
+            add_validation_project, add_validation_project_plain, eval_state_circuit, init_circuit,
+            refresh_circuit, reshare_circuit, reveal_circuit, sample_circuit,
+            setup_circuit_with_sizes, AuthInput, SetupCircuit,
+        },
+        new_builder, CircuitCollection, CircuitElement, CircuitRing, Gate, TCircuit,
     },
     field::{Field, RandElement},
-    func_mpc::AsyncMpc,
-    func_thresh::{AsyncThresh, InputId},
+    func_mpc::{AsyncMpc, MpcInstance},
+    func_thresh::{
+        agree_on_bits, agree_on_ids, arrange_inputs, call_sid, check_input_sizes,
+        state::{decode_state, encode_state, StateBackend, StateHeader, StateKey},
+        store::{StateRecords, StateStore},
+        AsyncReshare, AsyncThresh, EvalInput, InputId, Sessions,
+    },
     party::PartyId,
     polynomial::lagrange_poly,
 };
 
 use std::{
-    collections::HashMap,
-    fs::File,
+    collections::{HashMap, HashSet},
+    fs::{self, File},
     sync::{
         atomic::{AtomicU64, Ordering},
         Arc,
@@ -40,6 +49,9 @@ pub struct GenericThreshPlayer<T, FM> {
     lp_coeff: T,
     mpc: Arc<FM>,
     cur_mpc_sid: AtomicU64,
+    sessions: Sessions,
+    state_key: Option<StateKey>,
+    store: Option<(Arc<dyn StateStore>, String)>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -51,8 +63,14 @@ struct State<T> {
     // maps an input id to the (idx, bit) of the shares
     // so (2, 100) would be the 101st bit of share_inputs[2]
     input_share_idx: HashMap<InputId, (usize, usize)>,
+    // the number of times the shares have been refreshed
+    #[serde(default)]
+    epoch: u64,
 }
 
+// The shares added by a `setup` or `sample`, with the (idx, bit) of the ids they hold
+type Batch<T> = (Vec<(T, T)>, HashMap<InputId, (usize, usize)>);
+
 impl<T, FM> BaseFunc for GenericThreshPlayer<T, FM> {
     const FUNC_ID: FuncId = FuncId::Fthresh;
     const REQUIRED_FUNCS: &'static [FuncId] = &[FuncId::Fmpc];
@@ -70,9 +88,9 @@ pub trait Foo: CircuitElement {
 */
 
 // Compiler sometimes crashes if this is T: CircuitRing for some reason, but works with T: Foo which is identical to CircuitRing :shrug:
-impl<T, FM: AsyncMpc<T>> AsyncThresh for GenericThreshPlayer<T, FM>
+impl<T, FM: AsyncMpc<T> + Send + Sync> AsyncThresh for GenericThreshPlayer<T, FM>
 where
-    T: Field + RandElement + CircuitRing + Copy + Serialize,
+    T: Field + RandElement + CircuitRing + Copy + Serialize + Send + Sync,
     for<'d> T: Deserialize<'d>,
 {
     /// Write out the state of any authenticated shares
@@ -85,6 +103,31 @@ where
 
         let sid = SessionId::new(FuncId::Fthresh);
 
+        let bytes = encode_state(&self.state_header(), state, self.state_key.as_ref())
+            .with_context(|| self.err(sid, "Failed to encode state"))?;
+
+        fs::write(state_file, bytes)
+            .with_context(|| self.err(sid, "Failed to write state file"))?;
+
+        Ok(())
+    }
+
+    fn set_state_key(&mut self, key: StateKey) {
+        self.state_key = Some(key);
+    }
+
+    fn export_state_json(&self, state_file: &str) -> Result<(), UnexpectedError> {
+        if self.state.is_none() {
+            return Err(anyhow!("Init/resume has not been run, no state to write").into());
+        }
+
+        let state = self.state.as_ref().unwrap();
+
+        let sid = SessionId::new(FuncId::Fthresh);
+        if self.state_key.is_some() {
+            return Err(self.unexpected(sid, "Refusing to export encrypted state as plain JSON"));
+        }
+
         let f = File::create(state_file)
             .with_context(|| self.err(sid, "Failed to create state file"))?;
 
@@ -102,15 +145,47 @@ where
     ) -> Result<(), UnexpectedError> {
         let sid = SessionId::new(FuncId::Fthresh);
 
-        let f =
-            File::open(state_file).with_context(|| self.err(sid, "Failed to open state file"))?;
+        let bytes =
+            fs::read(state_file).with_context(|| self.err(sid, "Failed to open state file"))?;
 
-        let state: State<T> = serde_json::from_reader(f)
+        let (header, state): (_, State<T>) = decode_state(&bytes, self.state_key.as_ref())
             .with_context(|| self.err(sid, "Failed to deserialize state file"))?;
+        if let Some(header) = header {
+            header
+                .check(&self.state_header())
+                .with_context(|| self.err(sid, "State file does not match this party"))?;
+        }
 
-        self.state = Some(state);
+        self.restore(sid, state)
+    }
 
-        Ok(())
+    fn set_state_store(&mut self, store: Arc<dyn StateStore>, prefix: &str) {
+        self.store = Some((store, prefix.to_string()));
+    }
+
+    async fn resume_from_store(&mut self, _run_init: bool) -> Result<(), UnexpectedError> {
+        let sid = SessionId::new(FuncId::Fthresh);
+
+        let records = self
+            .records()
+            .ok_or_else(|| self.unexpected(sid, "No state store to resume from"))?;
+        let ((share_alpha, epoch), batches): (_, Vec<Batch<T>>) = records
+            .load()
+            .with_context(|| self.err(sid, "Failed to load state from store"))?
+            .ok_or_else(|| self.unexpected(sid, "No state in store"))?;
+
+        let mut state = State {
+            share_alpha,
+            share_inputs: Vec::new(),
+            input_share_idx: HashMap::new(),
+            epoch,
+        };
+        for (share_inputs, input_share_idx) in batches {
+            state.share_inputs.extend(share_inputs);
+            state.input_share_idx.extend(input_share_idx);
+        }
+
+        self.restore(sid, state)
     }
 
     async fn init(&mut self) -> Result<(), UnexpectedError> {
@@ -119,11 +194,8 @@ where
         }
 
         let sid = SessionId::new(FuncId::Fthresh);
-        let mpc_sid = self.cur_mpc_sid.fetch_add(1, Ordering::SeqCst);
-        let mpc_sid = SessionId {
-            parent: FuncId::Fthresh,
-            id: mpc_sid,
-        };
+        let mpc = self.next_mpc();
+        let mpc_sid = mpc.sid;
 
         let _ = self.mpc.init(mpc_sid, None).await?;
 
@@ -161,11 +233,12 @@ where
             share_alpha,
             share_inputs: Vec::new(),
             input_share_idx: HashMap::new(),
+            epoch: 0,
         };
 
         self.state = Some(state);
 
-        Ok(())
+        self.persist_all(sid)
     }
 
     async fn setup<I, O: CircuitElement>(
@@ -173,35 +246,48 @@ where
         input: &[bool],
         out_ids: &[InputId],
         circuit: &TCircuit<I, O>,
+    ) -> Result<(), UnexpectedError> {
+        let input_sizes = vec![input.len(); self.parties.len()];
+        self.setup_with_sizes(input, &input_sizes, out_ids, circuit)
+            .await
+    }
+
+    async fn setup_with_sizes<I, O: CircuitElement>(
+        &mut self,
+        input: &[bool],
+        input_sizes: &[usize],
+        out_ids: &[InputId],
+        circuit: &TCircuit<I, O>,
     ) -> Result<(), UnexpectedError> {
         if self.state.is_none() {
             return Err(anyhow!("Init/resume not run").into());
         }
-        let in_size = input.len();
-        // Simplify for now, assume all parties have the  same number of inputs
-        assert!(circuit.inputs.len() == in_size * self.parties.len());
+        let sid = SessionId::new(FuncId::Fthresh);
+        check_input_sizes(
+            &self.parties,
+            self.party_id,
+            input,
+            input_sizes,
+            circuit.inputs.len(),
+        )
+        .with_context(|| self.err(sid, "Invalid setup inputs"))?;
         assert!(circuit.outputs.len() == out_ids.len());
         let alpha = self.state.as_ref().unwrap().share_alpha.clone();
 
-        let sid = SessionId::new(FuncId::Fthresh);
-        let mpc_sid = self.cur_mpc_sid.fetch_add(1, Ordering::SeqCst);
-        let mpc_sid = SessionId {
-            parent: FuncId::Fthresh,
-            id: mpc_sid,
-        };
+        let mpc = self.next_mpc();
+        let mpc_sid = mpc.sid;
         self.mpc
             .init(mpc_sid, None)
             .await
             .with_context(|| self.err(sid, format!("Failed to initialize Fmpc {mpc_sid}")))?;
 
-        for &p in self.parties.iter() {
-            let input = if p == self.party_id {
-                Some(input.to_vec())
-            } else {
-                None
-            };
+        for (&p, &size) in self.parties.iter().zip(input_sizes.iter()) {
+            if size == 0 {
+                continue;
+            }
+            let input = (p == self.party_id).then(|| input.to_vec());
             self.mpc
-                .input_multi(mpc_sid, p, in_size, input)
+                .input_multi(mpc_sid, p, size, input)
                 .await
                 .with_context(|| {
                     self.err(
@@ -251,25 +337,20 @@ where
             }
         }
 
-        let circuit: TCircuit<(I, Vec<(T, Vec<T>)>), Vec<Vec<(T, T)>>> = setup_circuit(
-            self.n.into(),
-            in_size,
-            self.t.into(),
-            &self.party_points,
-            circuit,
-        );
+        let circuit: SetupCircuit<T, I> =
+            setup_circuit_with_sizes(input_sizes, self.t.into(), &self.party_points, circuit);
 
         let num_outputs = circuit.outputs.len();
         let party_outs = T::BIT_SIZE * num_shares * 2;
         let output_assignment = (0..num_outputs)
-            .map(|w| (w, 1 + (w / party_outs) as i32))
+            .map(|w| (w, self.parties[w / party_outs] as i32))
             .collect();
         let parse_fn =
             |bits: &[bool]| <Vec<(T, T)> as CircuitCollection>::from_bits(num_shares, bits);
 
         trace!("running mpc eval on setup circuit");
 
-        let mut output: Vec<(T, T)> = self
+        let output: Vec<(T, T)> = self
             .mpc
             .eval_generic(
                 mpc_sid,
@@ -280,17 +361,7 @@ where
             )
             .await?;
 
-        let sr = self.state.as_mut().unwrap();
-        let old_len = sr.share_inputs.len();
-        sr.share_inputs.append(&mut output);
-
-        let fsize = T::BIT_SIZE;
-        for (i, &id) in out_ids.iter().enumerate() {
-            sr.input_share_idx
-                .insert(id, (old_len + (i / fsize), i % fsize));
-        }
-
-        Ok(())
+        self.add_batch(sid, output, out_ids)
     }
 
     async fn sample(&mut self, ids: &[InputId]) -> Result<(), UnexpectedError> {
@@ -302,11 +373,8 @@ where
         let in_size = ids.len();
 
         let sid = SessionId::new(FuncId::Fthresh);
-        let mpc_sid = self.cur_mpc_sid.fetch_add(1, Ordering::SeqCst);
-        let mpc_sid = SessionId {
-            parent: FuncId::Fthresh,
-            id: mpc_sid,
-        };
+        let mpc = self.next_mpc();
+        let mpc_sid = mpc.sid;
 
         self.mpc
             .init(mpc_sid, None)
@@ -378,11 +446,11 @@ where
         let num_outputs = circuit.outputs.len();
         let party_outs = T::BIT_SIZE * num_shares * 2;
         let output_assignment = (0..num_outputs)
-            .map(|w| (w, 1 + (w / party_outs) as i32))
+            .map(|w| (w, self.parties[w / party_outs] as i32))
             .collect();
         let parse_fn =
             |bits: &[bool]| <Vec<(T, T)> as CircuitCollection>::from_bits(num_shares, bits);
-        let mut output: Vec<(T, T)> = self
+        let output: Vec<(T, T)> = self
             .mpc
             .eval_generic(
                 mpc_sid,
@@ -393,197 +461,1409 @@ where
             )
             .await?;
 
-        let sr = self.state.as_mut().unwrap();
-
-        let old_len = sr.share_inputs.len();
-        sr.share_inputs.append(&mut output);
-
-        let fsize = T::BIT_SIZE;
-        for (i, &id) in ids.iter().enumerate() {
-            sr.input_share_idx
-                .insert(id, (old_len + (i / fsize), i % fsize));
-        }
-
-        Ok(())
+        self.add_batch(sid, output, ids)
     }
 
     async fn eval<I, O: CircuitElement>(
-        &mut self,
+        &self,
         parties: &[PartyId],
         ids: &[InputId],
         circuit: &TCircuit<I, O>,
     ) -> Result<O, UnexpectedError> {
         assert_eq!(ids.len(), circuit.inputs.len());
 
-        if self.state.is_none() {
-            return Err(anyhow!("Init/resume not run").into());
+        let session = self.sessions.start(("eval", parties, ids, circuit)).await;
+        let sid = session.sid;
+
+        let (mpc, num_shares, share_bits) = self.input_shares(sid, parties, ids).await?;
+        let mpc_sid = mpc.sid;
+
+        let augmented_circuit: TCircuit<AuthInput<T>, Option<O>> =
+            add_validation_project(parties.len(), num_shares, &share_bits, circuit);
+
+        let output = self
+            .mpc
+            .eval_pub(mpc_sid, parties, &augmented_circuit)
+            .await?;
+
+        if output.is_none() {
+            // cheat
+            return Err(anyhow!("Cheat in input").into());
         }
-        // If we do not have the threshold of parties, abort
-        if parties.len() < self.t.into() {
-            return Err(anyhow!("Insufficient number of parties to run evaluation").into());
+
+        Ok(output.unwrap())
+    }
+
+    async fn eval_with_inputs<I, O: CircuitElement>(
+        &self,
+        parties: &[PartyId],
+        inputs: &[EvalInput],
+        private_input: &[bool],
+        circuit: &TCircuit<I, O>,
+    ) -> Result<O, UnexpectedError> {
+        let arranged = arrange_inputs(parties, self.party_id, inputs, private_input, circuit)?;
+
+        let session = self
+            .sessions
+            .start(("eval_with_inputs", parties, inputs, circuit))
+            .await;
+        let sid = session.sid;
+
+        // the authenticated shares of the stored inputs come first, then the private inputs
+        let (mpc, num_shares, share_bits) = self.input_shares(sid, parties, &arranged.ids).await?;
+        let mpc_sid = mpc.sid;
+        for (&p, &size) in parties.iter().zip(arranged.private_sizes.iter()) {
+            if size > 0 {
+                let input = (p == self.party_id).then(|| private_input.to_vec());
+                self.mpc.input_multi(mpc_sid, p, size, input).await?;
+            }
         }
 
-        // If I am not part of the computation I should track that execution has occurred
-        // but cannot return a value
-        if !parties.contains(&self.party_id) {
-            self.cur_mpc_sid.fetch_add(1, Ordering::SeqCst);
-            return Err(
-                anyhow!("I am not one of the parties that is supposed to execute eval").into(),
+        let num_private = arranged.private_sizes.iter().sum();
+        let augmented_circuit: TCircuit<(AuthInput<T>, Vec<bool>), Option<O>> =
+            add_validation_project_plain(
+                parties.len(),
+                num_shares,
+                &share_bits,
+                num_private,
+                &arranged.circuit,
             );
+
+        let output = self
+            .mpc
+            .eval_pub(mpc_sid, parties, &augmented_circuit)
+            .await?;
+
+        output.ok_or_else(|| anyhow!("Cheat in input").into())
+    }
+
+    async fn eval_to<I, O: CircuitElement>(
+        &self,
+        parties: &[PartyId],
+        ids: &[InputId],
+        circuit: &TCircuit<I, O>,
+        recipient: PartyId,
+    ) -> Result<Option<O>, UnexpectedError> {
+        assert_eq!(ids.len(), circuit.inputs.len());
+
+        if !parties.contains(&recipient) {
+            return Err(anyhow!("{recipient} is not one of the parties to evaluate for").into());
         }
 
-        // calculate the lagrange coefficient for the given set of parties
-        let party_points: Vec<_> = parties.iter().map(|&p| T::from(p.into())).collect();
+        let session = self
+            .sessions
+            .start(("eval_to", parties, ids, circuit, recipient))
+            .await;
+        let sid = session.sid;
 
-        let lp = lagrange_poly(
-            &party_points,
-            &self.party_points[(self.party_id - 1) as usize],
-            |x| *x,
-        );
+        let (mpc, num_shares, share_bits) = self.input_shares(sid, parties, ids).await?;
+        let mpc_sid = mpc.sid;
 
-        let state = self.state.as_ref().unwrap();
+        let augmented_circuit: TCircuit<AuthInput<T>, Option<O>> =
+            add_validation_project(parties.len(), num_shares, &share_bits, circuit);
 
-        let alpha = state.share_alpha.clone();
+        // every output, including whether the inputs were valid, goes to `recipient` alone
+        let output_assignment = (0..augmented_circuit.outputs.len())
+            .map(|w| (w, recipient as i32))
+            .collect();
+        let is_recipient = recipient == self.party_id;
+        let parse_fn = |bits: &[bool]| is_recipient.then(|| Option::<O>::from_bits(bits));
 
-        // accumulate the elements that contain the bits according to id
-        // map of original index into share_inputs to index into xs/macs
-        let mut found = HashMap::new();
-        // the shares of x and ax
-        let mut xs = Vec::new();
-        let mut macs = Vec::new();
-        // the list of bits to gather after validation
-        let mut share_bits = Vec::new();
-        let fsize = T::BIT_SIZE;
+        let output = self
+            .mpc
+            .eval_generic(
+                mpc_sid,
+                parties,
+                &augmented_circuit,
+                output_assignment,
+                parse_fn,
+            )
+            .await?;
 
-        for id in ids.iter() {
-            let (idx, bit) = state.input_share_idx[id];
-            if !found.contains_key(&idx) {
-                let j = xs.len();
-                found.insert(idx, j);
-                let xy = &state.share_inputs[idx];
-                xs.push(lp.clone() * xy.0);
-                macs.push(lp.clone() * xy.1);
-                share_bits.push(fsize * j + bit);
-            } else {
-                let si = found[&idx];
-                share_bits.push(si * fsize + bit);
-            }
+        match output {
+            Some(None) => Err(anyhow!("Cheat in input").into()),
+            Some(out) => Ok(out),
+            None => Ok(None),
         }
+    }
 
-        let sid = self.cur_mpc_sid.fetch_add(1, Ordering::SeqCst);
-        let sid = SessionId {
-            parent: FuncId::Fthresh,
-            id: sid,
-        };
-
-        let _ = self.mpc.init(sid, None).await?;
+    async fn eval_to_state<I, O: CircuitElement>(
+        &mut self,
+        parties: &[PartyId],
+        in_ids: &[InputId],
+        circuit: &TCircuit<I, O>,
+        out_ids: &[InputId],
+    ) -> Result<(), UnexpectedError> {
+        assert_eq!(in_ids.len(), circuit.inputs.len());
+        assert_eq!(out_ids.len(), circuit.outputs.len());
 
-        for &i in parties.iter() {
-            if i == self.party_id {
-                let _ = self.mpc.input(sid, i, Some(alpha.clone() * &lp)).await;
-                let _ = self
-                    .mpc
-                    .input_multi(sid, i, xs.len(), Some(xs.clone()))
-                    .await;
-                let _ = self
-                    .mpc
-                    .input_multi(sid, i, macs.len(), Some(macs.clone()))
-                    .await;
-            } else {
-                let _ = self.mpc.input::<T>(sid, i, None).await;
-                let _ = self.mpc.input_multi::<Vec<T>>(sid, i, xs.len(), None).await;
-                let _ = self
-                    .mpc
-                    .input_multi::<Vec<T>>(sid, i, macs.len(), None)
-                    .await;
-            }
+        if self.state.is_none() {
+            return Err(anyhow!("Init/resume not run").into());
+        }
+        if parties.len() < self.t.into() || !parties.iter().all(|p| self.parties.contains(p)) {
+            return Err(anyhow!("{parties:?} is not a quorum of the parties").into());
         }
 
-        let augmented_circuit: TCircuit<Vec<(T, Vec<T>, Vec<T>)>, Option<O>> =
-            add_validation_project(parties.len(), xs.len(), &share_bits, circuit);
+        let sid = SessionId::new(FuncId::Fthresh);
+        let output = self
+            .eval_shares(sid, parties, in_ids, circuit)
+            .await
+            .with_context(|| self.err(sid, format!("Failed to evaluate into {out_ids:?}")))?;
 
-        let output = self.mpc.eval_pub(sid, &parties, &augmented_circuit).await?;
+        self.add_batch(sid, output, out_ids)
+    }
 
-        if output.is_none() {
-            // cheat
-            return Err(anyhow!("Cheat in input").into());
+    async fn reveal_to(
+        &self,
+        parties: &[PartyId],
+        party: PartyId,
+        ids: &[InputId],
+    ) -> Result<Option<Vec<bool>>, UnexpectedError> {
+        if !parties.contains(&party) {
+            return Err(anyhow!("{party} is not one of the parties to reveal to").into());
         }
 
-        Ok(output.unwrap())
-    }
-}
+        let session = self
+            .sessions
+            .start(("reveal_to", parties, party, ids))
+            .await;
+        let sid = session.sid;
 
-impl<T: Field + RandElement + Copy, FM: AsyncMpc<T>> GenericThreshPlayer<T, FM> {
-    pub fn new(party_id: PartyId, n: PartyId, t: u16, mpc: Arc<FM>) -> Result<Self, ()> {
-        let parties: Vec<PartyId> = (1..n + 1).collect();
-        let party_points: Vec<T> = (1..n + 1).map(|x| T::from(x.into())).collect();
-        let lp = lagrange_poly(&party_points, &party_points[(party_id - 1) as usize], |x| {
-            *x
-        });
+        let (mpc, num_shares, share_bits) = self.input_shares(sid, parties, ids).await?;
+        let mpc_sid = mpc.sid;
 
-        Ok(GenericThreshPlayer {
-            party_id: party_id,
-            n: n,
-            t: t,
-            parties,
-            party_points,
-            lp_coeff: lp,
-            mpc: mpc,
-            state: None,
-            cur_mpc_sid: AtomicU64::new(0),
-        })
-    }
-}
+        let circuit = reveal_circuit::<T>(parties.len(), num_shares, &share_bits);
 
-#[cfg(test)]
-pub mod tests {
-    use super::*;
-    use crate::{
-        circuits::{aes::aes_key_schedule, arith::sum_circuit, new_builder, Gate},
-        ff2_128::FF2_128,
-        func_mpc::tests::build_test_mpcs,
-        func_net::tests::{build_test_nets, get_test_party_infos},
-    };
-    use tokio::{io, task::JoinSet};
+        // every output, including whether the inputs were valid, goes to `party` alone
+        let output_assignment = (0..circuit.outputs.len())
+            .map(|w| (w, party as i32))
+            .collect();
+        let is_target = party == self.party_id;
+        let parse_fn = |bits: &[bool]| is_target.then(|| (bits[0], bits[1..].to_vec()));
 
-    pub fn build_test_comcomps<FM: AsyncMpc<FF2_128>>(
-        mpcs: &[Arc<FM>],
-        t: PartyId,
-    ) -> Vec<GenericThreshPlayer<FF2_128, FM>> {
-        let num = mpcs.len();
-        (1..=num)
-            .map(|i| {
-                GenericThreshPlayer::new(i as PartyId, num as PartyId, t, mpcs[i - 1].clone())
-                    .unwrap()
-            })
-            .collect()
+        let out = self
+            .mpc
+            .eval_generic(mpc_sid, parties, &circuit, output_assignment, parse_fn)
+            .await
+            .with_context(|| self.err(sid, format!("Failed to reveal {ids:?} to {party}")))?;
+
+        match out {
+            Some((false, _)) => Err(self.unexpected(sid, "Cheat in revealed input")),
+            Some((true, bits)) => Ok(Some(bits)),
+            None => Ok(None),
+        }
     }
 
-    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
-    async fn test_comcomp_execution() -> io::Result<()> {
-        let party_info = get_test_party_infos(3);
-        let mpcs = build_test_mpcs(&party_info);
-        let comcomps = build_test_comcomps(&mpcs, 2);
+    async fn delete(&mut self, ids: &[InputId]) -> Result<(), UnexpectedError> {
+        if self.state.is_none() {
+            return Err(anyhow!("Init/resume not run").into());
+        }
 
-        let mut js = JoinSet::<Result<_, UnexpectedError>>::new();
-        for mut cc in comcomps.into_iter() {
-            js.spawn(async move {
-                let _ = cc.init().await?;
-                let ids: Vec<_> = (1..=256).collect();
-                let _ = cc.sample(&ids).await?;
-                let c = sum_circuit::<FF2_128>(2);
-                cc.eval(&[1, 2, 3], &ids, &c).await
-            });
+        let sid = SessionId::new(FuncId::Fthresh);
+        let idx = &self.state.as_ref().unwrap().input_share_idx;
+        if let Some(id) = ids.iter().find(|&id| !idx.contains_key(id)) {
+            return Err(self.unexpected(sid, format!("No input with id {id} to delete")));
         }
+        trace!("{}: delete {} inputs ({sid})", self.party_id, ids.len());
 
-        let mut res = Vec::new();
-        while let Some(x) = js.join_next().await {
-            res.push(x.unwrap().unwrap());
+        let mpc = self.next_mpc();
+        let mpc_sid = mpc.sid;
+        self.mpc
+            .init(mpc_sid, None)
+            .await
+            .with_context(|| self.err(sid, format!("Failed to initialize Fmpc {mpc_sid}")))?;
+        agree_on_ids(&*self.mpc, mpc_sid, self.party_id, &self.parties, ids)
+            .await
+            .with_context(|| self.err(sid, "Parties disagree on the inputs to delete"))?;
+
+        // the elements of the shares that held a deleted bit
+        let sr = self.state.as_ref().unwrap();
+        let deleted: HashSet<_> = ids.iter().collect();
+        let dirty: HashSet<_> = ids.iter().map(|id| sr.input_share_idx[id].0).collect();
+
+        // the inputs left in those elements are stored afresh, so the elements can be dropped
+        let mut moved: Vec<_> = sr
+            .input_share_idx
+            .iter()
+            .filter(|(id, (el, _))| !deleted.contains(id) && dirty.contains(el))
+            .map(|(&id, _)| id)
+            .collect();
+        moved.sort_unstable();
+        if !moved.is_empty() {
+            self.move_inputs(sid, &moved).await?;
         }
 
-        // check that all parties got the same output
-        assert_eq!(res[0], res[1]);
-        assert_eq!(res[0], res[2]);
+        // nothing is removed until the moved inputs are safely stored, then the elements
+        // that no input refers to are dropped
+        let sr = self.state.as_mut().unwrap();
+        for id in ids.iter() {
+            sr.input_share_idx.remove(id);
+        }
+        let mut kept: Vec<_> = sr
+            .input_share_idx
+            .iter()
+            .map(|(&id, &loc)| (loc, id))
+            .collect();
+        kept.sort_unstable();
+        let mut els: Vec<_> = kept.iter().map(|&((el, _), _)| el).collect();
+        els.dedup();
+        sr.share_inputs = els.iter().map(|&el| sr.share_inputs[el]).collect();
+        sr.input_share_idx = kept
+            .iter()
+            .map(|&((el, bit), id)| (id, (els.binary_search(&el).unwrap(), bit)))
+            .collect();
+
+        self.persist_all(sid)
+    }
+
+    fn epoch(&self) -> u64 {
+        self.state.as_ref().map_or(0, |s| s.epoch)
+    }
+
+    async fn refresh(&mut self) -> Result<(), UnexpectedError> {
+        if self.state.is_none() {
+            return Err(anyhow!("Init/resume not run").into());
+        }
+        let num_shares = self.state.as_ref().unwrap().share_inputs.len();
+        let num_zeros = 1 + 2 * num_shares;
+
+        let sid = SessionId::new(FuncId::Fthresh);
+        let mpc = self.next_mpc();
+        let mpc_sid = mpc.sid;
+
+        self.mpc
+            .init(mpc_sid, None)
+            .await
+            .with_context(|| self.err(sid, format!("Failed to initialize Fmpc {mpc_sid}")))?;
+
+        let fs_size: usize = num_zeros * (self.t as usize - 1);
+
+        for &i in self.parties.iter() {
+            let fs = if i == self.party_id {
+                // the non-constant coefficients of our zero polynomials
+                let mut rng = rand::thread_rng();
+                Some((0..fs_size).map(|_| T::rand(&mut rng)).collect::<Vec<T>>())
+            } else {
+                None
+            };
+            self.mpc
+                .input_multi(mpc_sid, i, fs_size, fs)
+                .await
+                .with_context(|| {
+                    self.err(sid, format!("Failed to input polys for {i} {mpc_sid}"))
+                })?;
+        }
+
+        let circuit: TCircuit<Vec<Vec<T>>, Vec<Vec<T>>> =
+            refresh_circuit(self.n.into(), num_shares, self.t.into(), &self.party_points);
+
+        let num_outputs = circuit.outputs.len();
+        let party_outs = T::BIT_SIZE * num_zeros;
+        let output_assignment = (0..num_outputs)
+            .map(|w| (w, self.parties[w / party_outs] as i32))
+            .collect();
+        let parse_fn = |bits: &[bool]| <Vec<T> as CircuitCollection>::from_bits(num_zeros, bits);
+
+        trace!("running mpc eval on refresh circuit");
+
+        let zeros: Vec<T> = self
+            .mpc
+            .eval_generic(
+                mpc_sid,
+                &self.parties,
+                &circuit,
+                output_assignment,
+                parse_fn,
+            )
+            .await
+            .with_context(|| self.err(sid, format!("Failed to refresh epoch {}", self.epoch())))?;
+
+        let sr = self.state.as_mut().unwrap();
+        sr.share_alpha += zeros[0];
+        for (xy, z) in sr.share_inputs.iter_mut().zip(zeros[1..].chunks_exact(2)) {
+            xy.0 += z[0];
+            xy.1 += z[1];
+        }
+        sr.epoch += 1;
+
+        self.persist_all(sid)
+    }
+}
+
+impl<T, FM: AsyncMpc<T> + Send + Sync> AsyncReshare for GenericThreshPlayer<T, FM>
+where
+    T: Field + RandElement + CircuitRing + Copy + Serialize + Send + Sync,
+    for<'d> T: Deserialize<'d>,
+{
+    async fn reshare(
+        &mut self,
+        old_parties: &[PartyId],
+        new_parties: &[PartyId],
+        new_t: u16,
+        ids: Option<&[InputId]>,
+    ) -> Result<(), UnexpectedError> {
+        // a joining party shares no history with the old committee, so every party derives the
+        // session from the call itself
+        let sid = call_sid(("reshare", old_parties, new_parties, new_t, ids));
+        // one Fmpc instance each to agree on the number of inputs and on their layout, and one
+        // to move them
+        let header_mpc = MpcInstance::new(self.mpc.clone(), sid.derive_ssid(FuncId::Fthresh));
+        let layout_mpc = MpcInstance::new(self.mpc.clone(), header_mpc.sid.next());
+        let mpc = MpcInstance::new(self.mpc.clone(), layout_mpc.sid.next());
+        let (header_sid, layout_sid, mpc_sid) = (header_mpc.sid, layout_mpc.sid, mpc.sid);
+
+        let is_old = old_parties.contains(&self.party_id);
+        let is_new = new_parties.contains(&self.party_id);
+        if !is_old && !is_new {
+            return Err(self.unexpected(
+                sid,
+                "I am not one of the parties that is supposed to execute reshare",
+            ));
+        }
+        if is_old && self.state.is_none() {
+            return Err(self.unexpected(sid, "Init/resume not run"));
+        }
+        if is_old && old_parties.len() < self.t.into() {
+            return Err(self.unexpected(sid, "Insufficient number of parties to reshare"));
+        }
+        if new_t == 0 || new_parties.len() < new_t.into() {
+            return Err(self.unexpected(
+                sid,
+                format!(
+                    "Invalid threshold {new_t} for {} parties",
+                    new_parties.len()
+                ),
+            ));
+        }
+
+        // the old parties move all of their inputs unless told which ones to move
+        let my_ids = match (self.state.as_ref(), ids) {
+            (_, Some(ids)) => ids.to_vec(),
+            (Some(sr), None) if is_old => {
+                let mut ids: Vec<_> = sr.input_share_idx.keys().cloned().collect();
+                ids.sort_unstable();
+                ids
+            }
+            _ => Vec::new(),
+        };
+        if is_old {
+            let idx = &self.state.as_ref().unwrap().input_share_idx;
+            if let Some(id) = my_ids.iter().find(|&id| !idx.contains_key(id)) {
+                return Err(self.unexpected(sid, format!("No input with id {id} to reshare")));
+            }
+        }
+
+        let mut all_parties: Vec<_> = old_parties.iter().chain(new_parties).cloned().collect();
+        all_parties.sort();
+        all_parties.dedup();
+
+        let old_shares = is_old.then(|| {
+            let old_points: Vec<_> = old_parties.iter().map(|&p| T::from(p.into())).collect();
+            let lp = lagrange_poly(&old_points, &T::from(self.party_id.into()), |x| {
+                T::zero() - x
+            });
+            self.gather_shares(&lp, &my_ids)
+        });
+
+        // The new parties learn the epoch, the ids and which bits of the old elements hold
+        // them from the old parties, who abort if any of them disagree
+        self.mpc
+            .init(header_sid, None)
+            .await
+            .with_context(|| self.err(sid, format!("Failed to initialize Fmpc {header_sid}")))?;
+
+        trace!("running mpc eval to agree on the inputs to reshare");
+
+        let header = encode_header(self.epoch(), my_ids.len());
+        let header = agree_on_bits(
+            &*self.mpc,
+            header_sid,
+            self.party_id,
+            old_parties,
+            &all_parties,
+            HEADER_BITS,
+            &header,
+        )
+        .await
+        .with_context(|| self.err(sid, "Failed to agree on the number of inputs to reshare"))?;
+        let (epoch, count) = decode_header(&header);
+
+        let layout = match &old_shares {
+            Some((.., share_bits)) => encode_layout(&my_ids, share_bits),
+            None => Vec::new(),
+        };
+        let layout = if count == 0 {
+            layout
+        } else {
+            self.mpc.init(layout_sid, None).await.with_context(|| {
+                self.err(sid, format!("Failed to initialize Fmpc {layout_sid}"))
+            })?;
+            agree_on_bits(
+                &*self.mpc,
+                layout_sid,
+                self.party_id,
+                old_parties,
+                &all_parties,
+                count * (ID_BITS + LAYOUT_BITS),
+                &layout,
+            )
+            .await
+            .with_context(|| self.err(sid, "Failed to agree on the layout of the inputs"))?
+        };
+        let (moved_ids, share_bits) = decode_layout(&layout);
+
+        if ids.is_some_and(|ids| ids != moved_ids) {
+            return Err(self.unexpected(
+                sid,
+                "The old parties are resharing different inputs than the ones given",
+            ));
+        }
+
+        let num_shares = share_bits.iter().max().map_or(0, |&b| b / T::BIT_SIZE + 1);
+        let new_num_shares = share_bits.len().div_ceil(T::BIT_SIZE);
+        let fs_size: usize = 2 * new_num_shares * (new_t as usize - 1);
+
+        self.mpc
+            .init(mpc_sid, None)
+            .await
+            .with_context(|| self.err(sid, format!("Failed to initialize Fmpc {mpc_sid}")))?;
+
+        for &i in old_parties.iter() {
+            let (alpha, xs, macs) = match &old_shares {
+                Some((alpha, xs, macs, _)) if i == self.party_id => {
+                    (Some(*alpha), Some(xs.clone()), Some(macs.clone()))
+                }
+                _ => (None, None, None),
+            };
+            self.mpc.input(mpc_sid, i, alpha).await.with_context(|| {
+                self.err(sid, format!("Failed to input alpha for {i} {mpc_sid}"))
+            })?;
+            self.mpc
+                .input_multi(mpc_sid, i, num_shares, xs)
+                .await
+                .with_context(|| self.err(sid, format!("Failed to input xs for {i} {mpc_sid}")))?;
+            self.mpc
+                .input_multi(mpc_sid, i, num_shares, macs)
+                .await
+                .with_context(|| {
+                    self.err(sid, format!("Failed to input macs for {i} {mpc_sid}"))
+                })?;
+        }
+
+        for &i in new_parties.iter() {
+            let (alpha_fs, all_fs) = if i == self.party_id {
+                // a random polynomial for the new MAC key and 2*l for the zero shares
+                let mut rng = rand::thread_rng();
+                let alpha_fs: Vec<T> = (0..new_t).map(|_| T::rand(&mut rng)).collect();
+                let all_fs: Vec<T> = (0..fs_size).map(|_| T::rand(&mut rng)).collect();
+                (Some(alpha_fs), Some(all_fs))
+            } else {
+                (None, None)
+            };
+            self.mpc
+                .input_multi(mpc_sid, i, new_t.into(), alpha_fs)
+                .await
+                .with_context(|| {
+                    self.err(sid, format!("Failed to input alpha for {i} {mpc_sid}"))
+                })?;
+            self.mpc
+                .input_multi(mpc_sid, i, fs_size, all_fs)
+                .await
+                .with_context(|| {
+                    self.err(sid, format!("Failed to input polys for {i} {mpc_sid}"))
+                })?;
+        }
+
+        let new_points: Vec<T> = new_parties.iter().map(|&p| T::from(p.into())).collect();
+        let circuit = reshare_circuit(
+            old_parties.len(),
+            num_shares,
+            &share_bits,
+            new_t.into(),
+            &new_points,
+        );
+
+        // the validity bit is public, then each new party gets its block of outputs
+        let num_outputs = circuit.outputs.len();
+        let party_outs = T::BIT_SIZE * (1 + 2 * new_num_shares);
+        let output_assignment = (0..num_outputs)
+            .map(|w| match w {
+                0 => (w, 0),
+                _ => (w, new_parties[(w - 1) / party_outs] as i32),
+            })
+            .collect();
+        let parse_fn = |bits: &[bool]| {
+            let shares = is_new.then(|| {
+                let share_alpha = <T as CircuitElement>::from_bits(&bits[1..=T::BIT_SIZE]);
+                let share_inputs = <Vec<(T, T)> as CircuitCollection>::from_bits(
+                    new_num_shares,
+                    &bits[1 + T::BIT_SIZE..],
+                );
+                (share_alpha, share_inputs)
+            });
+            (bits[0], shares)
+        };
+
+        trace!("running mpc eval on reshare circuit");
+
+        let (valid, shares) = self
+            .mpc
+            .eval_generic(mpc_sid, &all_parties, &circuit, output_assignment, parse_fn)
+            .await
+            .with_context(|| self.err(sid, format!("Failed to reshare epoch {epoch}")))?;
+
+        if !valid {
+            return Err(self.unexpected(sid, "Cheat in reshared input"));
+        }
+
+        // old parties that are not part of the new committee delete their shares
+        self.state = shares.map(|(share_alpha, share_inputs)| State {
+            share_alpha,
+            share_inputs,
+            input_share_idx: moved_ids
+                .iter()
+                .enumerate()
+                .map(|(i, &id)| (id, (i / T::BIT_SIZE, i % T::BIT_SIZE)))
+                .collect(),
+            epoch: epoch + 1,
+        });
+
+        if is_new {
+            // the new committee counts its instances afresh, as a joining party has none
+            self.cur_mpc_sid.store(0, Ordering::SeqCst);
+            self.n = new_parties.len() as u16;
+            self.t = new_t;
+            self.parties = new_parties.to_vec();
+            self.lp_coeff = lagrange_poly(&new_points, &T::from(self.party_id.into()), |x| {
+                T::zero() - x
+            });
+            self.party_points = new_points;
+            self.persist_all(sid)
+        } else if let Some(records) = self.records() {
+            records
+                .clear()
+                .with_context(|| self.err(sid, "Failed to delete stored state"))?;
+            Ok(())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<T: Field + CircuitElement + Copy, FM> GenericThreshPlayer<T, FM> {
+    fn state_header(&self) -> StateHeader {
+        StateHeader::new(self.party_id, self.n, self.t, StateBackend::Generic)
+    }
+
+    fn restore(&mut self, sid: SessionId, state: State<T>) -> Result<(), UnexpectedError> {
+        // after a restart only the state store knows how far the shares have been refreshed
+        let epoch = self.state.as_ref().map_or(0, |s| s.epoch);
+        let epoch = match self.records() {
+            Some(records) => records
+                .epoch()
+                .with_context(|| self.err(sid, "Failed to read the stored epoch"))?
+                .max(epoch),
+            None => epoch,
+        };
+        if state.epoch < epoch {
+            return Err(self.unexpected(
+                sid,
+                format!(
+                    "Saved state is from epoch {} but shares have been refreshed to epoch {epoch}",
+                    state.epoch,
+                ),
+            ));
+        }
+
+        self.state = Some(state);
+
+        Ok(())
+    }
+
+    fn records(&self) -> Option<StateRecords<'_>> {
+        self.store.as_ref().map(|(store, prefix)| StateRecords {
+            store: store.as_ref(),
+            prefix,
+            header: self.state_header(),
+            key: self.state_key.as_ref(),
+        })
+    }
+
+    /// Our shares of the MAC key and of the elements containing the inputs `ids`, all
+    /// multiplied by the Lagrange coefficient `lp`, along with the index of each input's
+    /// bit within the gathered elements
+    fn gather_shares(&self, lp: &T, ids: &[InputId]) -> (T, Vec<T>, Vec<T>, Vec<usize>) {
+        let state = self.state.as_ref().unwrap();
+
+        // accumulate the elements that contain the bits according to id
+        // map of original index into share_inputs to index into xs/macs
+        let mut found = HashMap::new();
+        // the shares of x and ax
+        let mut xs = Vec::new();
+        let mut macs = Vec::new();
+        // the list of bits to gather after validation
+        let mut share_bits = Vec::new();
+        let fsize = T::BIT_SIZE;
+
+        for id in ids.iter() {
+            let (idx, bit) = state.input_share_idx[id];
+            if !found.contains_key(&idx) {
+                let j = xs.len();
+                found.insert(idx, j);
+                let xy = &state.share_inputs[idx];
+                xs.push(*lp * xy.0);
+                macs.push(*lp * xy.1);
+                share_bits.push(fsize * j + bit);
+            } else {
+                let si = found[&idx];
+                share_bits.push(si * fsize + bit);
+            }
+        }
+
+        (state.share_alpha * *lp, xs, macs, share_bits)
+    }
+}
+
+impl<T: Field + CircuitElement + Copy, FM: AsyncMpc<T>> GenericThreshPlayer<T, FM> {
+    /// The next Fmpc instance for an operation that changes the stored inputs
+    fn next_mpc(&self) -> MpcInstance<T, FM> {
+        let id = self.cur_mpc_sid.fetch_add(1, Ordering::SeqCst);
+        let sid = SessionId {
+            parent: FuncId::Fthresh,
+            id,
+        };
+        MpcInstance::new(self.mpc.clone(), sid)
+    }
+
+    /// Start the Fmpc instance of session `sid` among `parties` and input our shares of the
+    /// inputs `ids`, giving the instance, the number of shares input by each party, and the
+    /// index of each input's bit within them
+    async fn input_shares(
+        &self,
+        sid: SessionId,
+        parties: &[PartyId],
+        ids: &[InputId],
+    ) -> Result<(MpcInstance<T, FM>, usize, Vec<usize>), UnexpectedError> {
+        if self.state.is_none() {
+            return Err(anyhow!("Init/resume not run").into());
+        }
+        // If we do not have the threshold of parties, abort
+        if parties.len() < self.t.into() {
+            return Err(anyhow!("Insufficient number of parties to run evaluation").into());
+        }
+
+        if !parties.contains(&self.party_id) {
+            return Err(
+                anyhow!("I am not one of the parties that is supposed to execute eval").into(),
+            );
+        }
+
+        let mpc = MpcInstance::new(self.mpc.clone(), sid.derive_ssid(FuncId::Fthresh));
+        let (num_shares, share_bits) = self.input_shares_to(mpc.sid, parties, ids).await?;
+
+        Ok((mpc, num_shares, share_bits))
+    }
+
+    /// Start the Fmpc instance `mpc_sid` and have each of `parties` input its shares of the
+    /// inputs `ids`, giving the number of shares input by each party and the index of each
+    /// input's bit within them. Parties that are not evaluating input nothing, but still get
+    /// the layout of the inputs to build the circuit.
+    async fn input_shares_to(
+        &self,
+        mpc_sid: SessionId,
+        parties: &[PartyId],
+        ids: &[InputId],
+    ) -> Result<(usize, Vec<usize>), UnexpectedError> {
+        // calculate the lagrange coefficient for the given set of parties
+        let lp = if parties.contains(&self.party_id) {
+            let party_points: Vec<_> = parties.iter().map(|&p| T::from(p.into())).collect();
+            lagrange_poly(&party_points, &T::from(self.party_id.into()), |x| {
+                T::zero() - x
+            })
+        } else {
+            T::zero()
+        };
+
+        let (alpha, xs, macs, share_bits) = self.gather_shares(&lp, ids);
+
+        self.mpc.init(mpc_sid, None).await?;
+
+        for &i in parties.iter() {
+            let mine = i == self.party_id;
+            self.mpc.input(mpc_sid, i, mine.then_some(alpha)).await?;
+            self.mpc
+                .input_multi(mpc_sid, i, xs.len(), mine.then(|| xs.clone()))
+                .await?;
+            self.mpc
+                .input_multi(mpc_sid, i, macs.len(), mine.then(|| macs.clone()))
+                .await?;
+        }
+
+        Ok((xs.len(), share_bits))
+    }
+}
+
+impl<T, FM> GenericThreshPlayer<T, FM>
+where
+    T: Field + CircuitElement + Copy + Serialize,
+    for<'d> T: Deserialize<'d>,
+{
+    /// Store the new `shares` holding `ids`, and append them to the state store if there is one
+    fn add_batch(
+        &mut self,
+        sid: SessionId,
+        shares: Vec<(T, T)>,
+        ids: &[InputId],
+    ) -> Result<(), UnexpectedError> {
+        let sr = self.state.as_ref().unwrap();
+        let old_len = sr.share_inputs.len();
+
+        let fsize = T::BIT_SIZE;
+        let new_idx: HashMap<_, _> = ids
+            .iter()
+            .enumerate()
+            .map(|(i, &id)| (id, (old_len + (i / fsize), i % fsize)))
+            .collect();
+
+        if let Some(records) = self.records() {
+            let base = (sr.share_alpha, sr.epoch);
+            records
+                .append(&base, &(shares.clone(), new_idx.clone()))
+                .with_context(|| self.err(sid, "Failed to persist state"))?;
+        }
+
+        let sr = self.state.as_mut().unwrap();
+        sr.share_inputs.extend(shares);
+        sr.input_share_idx.extend(new_idx);
+
+        Ok(())
+    }
+
+    /// Replace everything in the state store with the current state, if there is one
+    fn persist_all(&self, sid: SessionId) -> Result<(), UnexpectedError> {
+        if let (Some(records), Some(sr)) = (self.records(), self.state.as_ref()) {
+            let base = (sr.share_alpha, sr.epoch);
+            let batch = (sr.share_inputs.clone(), sr.input_share_idx.clone());
+            records
+                .set_epoch(sr.epoch)
+                .and_then(|_| records.replace(&base, &batch))
+                .with_context(|| self.err(sid, "Failed to persist state"))?;
+        }
+        Ok(())
+    }
+}
+
+impl<T, FM: AsyncMpc<T>> GenericThreshPlayer<T, FM>
+where
+    T: Field + RandElement + CircuitRing + Copy + Serialize,
+    for<'d> T: Deserialize<'d>,
+{
+    /// Evaluate `circuit` on the inputs `in_ids` among `parties`, giving my shares of its
+    /// outputs to store as new elements
+    async fn eval_shares<I, O>(
+        &self,
+        sid: SessionId,
+        parties: &[PartyId],
+        in_ids: &[InputId],
+        circuit: &TCircuit<I, O>,
+    ) -> Result<Vec<(T, T)>, UnexpectedError> {
+        let mpc = self.next_mpc();
+        let mpc_sid = mpc.sid;
+        let (num_shares, share_bits) = self
+            .input_shares_to(mpc_sid, parties, in_ids)
+            .await
+            .with_context(|| self.err(sid, format!("Failed to input shares to Fmpc {mpc_sid}")))?;
+
+        let num_out_shares = circuit.outputs.len().div_ceil(T::BIT_SIZE);
+        let fs_size = 2 * num_out_shares * (self.t as usize - 1);
+        for &i in self.parties.iter() {
+            // make 2*l random degree t-1 polynomials to use as zero shares
+            let all_fs = (i == self.party_id).then(|| {
+                let mut rng = rand::thread_rng();
+                (0..fs_size).map(|_| T::rand(&mut rng)).collect::<Vec<T>>()
+            });
+            self.mpc
+                .input_multi(mpc_sid, i, fs_size, all_fs)
+                .await
+                .with_context(|| self.err(sid, format!("Failed to input polys for {i}")))?;
+        }
+
+        let circuit = eval_state_circuit(
+            parties.len(),
+            num_shares,
+            &share_bits,
+            self.t.into(),
+            &self.party_points,
+            circuit,
+        );
+
+        // the validity bit is public, then each party gets its block of shares
+        let num_outputs = circuit.outputs.len();
+        let party_outs = T::BIT_SIZE * num_out_shares * 2;
+        let output_assignment = (0..num_outputs)
+            .map(|w| match w {
+                0 => (w, 0),
+                _ => (w, self.parties[(w - 1) / party_outs] as i32),
+            })
+            .collect();
+        let parse_fn = |bits: &[bool]| {
+            let shares = <Vec<(T, T)> as CircuitCollection>::from_bits(num_out_shares, &bits[1..]);
+            (bits[0], shares)
+        };
+
+        trace!("running mpc eval on eval to state circuit");
+
+        let (valid, output) = self
+            .mpc
+            .eval_generic(
+                mpc_sid,
+                &self.parties,
+                &circuit,
+                output_assignment,
+                parse_fn,
+            )
+            .await
+            .with_context(|| self.err(sid, format!("Failed to evaluate in Fmpc {mpc_sid}")))?;
+
+        if !valid {
+            return Err(self.unexpected(sid, "Cheat in input"));
+        }
+
+        Ok(output)
+    }
+
+    /// Store the inputs `ids` in new elements, leaving their old bits unused
+    async fn move_inputs(
+        &mut self,
+        sid: SessionId,
+        ids: &[InputId],
+    ) -> Result<(), UnexpectedError> {
+        let n = ids.len();
+        let copy_c: TCircuit<Vec<bool>, Vec<bool>> = {
+            let bit_copy: TCircuit<bool, bool> =
+                TCircuit::from_parts(vec![0], vec![Gate::Not(0, 1), Gate::Not(1, 2)], vec![2]);
+            let (mut b, ins) = new_builder().add_input_multi::<Vec<bool>>(n, None);
+            let mut outs = Vec::with_capacity(n);
+            for &i in ins.iter() {
+                let (b2, out) = b.extend_circuit(&[i], &bit_copy, None);
+                b = b2;
+                outs.extend(out);
+            }
+            b.refine_input().refine_output(&outs).to_circuit()
+        };
+        let parties = self.parties.clone();
+        let shares = self
+            .eval_shares(sid, &parties, ids, &copy_c)
+            .await
+            .with_context(|| self.err(sid, format!("Failed to move inputs {ids:?}")))?;
+
+        self.add_batch(sid, shares, ids)
+    }
+}
+
+/// The number of bits used to reveal the epoch and the number of inputs, then each input id
+/// and the index of its bit, during `reshare`
+const EPOCH_BITS: usize = 64;
+const HEADER_BITS: usize = EPOCH_BITS + 32;
+const ID_BITS: usize = 64;
+const LAYOUT_BITS: usize = 32;
+
+fn encode_bits(bits: &mut Vec<bool>, x: u64, num_bits: usize) {
+    bits.extend((0..num_bits).map(|i| (x >> i) & 1 == 1));
+}
+
+fn decode_bits(bits: &[bool]) -> u64 {
+    bits.iter().rev().fold(0, |acc, &b| (acc << 1) | b as u64)
+}
+
+fn encode_header(epoch: u64, count: usize) -> Vec<bool> {
+    let mut bits = Vec::with_capacity(HEADER_BITS);
+    encode_bits(&mut bits, epoch, EPOCH_BITS);
+    encode_bits(&mut bits, count as u64, HEADER_BITS - EPOCH_BITS);
+    bits
+}
+
+fn decode_header(bits: &[bool]) -> (u64, usize) {
+    let (epoch, count) = bits.split_at(EPOCH_BITS);
+    (decode_bits(epoch), decode_bits(count) as usize)
+}
+
+fn encode_layout(ids: &[InputId], share_bits: &[usize]) -> Vec<bool> {
+    let mut bits = Vec::with_capacity(ids.len() * (ID_BITS + LAYOUT_BITS));
+    for (&id, &b) in ids.iter().zip(share_bits) {
+        encode_bits(&mut bits, id as u64, ID_BITS);
+        encode_bits(&mut bits, b as u64, LAYOUT_BITS);
+    }
+    bits
+}
+
+fn decode_layout(bits: &[bool]) -> (Vec<InputId>, Vec<usize>) {
+    bits.chunks_exact(ID_BITS + LAYOUT_BITS)
+        .map(|c| {
+            let (id, b) = c.split_at(ID_BITS);
+            (decode_bits(id) as InputId, decode_bits(b) as usize)
+        })
+        .unzip()
+}
+
+impl<T: Field + RandElement + Copy, FM: AsyncMpc<T>> GenericThreshPlayer<T, FM> {
+    pub fn new(party_id: PartyId, n: PartyId, t: u16, mpc: Arc<FM>) -> Result<Self, ()> {
+        let parties: Vec<PartyId> = (1..n + 1).collect();
+        let party_points: Vec<T> = (1..n + 1).map(|x| T::from(x.into())).collect();
+        let lp = lagrange_poly(&party_points, &party_points[(party_id - 1) as usize], |x| {
+            T::zero() - x
+        });
+
+        Ok(GenericThreshPlayer {
+            party_id: party_id,
+            n: n,
+            t: t,
+            parties,
+            party_points,
+            lp_coeff: lp,
+            mpc: mpc,
+            state: None,
+            cur_mpc_sid: AtomicU64::new(0),
+            sessions: Sessions::default(),
+            state_key: None,
+            store: None,
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{
+        circuits::{
+            aes::aes_key_schedule, arith::sum_circuit, generic_thresh::tests::copy_circuit,
+            new_builder,
+        },
+        ff2_128::FF2_128,
+        ff2_64::FF2_64,
+        func_mpc::tests::{build_ideal_mpcs, build_test_mpcs},
+        func_net::tests::{build_test_nets, get_test_party_infos},
+        func_thresh::store::MemoryStateStore,
+    };
+    use tokio::{io, task::JoinSet};
+
+    pub fn build_test_comcomps<T: Field + RandElement + Copy, FM: AsyncMpc<T>>(
+        mpcs: &[Arc<FM>],
+        t: PartyId,
+    ) -> Vec<GenericThreshPlayer<T, FM>> {
+        let num = mpcs.len();
+        (1..=num)
+            .map(|i| {
+                GenericThreshPlayer::new(i as PartyId, num as PartyId, t, mpcs[i - 1].clone())
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_comcomp_execution() -> io::Result<()> {
+        let party_info = get_test_party_infos(3);
+        let mpcs = build_test_mpcs::<FF2_128>(&party_info).await;
+        let comcomps = build_test_comcomps(&mpcs, 2);
+
+        let mut js = JoinSet::<Result<_, UnexpectedError>>::new();
+        for mut cc in comcomps.into_iter() {
+            js.spawn(async move {
+                let _ = cc.init().await?;
+                let ids: Vec<_> = (1..=256).collect();
+                let _ = cc.sample(&ids).await?;
+                let c = sum_circuit::<FF2_128>(2);
+                cc.eval(&[1, 2, 3], &ids, &c).await
+            });
+        }
+
+        let mut res = Vec::new();
+        while let Some(x) = js.join_next().await {
+            res.push(x.unwrap().unwrap());
+        }
+
+        // check that all parties got the same output
+        assert_eq!(res[0], res[1]);
+        assert_eq!(res[0], res[2]);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_comcomp_concurrent_eval() -> io::Result<()> {
+        let party_info = get_test_party_infos(3);
+        let mpcs = build_test_mpcs::<FF2_128>(&party_info).await;
+        let comcomps = build_test_comcomps(&mpcs, 2);
+
+        let mut js = JoinSet::<Result<_, UnexpectedError>>::new();
+        for mut cc in comcomps.into_iter() {
+            js.spawn(async move {
+                let _ = cc.init().await?;
+                let ids: Vec<_> = (1..=256).collect();
+                let _ = cc.sample(&ids).await?;
+                let c = sum_circuit::<FF2_128>(2);
+                // two sessions over their own Fmpc networks at once
+                let (out_1, out_2) =
+                    tokio::join!(cc.eval(&[1, 2, 3], &ids, &c), cc.eval(&[1, 2, 3], &ids, &c),);
+                Ok((out_1?, out_2?))
+            });
+        }
+
+        let mut res = Vec::new();
+        while let Some(x) = js.join_next().await {
+            let (out_1, out_2) = x.unwrap().unwrap();
+            assert_eq!(out_1, out_2);
+            res.push(out_1);
+        }
+
+        // check that all parties got the same output
+        assert_eq!(res[0], res[1]);
+        assert_eq!(res[0], res[2]);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_comcomp_private_outputs() -> io::Result<()> {
+        let party_info = get_test_party_infos(3);
+        let mpcs = build_test_mpcs::<FF2_128>(&party_info).await;
+        let comcomps = build_test_comcomps(&mpcs, 2);
+
+        let mut js = JoinSet::<Result<_, UnexpectedError>>::new();
+        for mut cc in comcomps.into_iter() {
+            js.spawn(async move {
+                let _ = cc.init().await?;
+                let ids: Vec<_> = (1..=256).collect();
+                let _ = cc.sample(&ids).await?;
+                // reveal a few of the inputs, out of order, to a different party each time
+                let reveal_ids = [200, 3, 129, 3];
+                let to_1 = cc.reveal_to(&[1, 2, 3], 1, &reveal_ids).await?;
+                let to_3 = cc.reveal_to(&[1, 2, 3], 3, &reveal_ids).await?;
+
+                // only party 2 learns the output, which matches evaluating it publicly
+                let c = sum_circuit::<FF2_128>(2);
+                let sum = cc.eval_to(&[1, 2, 3], &ids, &c, 2).await?;
+                let public = cc.eval(&[1, 2, 3], &ids, &c).await?;
+                assert_eq!(sum.is_some(), cc.party() == 2);
+                assert!(sum.map_or(true, |s| s == public));
+
+                Ok((cc.party(), to_1, to_3))
+            });
+        }
+
+        let mut res = HashMap::new();
+        while let Some(x) = js.join_next().await {
+            let (p, to_1, to_3) = x.unwrap().unwrap();
+            res.insert(p, (to_1, to_3));
+        }
+
+        // only the target learns the bits, and each target learns the same bits
+        let bits = res[&1].0.clone().unwrap();
+        assert_eq!(bits.len(), 4);
+        assert_eq!(bits[1], bits[3]);
+        assert_eq!(res[&3].1, Some(bits));
+        assert_eq!(res[&1].1, None);
+        assert_eq!(res[&2], (None, None));
+        assert_eq!(res[&3].0, None);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_comcomp_eval_to_state() -> io::Result<()> {
+        let party_info = get_test_party_infos(3);
+        let mpcs = build_test_mpcs::<FF2_128>(&party_info).await;
+        let comcomps = build_test_comcomps(&mpcs, 2);
+
+        let mut js = JoinSet::<Result<_, UnexpectedError>>::new();
+        for mut cc in comcomps.into_iter() {
+            js.spawn(async move {
+                let _ = cc.init().await?;
+                let ids: Vec<_> = (1..=256).collect();
+                let _ = cc.sample(&ids).await?;
+
+                // only parties 1 and 2 provide inputs, but all three get the new shares
+                let sum_ids: Vec<_> = (1000..1128).collect();
+                let c = sum_circuit::<FF2_128>(2);
+                cc.eval_to_state(&[1, 2], &ids, &c, &sum_ids).await?;
+                let xs = cc.reveal_to(&[1, 2, 3], 3, &ids).await?;
+                if cc.party() == 1 {
+                    return Ok(None);
+                }
+                let sum = cc.reveal_to(&[2, 3], 3, &sum_ids).await?;
+                Ok(xs.zip(sum))
+            });
+        }
+
+        while let Some(x) = js.join_next().await {
+            if let Some((xs, sum)) = x.unwrap().unwrap() {
+                let expected: Vec<_> = (0..128).map(|i| xs[i] ^ xs[128 + i]).collect();
+                assert_eq!(sum, expected);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_comcomp_eval_with_inputs() -> io::Result<()> {
+        let party_info = get_test_party_infos(3);
+        let mpcs = build_test_mpcs::<FF2_128>(&party_info).await;
+        let comcomps = build_test_comcomps(&mpcs, 2);
+
+        let mut js = JoinSet::<Result<_, UnexpectedError>>::new();
+        for mut cc in comcomps.into_iter() {
+            js.spawn(async move {
+                let _ = cc.init().await?;
+                let ids: Vec<_> = (1..=128).collect();
+                let _ = cc.sample(&ids).await?;
+
+                // add the stored bits to 64 private bits of party 3 and 64 public bits
+                let inputs: Vec<_> = ids
+                    .iter()
+                    .map(|&id| EvalInput::Stored(id))
+                    .chain((0..64).map(|i| EvalInput::Public(i % 3 == 0)))
+                    .chain((0..64).map(|i| EvalInput::Private(3, 63 - i)))
+                    .collect();
+                let private: Vec<_> = if cc.party() == 3 {
+                    (0..64).map(|i| i % 2 == 0).collect()
+                } else {
+                    Vec::new()
+                };
+
+                let c = sum_circuit::<FF2_128>(2);
+                let out = if cc.party() == 1 {
+                    None
+                } else {
+                    Some(cc.eval_with_inputs(&[2, 3], &inputs, &private, &c).await?)
+                };
+                let xs = cc.reveal_to(&[1, 2, 3], 1, &ids).await?;
+                Ok((out, xs))
+            });
+        }
+
+        let mut res = Vec::new();
+        while let Some(x) = js.join_next().await {
+            res.push(x.unwrap().unwrap());
+        }
+        let outs: Vec<_> = res.iter().filter_map(|(out, _)| *out).collect();
+        assert_eq!(outs.len(), 2);
+        assert_eq!(outs[0], outs[1]);
+
+        let xs = res.iter().find_map(|(_, xs)| xs.clone()).unwrap();
+        let mut bits = vec![false; 128];
+        outs[0].to_bits(&mut bits);
+        let expected: Vec<_> = (0..128)
+            .map(|i| {
+                xs[i]
+                    ^ if i < 64 {
+                        i % 3 == 0
+                    } else {
+                        (127 - i) % 2 == 0
+                    }
+            })
+            .collect();
+        assert_eq!(bits, expected);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_comcomp_dealer_setup() -> io::Result<()> {
+        let party_info = get_test_party_infos(3);
+        let mpcs = build_test_mpcs::<FF2_128>(&party_info).await;
+        let comcomps = build_test_comcomps(&mpcs, 2);
+
+        let key: Vec<_> = (0..128).map(|i| i % 5 == 0).collect();
+
+        let mut js = JoinSet::<Result<_, UnexpectedError>>::new();
+        for mut cc in comcomps.into_iter() {
+            let key = key.clone();
+            js.spawn(async move {
+                let _ = cc.init().await?;
+
+                // party 2 imports a key it holds by itself
+                let copy_c = copy_circuit::<FF2_128>();
+                let input = if cc.party() == 2 { key } else { Vec::new() };
+                let ids: Vec<_> = (1..=128).collect();
+                cc.setup_with_sizes(&input, &[0, 128, 0], &ids, &copy_c)
+                    .await?;
+                cc.reveal_to(&[1, 2, 3], 1, &ids).await
+            });
+        }
+
+        let mut res = Vec::new();
+        while let Some(x) = js.join_next().await {
+            if let Some(bits) = x.unwrap().unwrap() {
+                res.push(bits);
+            }
+        }
+        assert_eq!(res, vec![key]);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_comcomp_delete() -> io::Result<()> {
+        let party_info = get_test_party_infos(3);
+        delete_test(build_test_mpcs::<FF2_128>(&party_info).await).await
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_comcomp_delete_ideal() -> io::Result<()> {
+        delete_test(build_ideal_mpcs::<FF2_128>(3)).await
+    }
+
+    async fn delete_test<FM: AsyncMpc<FF2_128> + 'static>(mpcs: Vec<Arc<FM>>) -> io::Result<()> {
+        let comcomps = build_test_comcomps(&mpcs, 2);
+
+        let mut js = JoinSet::<Result<_, UnexpectedError>>::new();
+        for mut cc in comcomps.into_iter() {
+            js.spawn(async move {
+                let _ = cc.init().await?;
+                let ids: Vec<_> = (1..=256).collect();
+                let _ = cc.sample(&ids).await?;
+                let before = cc.reveal_to(&[1, 2, 3], 1, &ids[64..128]).await?;
+
+                // nothing is deleted unless every party deletes the same inputs
+                let first = if cc.party_id == 1 { 128 } else { 129 };
+                assert!(cc.delete(&ids[first..]).await.is_err());
+                assert_eq!(cc.state.as_ref().unwrap().input_share_idx.len(), 256);
+
+                // the second element is dropped without moving anything
+                cc.delete(&ids[128..]).await?;
+                assert_eq!(cc.state.as_ref().unwrap().share_inputs.len(), 1);
+
+                // the first element still holds the rest, which are moved to a new element
+                // under the same MAC key
+                let alpha = cc.state.as_ref().unwrap().share_alpha;
+                cc.delete(&ids[..64]).await?;
+                let sr = cc.state.as_ref().unwrap();
+                assert_eq!(sr.share_inputs.len(), 1);
+                assert_eq!(sr.input_share_idx.len(), 64);
+                assert_eq!(sr.share_alpha, alpha);
+                assert_eq!(cc.epoch(), 0);
+                assert!(cc.delete(&ids[..1]).await.is_err());
+
+                let after = cc.reveal_to(&[1, 2, 3], 1, &ids[64..128]).await?;
+                Ok(before.zip(after))
+            });
+        }
+
+        while let Some(x) = js.join_next().await {
+            if let Some((before, after)) = x.unwrap().unwrap() {
+                assert_eq!(before, after);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_comcomp_execution_ff2_64() -> io::Result<()> {
+        let party_info = get_test_party_infos(3);
+        let mpcs = build_test_mpcs::<FF2_64>(&party_info).await;
+        let comcomps = build_test_comcomps(&mpcs, 2);
+
+        let mut js = JoinSet::<Result<_, UnexpectedError>>::new();
+        for mut cc in comcomps.into_iter() {
+            js.spawn(async move {
+                let _ = cc.init().await?;
+                let ids: Vec<_> = (1..=128).collect();
+                let _ = cc.sample(&ids).await?;
+                let c = sum_circuit::<FF2_64>(2);
+                cc.eval(&[1, 2, 3], &ids, &c).await
+            });
+        }
+
+        let mut res = Vec::new();
+        while let Some(x) = js.join_next().await {
+            res.push(x.unwrap().unwrap());
+        }
+
+        // check that all parties got the same output
+        assert_eq!(res[0], res[1]);
+        assert_eq!(res[0], res[2]);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_comcomp_reshare() -> io::Result<()> {
+        let party_info = get_test_party_infos(4);
+        reshare_test(build_test_mpcs::<FF2_128>(&party_info).await).await
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_comcomp_reshare_ideal() -> io::Result<()> {
+        reshare_test(build_ideal_mpcs::<FF2_128>(4)).await
+    }
+
+    async fn reshare_test<FM: AsyncMpc<FF2_128> + 'static>(mpcs: Vec<Arc<FM>>) -> io::Result<()> {
+        // parties 1-3 start as the committee, and party 4 joins it
+        let mut comcomps = build_test_comcomps(&mpcs[..3], 2);
+        comcomps.push(GenericThreshPlayer::new(4, 4, 2, mpcs[3].clone()).unwrap());
+
+        let ids: Vec<_> = (1..=256).collect();
+        // move the inputs in a different order, so they are spread over new elements
+        let moved: Vec<_> = ids.iter().rev().cloned().collect();
+
+        let mut js = JoinSet::<Result<_, UnexpectedError>>::new();
+        for mut cc in comcomps.into_iter() {
+            let ids = ids.clone();
+            let moved = moved.clone();
+            js.spawn(async move {
+                let c = sum_circuit::<FF2_128>(2);
+                let old_parties = [1, 2, 3];
+                let new_parties = [2, 3, 4];
+                cc.set_state_store(Arc::new(MemoryStateStore::new()), "generic");
+
+                let before = if old_parties.contains(&cc.party_id) {
+                    cc.init().await?;
+                    cc.sample(&ids).await?;
+                    Some(cc.eval(&old_parties, &ids, &c).await?)
+                } else {
+                    None
+                };
+
+                cc.reshare(&old_parties, &new_parties, 3, Some(&moved))
+                    .await?;
+
+                let after = if new_parties.contains(&cc.party_id) {
+                    // the stored state has moved to the new committee along with the shares
+                    cc.resume_from_store(false).await?;
+                    assert_eq!(cc.epoch(), 1);
+                    let after = cc.eval(&new_parties, &ids, &c).await?;
+                    // the joining party agrees with the others on the instances that follow
+                    let extra: Vec<_> = (257..=264).collect();
+                    cc.sample(&extra).await?;
+
+                    // then move all of the inputs again within the new committee
+                    cc.reshare(&new_parties, &new_parties, 2, None).await?;
+                    assert_eq!(cc.epoch(), 2);
+                    assert_eq!(cc.eval(&new_parties, &ids, &c).await?, after);
+                    Some(after)
+                } else {
+                    assert!(cc.state.is_none());
+                    assert!(cc.resume_from_store(false).await.is_err());
+                    None
+                };
+
+                Ok((before, after))
+            });
+        }
+
+        let mut res = Vec::new();
+        while let Some(x) = js.join_next().await {
+            res.push(x.unwrap().unwrap());
+        }
+
+        // check that the new committee gets the same output as the old one
+        let before: Vec<_> = res.iter().filter_map(|r| r.0).collect();
+        let after: Vec<_> = res.iter().filter_map(|r| r.1).collect();
+        assert_eq!(before.len(), 3);
+        assert_eq!(after.len(), 3);
+        assert!(after.iter().all(|&a| a == before[0]));
 
         Ok(())
     }
@@ -606,7 +1886,7 @@ pub mod tests {
     #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
     async fn test_comcomp_setup() -> io::Result<()> {
         let party_info = get_test_party_infos(5);
-        let mpcs = build_test_mpcs(&party_info);
+        let mpcs = build_test_mpcs::<FF2_128>(&party_info).await;
         let comcomps = build_test_comcomps(&mpcs, 3);
 
         let aes = aes_key_schedule();
@@ -1,49 +1,421 @@
 use crate::{
-    base_func::UnexpectedError,
-    circuits::{CircuitElement, TCircuit},
+    base_func::{FuncId, SessionId, UnexpectedError},
+    circuits::{
+        elements::{wire_inputs, InputWire},
+        generic_thresh::layout_circuit,
+        CircuitElement, TCircuit,
+    },
+    func_mpc::AsyncMpc,
     party::PartyId,
 };
 
+use anyhow::anyhow;
+
+use sha2::{Digest, Sha256};
+
+use state::StateKey;
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    future::Future,
+    hash::{Hash, Hasher},
+    sync::{Arc, Mutex},
+};
+use store::StateStore;
+use tokio::sync::OwnedMutexGuard;
+
 pub type InputId = usize;
 
+/// Where an input of a circuit run by `eval_with_inputs` comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EvalInput {
+    /// The stored input with this id
+    Stored(InputId),
+    /// Bit `i` of the private input of an evaluating party
+    Private(PartyId, usize),
+    /// A bit known to all of the evaluating parties
+    Public(bool),
+}
+
 /// The trait to represent the F_thresh functionality over base element type T.
-/// Does not allow for concurrent operations.
-pub trait AsyncThresh {
+/// Evaluations and reveals only read the stored inputs, so they take `&self` and any number
+/// of them can run at once. The parties of each call agree on its session from what it is
+/// called with, so calls that differ in their parties, inputs, circuit or recipient are kept
+/// apart however they interleave, while identical calls run one after another and are paired
+/// up in the order each party makes them. Operations that change the stored inputs take
+/// `&mut self`.
+pub trait AsyncThresh: Send + Sync {
     /// Restore the saved keys and inputs from a previous run, replacing the `init` call.
-    async fn resume_from_state_file(
+    fn resume_from_state_file(
         &mut self,
         state_file: &str,
         run_init: bool,
-    ) -> Result<(), UnexpectedError>;
+    ) -> impl Future<Output = Result<(), UnexpectedError>> + Send;
 
-    /// Save the secret key and input shares to `state_file`
+    /// Save the secret key and input shares to `state_file` in the binary state format
     fn write_state_to_file(&self, state_file: &str) -> Result<(), UnexpectedError>;
 
+    /// Encrypt state files written from now on under `key`, and only resume from files
+    /// encrypted under it for this party
+    fn set_state_key(&mut self, key: StateKey);
+
+    /// Save the secret key and input shares to `state_file` as JSON for debugging.
+    /// These files can be resumed from, but are much larger and slower to load.
+    /// Fails once a state key is set, as the JSON is never encrypted.
+    fn export_state_json(&self, state_file: &str) -> Result<(), UnexpectedError>;
+
+    /// Persist the state to `store` under keys starting with `prefix`, with each `setup` and
+    /// `sample` appending its new shares rather than rewriting all of them
+    fn set_state_store(&mut self, store: Arc<dyn StateStore>, prefix: &str);
+
+    /// Restore the saved keys and inputs from the state store, replacing the `init` call
+    fn resume_from_store(
+        &mut self,
+        run_init: bool,
+    ) -> impl Future<Output = Result<(), UnexpectedError>> + Send;
+
     /// Runs the initial setup for the functionality to generate keys
-    async fn init(&mut self) -> Result<(), UnexpectedError>;
+    fn init(&mut self) -> impl Future<Output = Result<(), UnexpectedError>> + Send;
 
     /// Jointly run a circuit to generate a threshold-shared state
     /// Each party provides k inputs such that |I| = nk
     /// and generates |O| threshold auth bits using the circuit output.
-    async fn setup<I, O: CircuitElement>(
+    fn setup<I, O: CircuitElement>(
         &mut self,
         input: &[bool],
         out_ids: &[InputId],
         circuit: &TCircuit<I, O>,
-    ) -> Result<(), UnexpectedError>;
+    ) -> impl Future<Output = Result<(), UnexpectedError>> + Send;
+
+    /// Jointly run a circuit to generate a threshold-shared state, where the `i`th party of
+    /// the committee provides the next `input_sizes[i]` inputs of the circuit. Parties without
+    /// any inputs pass an empty `input`, so a single dealer can import a secret it holds.
+    fn setup_with_sizes<I, O: CircuitElement>(
+        &mut self,
+        input: &[bool],
+        input_sizes: &[usize],
+        out_ids: &[InputId],
+        circuit: &TCircuit<I, O>,
+    ) -> impl Future<Output = Result<(), UnexpectedError>> + Send;
 
     /// Collectively sample |ids| random inputs
-    async fn sample(&mut self, ids: &[InputId]) -> Result<(), UnexpectedError>;
+    fn sample(
+        &mut self,
+        ids: &[InputId],
+    ) -> impl Future<Output = Result<(), UnexpectedError>> + Send;
 
     /// Evaluate the given circuit with the specified list of parties
-    async fn eval<I, O: CircuitElement>(
-        &mut self,
+    fn eval<I, O: CircuitElement>(
+        &self,
+        parties: &[PartyId],
+        ids: &[InputId],
+        circuit: &TCircuit<I, O>,
+    ) -> impl Future<Output = Result<O, UnexpectedError>> + Send;
+
+    /// Evaluate the given circuit with the specified list of parties, taking each of its
+    /// inputs from where `inputs` says. Each party passes its own private input bits, which
+    /// must cover every `EvalInput::Private` position of that party.
+    fn eval_with_inputs<I, O: CircuitElement>(
+        &self,
+        parties: &[PartyId],
+        inputs: &[EvalInput],
+        private_input: &[bool],
+        circuit: &TCircuit<I, O>,
+    ) -> impl Future<Output = Result<O, UnexpectedError>> + Send;
+
+    /// Evaluate the given circuit with the specified list of parties, which must include
+    /// `recipient`, giving the output to `recipient` alone and `None` to everyone else
+    fn eval_to<I, O: CircuitElement>(
+        &self,
         parties: &[PartyId],
         ids: &[InputId],
         circuit: &TCircuit<I, O>,
-    ) -> Result<O, UnexpectedError>;
+        recipient: PartyId,
+    ) -> impl Future<Output = Result<Option<O>, UnexpectedError>> + Send;
+
+    /// Evaluate the given circuit over the inputs `in_ids` and store its outputs as new inputs
+    /// `out_ids`, called by every party. The inputs are taken from the specified list of
+    /// parties, which must reach the threshold, and every party gets shares of the outputs.
+    fn eval_to_state<I, O: CircuitElement>(
+        &mut self,
+        parties: &[PartyId],
+        in_ids: &[InputId],
+        circuit: &TCircuit<I, O>,
+        out_ids: &[InputId],
+    ) -> impl Future<Output = Result<(), UnexpectedError>> + Send;
+
+    /// Open the inputs `ids` to `party` alone, run with the specified list of parties,
+    /// which must include `party`. Only `party` checks that the inputs are authentic and
+    /// learns their bits, every other party gets `None`.
+    fn reveal_to(
+        &self,
+        parties: &[PartyId],
+        party: PartyId,
+        ids: &[InputId],
+    ) -> impl Future<Output = Result<Option<Vec<bool>>, UnexpectedError>> + Send;
+
+    /// Delete the inputs `ids`, called by every party. Any inputs packed together with a
+    /// deleted one are first stored afresh, so that the shares can be compacted without
+    /// keeping anything from which the deleted inputs could be recovered, and the compacted
+    /// state is persisted.
+    fn delete(
+        &mut self,
+        ids: &[InputId],
+    ) -> impl Future<Output = Result<(), UnexpectedError>> + Send;
+
+    /// The number of times the shares have been refreshed
+    fn epoch(&self) -> u64;
+
+    /// Re-randomize all of the stored shares without changing the inputs, and move to the
+    /// next epoch. State files record their epoch, and resuming from one older than the
+    /// current epoch fails, since its shares no longer combine with the other parties'.
+    /// With a state store set, the epoch is also kept in the store apart from the state,
+    /// so older state is still refused after a restart.
+    fn refresh(&mut self) -> impl Future<Output = Result<(), UnexpectedError>> + Send;
+}
+
+/// Moving the stored inputs to a new committee, for backends whose MACs are not fixed to the
+/// committee they were set up for
+pub trait AsyncReshare: AsyncThresh {
+    /// Move the stored inputs from the `old_parties` to a new committee of `new_parties` with
+    /// threshold `new_t` under a fresh MAC key, called by every party in either committee.
+    /// All of the inputs are moved in order of their ids, unless `ids` lists the ones to move,
+    /// in which case the rest are dropped. The old parties check that they agree on the inputs,
+    /// and the new parties learn them, along with their layout, from the old ones.
+    /// The new committee moves to the next epoch, and old parties that are not in it delete
+    /// their state.
+    fn reshare(
+        &mut self,
+        old_parties: &[PartyId],
+        new_parties: &[PartyId],
+        new_t: u16,
+        ids: Option<&[InputId]>,
+    ) -> impl Future<Output = Result<(), UnexpectedError>> + Send;
+}
+
+/// Check that `input_sizes` splits the `num_inputs` inputs of a setup circuit between the
+/// `parties`, and that `input` holds as many bits as `my_id` provides
+pub(crate) fn check_input_sizes(
+    parties: &[PartyId],
+    my_id: PartyId,
+    input: &[bool],
+    input_sizes: &[usize],
+    num_inputs: usize,
+) -> Result<(), UnexpectedError> {
+    if input_sizes.len() != parties.len() {
+        return Err(anyhow!(
+            "{} input sizes given for {} parties",
+            input_sizes.len(),
+            parties.len()
+        )
+        .into());
+    }
+
+    let total: usize = input_sizes.iter().sum();
+    if total != num_inputs {
+        return Err(anyhow!("{total} inputs given for a circuit with {num_inputs} inputs").into());
+    }
+
+    let my_size = parties
+        .iter()
+        .position(|&p| p == my_id)
+        .map_or(0, |i| input_sizes[i]);
+    if input.len() != my_size {
+        return Err(anyhow!("Expected {my_size} input bits, got {}", input.len()).into());
+    }
+
+    Ok(())
+}
+
+/// Reveal to all of `parties` the `num_bits` bits that each of the parties `from` inputs to
+/// the initialized Fmpc instance `mpc_sid`, where ours are `bits`, failing unless they all
+/// input the same bits
+pub(crate) async fn agree_on_bits<T, FM: AsyncMpc<T>>(
+    mpc: &FM,
+    mpc_sid: SessionId,
+    my_id: PartyId,
+    from: &[PartyId],
+    parties: &[PartyId],
+    num_bits: usize,
+    bits: &[bool],
+) -> Result<Vec<bool>, UnexpectedError> {
+    for &p in from.iter() {
+        let input = (p == my_id).then(|| bits.to_vec());
+        mpc.input_multi(mpc_sid, p, num_bits, input).await?;
+    }
+
+    let circuit = layout_circuit(from.len(), num_bits);
+    let output_assignment = (0..circuit.outputs.len()).map(|w| (w, 0)).collect();
+    let parse_fn = |bits: &[bool]| bits[0].then(|| bits[1..].to_vec());
+
+    mpc.eval_generic(mpc_sid, parties, &circuit, output_assignment, parse_fn)
+        .await?
+        .ok_or_else(|| anyhow!("Parties disagree on the bits they input to Fmpc {mpc_sid}").into())
+}
+
+/// Check that each of `parties` input the same `ids` to the initialized Fmpc instance
+/// `mpc_sid`, in any order, by comparing a hash of them
+pub(crate) async fn agree_on_ids<T, FM: AsyncMpc<T>>(
+    mpc: &FM,
+    mpc_sid: SessionId,
+    my_id: PartyId,
+    parties: &[PartyId],
+    ids: &[InputId],
+) -> Result<(), UnexpectedError> {
+    let mut sorted = ids.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+
+    let mut hasher = Sha256::new();
+    for id in sorted {
+        hasher.update((id as u64).to_le_bytes());
+    }
+    let bits: Vec<_> = hasher
+        .finalize()
+        .iter()
+        .flat_map(|b| (0..8).map(move |i| (b >> i) & 1 == 1))
+        .collect();
+
+    agree_on_bits(mpc, mpc_sid, my_id, parties, parties, bits.len(), &bits).await?;
+    Ok(())
+}
+
+/// The inputs of an `eval_with_inputs` call, rearranged to be the stored inputs `ids`
+/// followed by `private_sizes[i]` private bits from each of the evaluating parties in turn,
+/// along with the circuit that takes its inputs in that order
+pub(crate) struct ArrangedInputs<O> {
+    pub ids: Vec<InputId>,
+    pub private_sizes: Vec<usize>,
+    pub circuit: TCircuit<Vec<bool>, O>,
+}
+
+/// Rearrange the inputs of an `eval_with_inputs` call by `my_id`, checking that they fit the
+/// circuit and that `private_input` covers all of our private inputs
+pub(crate) fn arrange_inputs<I, O>(
+    parties: &[PartyId],
+    my_id: PartyId,
+    inputs: &[EvalInput],
+    private_input: &[bool],
+    circuit: &TCircuit<I, O>,
+) -> Result<ArrangedInputs<O>, UnexpectedError> {
+    if inputs.len() != circuit.inputs.len() {
+        return Err(anyhow!(
+            "{} inputs given for a circuit with {} inputs",
+            inputs.len(),
+            circuit.inputs.len()
+        )
+        .into());
+    }
+
+    let mut ids = Vec::new();
+    let mut private_sizes = vec![0; parties.len()];
+    for input in inputs.iter() {
+        match *input {
+            EvalInput::Stored(id) => ids.push(id),
+            EvalInput::Private(p, i) => {
+                let j = parties
+                    .iter()
+                    .position(|&q| q == p)
+                    .ok_or_else(|| anyhow!("Private input from {p} who is not evaluating"))?;
+                private_sizes[j] = private_sizes[j].max(i + 1);
+            }
+            EvalInput::Public(_) => {}
+        }
+    }
+
+    let my_size = parties
+        .iter()
+        .position(|&p| p == my_id)
+        .map_or(0, |j| private_sizes[j]);
+    if private_input.len() != my_size {
+        return Err(anyhow!(
+            "Expected {my_size} private input bits, got {}",
+            private_input.len()
+        )
+        .into());
+    }
+
+    // where each party's private bits start among the rearranged inputs
+    let mut private_starts = Vec::with_capacity(parties.len());
+    let mut next = ids.len();
+    for &size in private_sizes.iter() {
+        private_starts.push(next);
+        next += size;
+    }
+
+    let mut num_stored = 0;
+    let wiring: Vec<_> = inputs
+        .iter()
+        .map(|input| match *input {
+            EvalInput::Stored(_) => {
+                num_stored += 1;
+                InputWire::Input(num_stored - 1)
+            }
+            EvalInput::Private(p, i) => {
+                let j = parties.iter().position(|&q| q == p).unwrap();
+                InputWire::Input(private_starts[j] + i)
+            }
+            EvalInput::Public(b) => InputWire::Const(b),
+        })
+        .collect();
+
+    Ok(ArrangedInputs {
+        ids,
+        private_sizes,
+        circuit: wire_inputs(next, &wiring, circuit),
+    })
+}
+
+/// The session of a call made with `call`, which every party of it derives alike
+pub(crate) fn call_sid(call: impl Hash) -> SessionId {
+    let mut h = DefaultHasher::new();
+    call.hash(&mut h);
+    SessionId {
+        parent: FuncId::Fthresh,
+        id: h.finish(),
+    }
 }
 
+/// The sessions of the calls that can run at once, each derived from a hash of what the call
+/// is made with, so that every party of it picks the same one without talking
+#[derive(Debug, Default)]
+pub(crate) struct Sessions {
+    running: Mutex<HashMap<SessionId, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+/// A running session, letting the next identical call start once dropped
+pub(crate) struct Session<'a> {
+    sessions: &'a Sessions,
+    pub(crate) sid: SessionId,
+    lock: Option<OwnedMutexGuard<()>>,
+}
+
+impl Sessions {
+    /// Start the session of the call made with `call`, once any identical call is done
+    pub(crate) async fn start(&self, call: impl Hash) -> Session<'_> {
+        let sid = call_sid(call);
+        let lock = self.running.lock().unwrap().entry(sid).or_default().clone();
+        Session {
+            sessions: self,
+            sid,
+            lock: Some(lock.lock_owned().await),
+        }
+    }
+}
+
+impl Drop for Session<'_> {
+    fn drop(&mut self) {
+        let mut running = self.sessions.running.lock().unwrap();
+        let lock = self.lock.take().unwrap();
+        // the map and this session hold the only references unless another call is waiting
+        if Arc::strong_count(OwnedMutexGuard::mutex(&lock)) == 2 {
+            running.remove(&self.sid);
+        }
+    }
+}
+
+pub mod state;
+pub mod store;
+
 pub mod rst_thresh;
 pub use rst_thresh::RstThreshPlayer;
 
@@ -1,18 +1,22 @@
 use crate::{
-    auth_bits::ThreshAbits,
+    auth_bits::{Abits, ThreshAbits},
     base_func::{BaseFunc, FuncId, SessionId, UnexpectedError},
-    circuits::{elements::out_mask, CircuitElement, TCircuit},
+    circuits::{elements::out_mask, new_builder, CircuitElement, Gate, TCircuit},
     field::{Field, RandElement},
-    func_mpc::AsyncMpc,
-    func_thresh::{AsyncThresh, InputId},
+    func_mpc::{AsyncMpc, MpcInstance},
+    func_thresh::{
+        agree_on_bits, agree_on_ids, arrange_inputs, check_input_sizes,
+        state::{decode_state, encode_state, StateBackend, StateHeader, StateKey},
+        store::{StateRecords, StateStore},
+        AsyncThresh, EvalInput, InputId, Sessions,
+    },
     func_thresh_abit::AsyncTabit,
     party::PartyId,
 };
 
 use std::{
-    cell::RefCell,
-    collections::HashMap,
-    fs::File,
+    collections::{HashMap, HashSet},
+    fs::{self, File},
     sync::{
         atomic::{AtomicU64, Ordering},
         Arc,
@@ -24,28 +28,44 @@ use log::trace;
 
 use serde::{Deserialize, Serialize};
 
+/// Threshold functionality from thresholdised authenticated bits, whose shared delta is
+/// used directly as the garbling offset of Fmpc, so T must be a 128-bit field.
+/// Narrower fields can be used with `GenericThreshPlayer`, which can also reshare to a new
+/// committee, while the pairwise MACs of Ftabit fix this one to the committee it was set up for.
 #[derive(Debug)]
 pub struct RstThreshPlayer<T: Field, FM, FT> {
     party_id: PartyId,
-    //n: u16,
-    //t: u16,
+    n: u16,
+    t: u16,
     parties: Vec<PartyId>,
     party_points: Vec<T>,
     mpc: Arc<FM>,
     tabit: Arc<FT>,
     cur_mpc_sid: AtomicU64,
-    delta: RefCell<Option<T>>,
-    tabits: RefCell<Vec<ThreshAbits<T>>>,
-    input_tabit_idx: RefCell<HashMap<InputId, usize>>,
+    sessions: Sessions,
+    state_key: Option<StateKey>,
+    store: Option<(Arc<dyn StateStore>, String)>,
+    delta: Option<T>,
+    tabits: Vec<ThreshAbits<T>>,
+    // maps an input id to the (batch, bit) of its tabit
+    // so (2, 100) would be the 101st bit of tabits[2]
+    input_tabit_idx: HashMap<InputId, (usize, usize)>,
+    epoch: u64,
 }
 
 #[derive(Serialize, Deserialize)]
 struct State<T> {
     delta: T,
     tabits: Vec<ThreshAbits<T>>,
+    // the position of each input's bit counting across all of the tabits
     tabit_idx: HashMap<InputId, usize>,
+    #[serde(default)]
+    epoch: u64,
 }
 
+// The tabits added by a `setup` or `sample`, with the bits of the ids they hold
+type Batch<T> = (Vec<ThreshAbits<T>>, HashMap<InputId, usize>);
+
 impl<T: Field, FM, FT> BaseFunc for RstThreshPlayer<T, FM, FT> {
     const FUNC_ID: FuncId = FuncId::Fthresh;
     const REQUIRED_FUNCS: &'static [FuncId] = &[FuncId::Fmpc, FuncId::Ftabit];
@@ -55,31 +75,39 @@ impl<T: Field, FM, FT> BaseFunc for RstThreshPlayer<T, FM, FT> {
     }
 }
 
-impl<T, FM: AsyncMpc<T>, FT: AsyncTabit<T>> AsyncThresh for RstThreshPlayer<T, FM, FT>
+impl<T, FM, FT> AsyncThresh for RstThreshPlayer<T, FM, FT>
 where
-    T: Field + CircuitElement + RandElement + Copy + Serialize,
+    T: Field + CircuitElement + RandElement + Copy + Serialize + Send + Sync,
+    FM: AsyncMpc<T> + Send + Sync,
+    FT: AsyncTabit<T> + Send + Sync,
     for<'d> T: Deserialize<'d>,
 {
     /// Write out the state of any authenticated shares
     fn write_state_to_file(&self, file_name: &str) -> Result<(), UnexpectedError> {
-        let state = {
-            let delta = self.delta.borrow().unwrap().clone();
-            let tabits = self.tabits.borrow().clone();
-            let tabit_idx = self.input_tabit_idx.borrow().clone();
-
-            State {
-                delta,
-                tabits,
-                tabit_idx,
-            }
-        };
+        let sid = SessionId::new(FuncId::Fthresh);
+
+        let bytes = encode_state(&self.state_header(), &self.state(), self.state_key.as_ref())
+            .with_context(|| self.err(sid, "Failed to encode state"))?;
 
+        fs::write(file_name, bytes).with_context(|| self.err(sid, "Failed to write state file"))?;
+
+        Ok(())
+    }
+
+    fn set_state_key(&mut self, key: StateKey) {
+        self.state_key = Some(key);
+    }
+
+    fn export_state_json(&self, file_name: &str) -> Result<(), UnexpectedError> {
         let sid = SessionId::new(FuncId::Fthresh);
+        if self.state_key.is_some() {
+            return Err(self.unexpected(sid, "Refusing to export encrypted state as plain JSON"));
+        }
 
         let f = File::create(file_name)
             .with_context(|| self.err(sid, "Failed to create state file"))?;
 
-        serde_json::to_writer(f, &state)
+        serde_json::to_writer(f, &self.state())
             .with_context(|| self.err(sid, "Failed to write state file"))?;
 
         Ok(())
@@ -93,36 +121,47 @@ where
     ) -> Result<(), UnexpectedError> {
         let sid = SessionId::new(FuncId::Fthresh);
 
-        let f =
-            File::open(file_name).with_context(|| self.err(sid, "Failed to open state file"))?;
+        let bytes =
+            fs::read(file_name).with_context(|| self.err(sid, "Failed to open state file"))?;
 
-        let state: State<T> = serde_json::from_reader(f)
+        let (header, state): (_, State<T>) = decode_state(&bytes, self.state_key.as_ref())
             .with_context(|| self.err(sid, "Failed to deserialize state file"))?;
-
-        {
-            let mut d = self.delta.borrow_mut();
-            *d = Some(state.delta.clone());
+        if let Some(header) = header {
+            header
+                .check(&self.state_header())
+                .with_context(|| self.err(sid, "State file does not match this party"))?;
         }
 
-        {
-            let mut t = self.tabits.borrow_mut();
-            *t = state.tabits;
-        }
+        self.restore(sid, state, run_init).await
+    }
 
-        {
-            let mut i = self.input_tabit_idx.borrow_mut();
-            *i = state.tabit_idx;
-        }
+    fn set_state_store(&mut self, store: Arc<dyn StateStore>, prefix: &str) {
+        self.store = Some((store, prefix.to_string()));
+    }
 
-        if run_init {
-            let sid = SessionId::new(FuncId::Fthresh);
-            self.tabit
-                .init(sid, state.delta)
-                .await
-                .with_context(|| self.err(sid, "Failed to initialize Ftabit"))?;
+    async fn resume_from_store(&mut self, run_init: bool) -> Result<(), UnexpectedError> {
+        let sid = SessionId::new(FuncId::Fthresh);
+
+        let records = self
+            .records()
+            .ok_or_else(|| self.unexpected(sid, "No state store to resume from"))?;
+        let ((delta, epoch), batches): (_, Vec<Batch<T>>) = records
+            .load()
+            .with_context(|| self.err(sid, "Failed to load state from store"))?
+            .ok_or_else(|| self.unexpected(sid, "No state in store"))?;
+
+        let mut state = State {
+            delta,
+            tabits: Vec::new(),
+            tabit_idx: HashMap::new(),
+            epoch,
+        };
+        for (tabits, tabit_idx) in batches {
+            state.tabits.extend(tabits);
+            state.tabit_idx.extend(tabit_idx);
         }
 
-        Ok(())
+        self.restore(sid, state, run_init).await
     }
 
     async fn init(&mut self) -> Result<(), UnexpectedError> {
@@ -132,10 +171,7 @@ where
             T::rand(&mut rng)
         };
 
-        {
-            let mut d = self.delta.borrow_mut();
-            *d = Some(delta);
-        }
+        self.delta = Some(delta);
 
         let sid = SessionId::new(FuncId::Fthresh);
         trace!("{}: init ({sid})", self.party_id);
@@ -147,7 +183,7 @@ where
             .await
             .with_context(|| self.err(sid, "Failed to initialize Ftabit"))?;
 
-        Ok(())
+        self.persist_all(sid)
     }
 
     async fn setup<I, O: CircuitElement>(
@@ -156,14 +192,32 @@ where
         out_ids: &[InputId],
         circuit: &TCircuit<I, O>,
     ) -> Result<(), UnexpectedError> {
-        let delta = { self.delta.borrow().clone().unwrap() };
+        let input_sizes = vec![input.len(); self.parties.len()];
+        self.setup_with_sizes(input, &input_sizes, out_ids, circuit)
+            .await
+    }
 
+    async fn setup_with_sizes<I, O: CircuitElement>(
+        &mut self,
+        input: &[bool],
+        input_sizes: &[usize],
+        out_ids: &[InputId],
+        circuit: &TCircuit<I, O>,
+    ) -> Result<(), UnexpectedError> {
         let sid = SessionId::new(FuncId::Fthresh);
-        let mpc_sid = self.cur_mpc_sid.fetch_add(1, Ordering::SeqCst);
-        let mpc_sid = SessionId {
-            parent: FuncId::Fthresh,
-            id: mpc_sid,
-        };
+        check_input_sizes(
+            &self.parties,
+            self.party_id,
+            input,
+            input_sizes,
+            circuit.inputs.len(),
+        )
+        .with_context(|| self.err(sid, "Invalid setup inputs"))?;
+
+        let delta = self.delta.unwrap();
+
+        let mpc = self.next_mpc();
+        let mpc_sid = mpc.sid;
         self.mpc
             .init(mpc_sid, Some(delta))
             .await
@@ -190,25 +244,8 @@ where
             .await
             .with_context(|| self.err(sid, format!("Failed to provide abits to Fmpc {mpc_sid}")))?;
 
-        let size = input.len();
-        // Simplify for now, assume all parties have the  same number of inputs
-        assert!(circuit.inputs.len() == size * self.parties.len());
-        for &p in self.parties.iter() {
-            let input = if p == self.party_id {
-                Some(input.to_vec())
-            } else {
-                None
-            };
-            self.mpc
-                .input_multi(mpc_sid, p, size, input)
-                .await
-                .with_context(|| {
-                    self.err(
-                        sid,
-                        format!("Failed to provide input from {p} to Fmpc {mpc_sid}"),
-                    )
-                })?;
-        }
+        self.input_private(sid, mpc_sid, &self.parties, input_sizes, input)
+            .await?;
 
         let mask_circuit = out_mask(circuit);
 
@@ -229,18 +266,7 @@ where
         // current tabits are <<r>> calculate <<s>> = (s+r) + <<r>>
         tabits.add_consts(&bits, delta);
 
-        {
-            let mut prev = self.tabits.borrow_mut();
-            prev.push(tabits);
-        }
-
-        {
-            let mut map = self.input_tabit_idx.borrow_mut();
-            let next_idx = map.len();
-            map.extend(out_ids.iter().cloned().zip(next_idx..));
-        }
-
-        Ok(())
+        self.add_batch(sid, tabits, out_ids)
     }
 
     async fn sample(&mut self, ids: &[InputId]) -> Result<(), UnexpectedError> {
@@ -252,138 +278,732 @@ where
             )
         })?;
 
-        {
-            let mut prev = self.tabits.borrow_mut();
-            prev.push(tabits);
+        self.add_batch(sid, tabits, ids)
+    }
+
+    async fn eval<I, O: CircuitElement>(
+        &self,
+        parties: &[PartyId],
+        ids: &[InputId],
+        circuit: &TCircuit<I, O>,
+    ) -> Result<O, UnexpectedError> {
+        assert!(ids.len() == circuit.inputs.len());
+
+        let session = self.sessions.start(("eval", parties, ids, circuit)).await;
+        let sid = session.sid;
+
+        let mpc = self.input_abits(sid, parties, ids).await?;
+        let mpc_sid = mpc.sid;
+
+        let out = self
+            .mpc
+            .eval_pub(mpc_sid, parties, circuit)
+            .await
+            .with_context(|| {
+                self.err(
+                    sid,
+                    format!("Failed to evaluate the circuit in Fmpc {mpc_sid}"),
+                )
+            })?;
+
+        Ok(out)
+    }
+
+    async fn eval_with_inputs<I, O: CircuitElement>(
+        &self,
+        parties: &[PartyId],
+        inputs: &[EvalInput],
+        private_input: &[bool],
+        circuit: &TCircuit<I, O>,
+    ) -> Result<O, UnexpectedError> {
+        let arranged = arrange_inputs(parties, self.party_id, inputs, private_input, circuit)?;
+
+        let session = self
+            .sessions
+            .start(("eval_with_inputs", parties, inputs, circuit))
+            .await;
+        let sid = session.sid;
+
+        // the stored inputs come first as abits, then each party's private inputs
+        let mpc = self.input_abits(sid, parties, &arranged.ids).await?;
+        let mpc_sid = mpc.sid;
+        self.input_private(
+            sid,
+            mpc_sid,
+            parties,
+            &arranged.private_sizes,
+            private_input,
+        )
+        .await?;
+
+        let out = self
+            .mpc
+            .eval_pub(mpc_sid, parties, &arranged.circuit)
+            .await
+            .with_context(|| {
+                self.err(
+                    sid,
+                    format!("Failed to evaluate the circuit in Fmpc {mpc_sid}"),
+                )
+            })?;
+
+        Ok(out)
+    }
+
+    async fn eval_to<I, O: CircuitElement>(
+        &self,
+        parties: &[PartyId],
+        ids: &[InputId],
+        circuit: &TCircuit<I, O>,
+        recipient: PartyId,
+    ) -> Result<Option<O>, UnexpectedError> {
+        assert!(ids.len() == circuit.inputs.len());
+
+        let session = self
+            .sessions
+            .start(("eval_to", parties, ids, circuit, recipient))
+            .await;
+        let sid = session.sid;
+
+        if !parties.contains(&recipient) {
+            return Err(self.unexpected(
+                sid,
+                format!("{recipient} is not one of the parties {parties:?}"),
+            ));
         }
 
+        let mpc = self.input_abits(sid, parties, ids).await?;
+        let mpc_sid = mpc.sid;
+
+        let output_assignment = (0..circuit.outputs.len())
+            .map(|w| (w, recipient as i32))
+            .collect();
+        let is_recipient = recipient == self.party_id;
+        let parse_fn = |bits: &[bool]| is_recipient.then(|| O::from_bits(bits));
+
+        let out = self
+            .mpc
+            .eval_generic(mpc_sid, parties, circuit, output_assignment, parse_fn)
+            .await
+            .with_context(|| {
+                self.err(
+                    sid,
+                    format!("Failed to evaluate the circuit for {recipient} in Fmpc {mpc_sid}"),
+                )
+            })?;
+
+        Ok(out)
+    }
+
+    /// The quorum `parties` evaluates the circuit masked by new tabits, then passes the masked
+    /// outputs on to the rest of the committee, who need them to form their new tabits too.
+    /// Every party in the quorum must pass on the same outputs, so one honest member is enough.
+    async fn eval_to_state<I, O: CircuitElement>(
+        &mut self,
+        parties: &[PartyId],
+        in_ids: &[InputId],
+        circuit: &TCircuit<I, O>,
+        out_ids: &[InputId],
+    ) -> Result<(), UnexpectedError> {
+        assert!(in_ids.len() == circuit.inputs.len());
+        assert!(out_ids.len() == circuit.outputs.len());
+
+        let sid = SessionId::new(FuncId::Fthresh);
+        // the tabits are converted for the quorum in the order of the committee
+        let mut quorum = parties.to_vec();
+        quorum.sort_unstable();
+        quorum.dedup();
+        if quorum.len() != parties.len()
+            || parties.len() < self.t.into()
+            || !parties.iter().all(|p| self.parties.contains(p))
         {
-            let mut map = self.input_tabit_idx.borrow_mut();
-            let next_idx = map.len();
-            map.extend(ids.iter().cloned().zip(next_idx..));
+            return Err(self.unexpected(
+                sid,
+                format!("{parties:?} is not a quorum of {:?}", self.parties),
+            ));
         }
 
-        Ok(())
+        let delta = self.delta.unwrap();
+        // every party takes both instances so that the counters stay in step
+        let mpc = self.next_mpc();
+        let share_mpc = self.next_mpc();
+
+        // as in setup, mask the outputs with new tabits <<r>>
+        let mut tabits = self
+            .tabit
+            .sample(sid, out_ids.len())
+            .await
+            .with_context(|| {
+                self.err(
+                    sid,
+                    format!("Failed to sample new thresh abits with ids {out_ids:?}"),
+                )
+            })?;
+
+        // the quorum learns the masked output bits (s + r)
+        let mut bits = vec![false; O::BIT_SIZE];
+        if quorum.contains(&self.party_id) {
+            let mpc_sid = mpc.sid;
+            self.mpc
+                .init(mpc_sid, Some(delta))
+                .await
+                .with_context(|| self.err(sid, format!("Failed to initialize Fmpc {mpc_sid}")))?;
+
+            let my_point = T::from(self.party_id.into());
+            let sub_points: Vec<_> = quorum.iter().map(|p| T::from(*p as u64)).collect();
+            let idx: Vec<usize> = (0..out_ids.len()).collect();
+            let mut abits = tabits.convert(&my_point, &self.party_points, &sub_points, &idx);
+            abits.append(self.convert_ids(&quorum, in_ids));
+
+            self.mpc.input_abit(mpc_sid, abits).await.with_context(|| {
+                self.err(sid, format!("Failed to provide abits to Fmpc {mpc_sid}"))
+            })?;
+
+            let masked_out = self
+                .mpc
+                .eval_pub(mpc_sid, &quorum, &out_mask(circuit))
+                .await
+                .with_context(|| {
+                    self.err(
+                        sid,
+                        format!("Failed to evaluate the circuit in Fmpc {mpc_sid}"),
+                    )
+                })?;
+            masked_out.to_bits(&mut bits[..]);
+        }
+        drop(mpc);
+
+        // and passes them on to the whole committee
+        let share_sid = share_mpc.sid;
+        self.mpc
+            .init(share_sid, Some(delta))
+            .await
+            .with_context(|| self.err(sid, format!("Failed to initialize Fmpc {share_sid}")))?;
+        let bits = agree_on_bits(
+            &*self.mpc,
+            share_sid,
+            self.party_id,
+            &quorum,
+            &self.parties,
+            bits.len(),
+            &bits,
+        )
+        .await
+        .with_context(|| self.err(sid, "Quorum disagrees on the masked outputs"))?;
+
+        // current tabits are <<r>> calculate <<s>> = (s+r) + <<r>>
+        tabits.add_consts(&bits, delta);
+
+        self.add_batch(sid, tabits, out_ids)
     }
 
-    async fn eval<I, O: CircuitElement>(
-        &mut self,
+    async fn reveal_to(
+        &self,
         parties: &[PartyId],
+        party: PartyId,
         ids: &[InputId],
-        circuit: &TCircuit<I, O>,
-    ) -> Result<O, UnexpectedError> {
-        assert!(ids.len() == circuit.inputs.len());
+    ) -> Result<Option<Vec<bool>>, UnexpectedError> {
+        let session = self
+            .sessions
+            .start(("reveal_to", parties, party, ids))
+            .await;
+        let sid = session.sid;
+
+        if !parties.contains(&self.party_id) || !parties.contains(&party) {
+            return Err(self.unexpected(
+                sid,
+                format!("Both {party} and this party must be in {parties:?} to reveal inputs"),
+            ));
+        }
+
+        let abits = self.convert_ids(parties, ids);
 
-        let delta = { self.delta.borrow().clone().unwrap() };
+        // the tabits were all made under the one Ftabit instance, only the opening is per sid
+        let tabit_sid = SessionId::new(FuncId::Fthresh);
+        let bits = self
+            .tabit
+            .open_to(tabit_sid, sid, &abits, parties, party)
+            .await
+            .with_context(|| self.err(sid, format!("Failed to reveal {ids:?} to {party}")))?;
+
+        Ok(bits)
+    }
 
+    async fn delete(&mut self, ids: &[InputId]) -> Result<(), UnexpectedError> {
         let sid = SessionId::new(FuncId::Fthresh);
-        let mpc_sid = self.cur_mpc_sid.fetch_add(1, Ordering::SeqCst);
-        let mpc_sid = SessionId {
+        if let Some(id) = ids
+            .iter()
+            .find(|&id| !self.input_tabit_idx.contains_key(id))
+        {
+            return Err(self.unexpected(sid, format!("No input with id {id} to delete")));
+        }
+        trace!("{}: delete {} inputs ({sid})", self.party_id, ids.len());
+
+        let mpc = self.next_mpc();
+        let mpc_sid = mpc.sid;
+        self.mpc
+            .init(mpc_sid, self.delta)
+            .await
+            .with_context(|| self.err(sid, format!("Failed to initialize Fmpc {mpc_sid}")))?;
+        agree_on_ids(&*self.mpc, mpc_sid, self.party_id, &self.parties, ids)
+            .await
+            .with_context(|| self.err(sid, "Parties disagree on the inputs to delete"))?;
+
+        // the elements of the tabits that held a deleted bit
+        let element = |&(batch, bit): &(usize, usize)| (batch, self.tabits[batch].element(bit));
+        let deleted: HashSet<_> = ids.iter().collect();
+        let dirty: HashSet<_> = ids
+            .iter()
+            .map(|id| element(&self.input_tabit_idx[id]))
+            .collect();
+
+        // the inputs left in those elements are stored afresh, so the elements can be dropped
+        let mut moved: Vec<_> = self
+            .input_tabit_idx
+            .iter()
+            .filter(|(id, loc)| !deleted.contains(id) && dirty.contains(&element(loc)))
+            .map(|(&id, _)| id)
+            .collect();
+        moved.sort_unstable();
+        if !moved.is_empty() {
+            self.move_inputs(sid, &moved).await?;
+        }
+
+        // nothing is removed until the moved inputs are safely stored
+        for id in ids.iter() {
+            self.input_tabit_idx.remove(id);
+        }
+        self.compact();
+        self.persist_all(sid)
+    }
+
+    fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    async fn refresh(&mut self) -> Result<(), UnexpectedError> {
+        let sid = SessionId::new(FuncId::Fthresh);
+        trace!("{}: refresh epoch {} ({sid})", self.party_id, self.epoch);
+
+        // the tabits are only modified once the zero sharings have been checked
+        self.tabit
+            .refresh(sid, &mut self.tabits)
+            .await
+            .with_context(|| self.err(sid, format!("Failed to refresh epoch {}", self.epoch)))?;
+        self.epoch += 1;
+
+        self.persist_all(sid)
+    }
+}
+
+impl<T, FM: AsyncMpc<T>, FT: AsyncTabit<T>> RstThreshPlayer<T, FM, FT>
+where
+    T: Field + CircuitElement + RandElement + Copy + Serialize,
+    for<'d> T: Deserialize<'d>,
+{
+    /// The next Fmpc instance for an operation that changes the stored inputs
+    fn next_mpc(&self) -> MpcInstance<T, FM> {
+        let id = self.cur_mpc_sid.fetch_add(1, Ordering::SeqCst);
+        let sid = SessionId {
             parent: FuncId::Fthresh,
-            id: mpc_sid,
+            id,
         };
+        MpcInstance::new(self.mpc.clone(), sid)
+    }
+
+    /// Start the Fmpc instance of session `sid` and provide it the inputs `ids` as abits
+    /// shared among `parties`, giving the instance
+    async fn input_abits(
+        &self,
+        sid: SessionId,
+        parties: &[PartyId],
+        ids: &[InputId],
+    ) -> Result<MpcInstance<T, FM>, UnexpectedError> {
+        let delta = self.delta.unwrap();
+
+        let mpc = MpcInstance::new(self.mpc.clone(), sid.derive_ssid(FuncId::Fthresh));
+        let mpc_sid = mpc.sid;
         self.mpc
             .init(mpc_sid, Some(delta))
             .await
             .with_context(|| self.err(sid, format!("Failed to initialize Fmpc {mpc_sid}")))?;
 
-        let all_ids: Vec<_> = {
-            let map = self.input_tabit_idx.borrow();
-            ids.iter().map(|&i| map[&i].clone()).collect()
-        };
-
         // Convert the saved tabits into t-party abits
-        let abits = {
-            let all_tabits = self.tabits.borrow();
+        let abits = self.convert_ids(parties, ids);
 
-            let my_point = T::from(self.party_id.into());
-            let all_points = &self.party_points;
-            let sub_points: Vec<_> = parties.iter().map(|p| T::from(*p as u64)).collect();
-
-            let mut start = 0;
-            // TODO: this doesn't work well if the ids splice between multiple sets of abits
-            all_tabits
-                .iter()
-                .map(|ts| {
-                    let end = start + ts.nbits;
-                    let idx: Vec<_> = all_ids
-                        .iter()
-                        .filter(|&&i| i >= start && i < end)
-                        .map(|&i| i - start)
-                        .collect();
-                    start = end;
-                    ts.convert(&my_point, all_points, &sub_points, &idx)
-                })
-                .reduce(|mut a, b| {
-                    a.append(b);
-                    a
-                })
-                .unwrap()
-        };
+        self.mpc
+            .input_abit(mpc_sid, abits)
+            .await
+            .with_context(|| self.err(sid, format!("Failed to provide abits to Fmpc {mpc_sid}")))?;
+
+        Ok(mpc)
+    }
+
+    /// Provide `sizes[i]` private input bits from each of `parties` to the Fmpc instance
+    /// `mpc_sid`, where ours are `private_input`
+    async fn input_private(
+        &self,
+        sid: SessionId,
+        mpc_sid: SessionId,
+        parties: &[PartyId],
+        sizes: &[usize],
+        private_input: &[bool],
+    ) -> Result<(), UnexpectedError> {
+        for (&p, &size) in parties.iter().zip(sizes.iter()) {
+            if size == 0 {
+                continue;
+            }
+            let input = (p == self.party_id).then(|| private_input.to_vec());
+            self.mpc
+                .input_multi(mpc_sid, p, size, input)
+                .await
+                .with_context(|| {
+                    self.err(
+                        sid,
+                        format!("Failed to provide input from {p} to Fmpc {mpc_sid}"),
+                    )
+                })?;
+        }
+
+        Ok(())
+    }
+
+    /// Store the inputs `ids` in a new batch of tabits, leaving their old bits unused
+    async fn move_inputs(
+        &mut self,
+        sid: SessionId,
+        ids: &[InputId],
+    ) -> Result<(), UnexpectedError> {
+        let delta = self.delta.unwrap();
+        let mpc = self.next_mpc();
+        let mpc_sid = mpc.sid;
+        self.mpc
+            .init(mpc_sid, Some(delta))
+            .await
+            .with_context(|| self.err(sid, format!("Failed to initialize Fmpc {mpc_sid}")))?;
+
+        // as in setup, mask the inputs with new tabits <<r>>
+        let mut tabits = self.tabit.sample(sid, ids.len()).await.with_context(|| {
+            self.err(
+                sid,
+                format!("Failed to sample new thresh abits for ids {ids:?}"),
+            )
+        })?;
+
+        let my_point = T::from(self.party_id.into());
+        let idx: Vec<usize> = (0..ids.len()).collect();
+        let mut abits = tabits.convert(&my_point, &self.party_points, &self.party_points, &idx);
+        abits.append(self.convert_ids(&self.parties, ids));
 
         self.mpc
             .input_abit(mpc_sid, abits)
             .await
             .with_context(|| self.err(sid, format!("Failed to provide abits to Fmpc {mpc_sid}")))?;
 
-        let out = self
+        let n = ids.len();
+        let xor_c: TCircuit<Vec<bool>, Vec<bool>> = {
+            let bit_xor: TCircuit<[bool; 2], bool> =
+                TCircuit::from_parts(vec![0, 1], vec![Gate::Xor(0, 1, 2)], vec![2]);
+            let (mut b, ins) = new_builder().add_input_multi::<Vec<bool>>(2 * n, None);
+            let mut outs = Vec::with_capacity(n);
+            for i in 0..n {
+                let (b2, out) = b.extend_circuit(&[ins[i], ins[n + i]], &bit_xor, None);
+                b = b2;
+                outs.extend(out);
+            }
+            b.refine_input().refine_output(&outs).to_circuit()
+        };
+
+        // mpc outputs the masked inputs (x + r)
+        let output_assignment = (0..n).map(|w| (w, 0)).collect();
+        let masked = self
             .mpc
-            .eval_pub(mpc_sid, &parties, circuit)
+            .eval_generic(
+                mpc_sid,
+                &self.parties,
+                &xor_c,
+                output_assignment,
+                |bits: &[bool]| bits.to_vec(),
+            )
             .await
-            .with_context(|| {
-                self.err(
-                    sid,
-                    format!("Failed to evaluate the circuit in Fmpc {mpc_sid}"),
-                )
-            })?;
+            .with_context(|| self.err(sid, format!("Failed to mask inputs in Fmpc {mpc_sid}")))?;
 
-        Ok(out)
+        // current tabits are <<r>> calculate <<x>> = (x+r) + <<r>>
+        tabits.add_consts(&masked, delta);
+
+        self.add_batch(sid, tabits, ids)
+    }
+
+    /// Drop the bits of the tabits that no input refers to, along with any elements or
+    /// batches left without bits
+    fn compact(&mut self) {
+        let mut kept = vec![Vec::new(); self.tabits.len()];
+        for (&id, &(batch, bit)) in self.input_tabit_idx.iter() {
+            kept[batch].push((bit, id));
+        }
+
+        let mut tabits = Vec::new();
+        let mut idx = HashMap::new();
+        for (ts, mut bits) in self.tabits.iter().zip(kept) {
+            if bits.is_empty() {
+                continue;
+            }
+            bits.sort_unstable();
+            let sel: Vec<_> = bits.iter().map(|&(bit, _)| bit).collect();
+            idx.extend(
+                bits.iter()
+                    .enumerate()
+                    .map(|(i, &(_, id))| (id, (tabits.len(), i))),
+            );
+            tabits.push(ts.slice(&sel));
+        }
+
+        self.tabits = tabits;
+        self.input_tabit_idx = idx;
+    }
+
+    /// Convert the saved tabits of the inputs `ids` into abits shared among `parties`,
+    /// in the order of `ids`
+    fn convert_ids(&self, parties: &[PartyId], ids: &[InputId]) -> Abits<T> {
+        let locs: Vec<_> = ids.iter().map(|i| self.input_tabit_idx[i]).collect();
+
+        let all_tabits = &self.tabits;
+
+        let my_point = T::from(self.party_id.into());
+        let all_points = &self.party_points;
+        let sub_points: Vec<_> = parties.iter().map(|p| T::from(*p as u64)).collect();
+
+        // convert all of the bits wanted from each batch at once
+        let mut wanted = vec![Vec::new(); all_tabits.len()];
+        for &(batch, bit) in locs.iter() {
+            wanted[batch].push(bit);
+        }
+
+        let mut next = Vec::with_capacity(all_tabits.len());
+        let mut abits = Abits::empty(parties.len() - 1);
+        for (ts, idx) in all_tabits.iter().zip(wanted.iter()) {
+            next.push(abits.len());
+            if !idx.is_empty() {
+                abits.append(ts.convert(&my_point, all_points, &sub_points, idx));
+            }
+        }
+
+        // the abits are grouped by batch, put them back in the order of ids
+        let perm: Vec<_> = locs
+            .iter()
+            .map(|&(batch, _)| {
+                next[batch] += 1;
+                next[batch] - 1
+            })
+            .collect();
+        abits.permute(&perm)
+    }
+
+    async fn restore(
+        &mut self,
+        sid: SessionId,
+        state: State<T>,
+        run_init: bool,
+    ) -> Result<(), UnexpectedError> {
+        // after a restart only the state store knows how far the shares have been refreshed
+        let epoch = match self.records() {
+            Some(records) => records
+                .epoch()
+                .with_context(|| self.err(sid, "Failed to read the stored epoch"))?
+                .max(self.epoch),
+            None => self.epoch,
+        };
+        if state.epoch < epoch {
+            return Err(self.unexpected(
+                sid,
+                format!(
+                    "Saved state is from epoch {} but shares have been refreshed to epoch {epoch}",
+                    state.epoch,
+                ),
+            ));
+        }
+        self.epoch = state.epoch;
+
+        self.delta = Some(state.delta);
+        self.input_tabit_idx = batch_index(&state.tabits, &state.tabit_idx);
+        self.tabits = state.tabits;
+
+        if run_init {
+            let sid = SessionId::new(FuncId::Fthresh);
+            self.tabit
+                .init(sid, state.delta)
+                .await
+                .with_context(|| self.err(sid, "Failed to initialize Ftabit"))?;
+        }
+
+        Ok(())
+    }
+
+    fn records(&self) -> Option<StateRecords<'_>> {
+        self.store.as_ref().map(|(store, prefix)| StateRecords {
+            store: store.as_ref(),
+            prefix,
+            header: self.state_header(),
+            key: self.state_key.as_ref(),
+        })
+    }
+
+    /// Store the new `tabits` holding `ids`, and append them to the state store if there is one
+    fn add_batch(
+        &mut self,
+        sid: SessionId,
+        tabits: ThreshAbits<T>,
+        ids: &[InputId],
+    ) -> Result<(), UnexpectedError> {
+        let batch_num = self.tabits.len();
+        let start = self.tabits.iter().map(|ts| ts.nbits).sum::<usize>();
+
+        if let Some(records) = self.records() {
+            let base = (self.delta.unwrap(), self.epoch);
+            let flat_idx: HashMap<_, _> = ids.iter().cloned().zip(start..).collect();
+            let batch = (vec![tabits.clone()], flat_idx);
+            records
+                .append(&base, &batch)
+                .with_context(|| self.err(sid, "Failed to persist state"))?;
+        }
+
+        self.tabits.push(tabits);
+        self.input_tabit_idx.extend(
+            ids.iter()
+                .enumerate()
+                .map(|(bit, &id)| (id, (batch_num, bit))),
+        );
+
+        Ok(())
+    }
+
+    /// Replace everything in the state store with the current state, if there is one
+    fn persist_all(&self, sid: SessionId) -> Result<(), UnexpectedError> {
+        if let Some(records) = self.records() {
+            let base = (self.delta.unwrap(), self.epoch);
+            let batch = (
+                self.tabits.clone(),
+                flat_index(&self.tabits, &self.input_tabit_idx),
+            );
+            records
+                .set_epoch(self.epoch)
+                .and_then(|_| records.replace(&base, &batch))
+                .with_context(|| self.err(sid, "Failed to persist state"))?;
+        }
+        Ok(())
     }
 }
 
 impl<T: Field, FM: AsyncMpc<T>, FT: AsyncTabit<T>> RstThreshPlayer<T, FM, FT> {
+    fn state(&self) -> State<T> {
+        State {
+            delta: self.delta.clone().unwrap(),
+            tabits: self.tabits.clone(),
+            tabit_idx: flat_index(&self.tabits, &self.input_tabit_idx),
+            epoch: self.epoch,
+        }
+    }
+
+    fn state_header(&self) -> StateHeader {
+        StateHeader::new(self.party_id, self.n, self.t, StateBackend::Rst)
+    }
+
     pub fn new(
         party_id: PartyId,
         n: PartyId,
-        _t: u16,
+        t: u16,
         mpc: Arc<FM>,
         tabit: Arc<FT>,
     ) -> Result<Self, ()> {
+        // The delta is handed to Fmpc, which garbles under it and so needs all 128 bits of it.
+        // Supporting narrower fields such as FF2_64 or FF2_8 is out of scope.
+        if T::BYTES != 16 {
+            return Err(());
+        }
+
         let parties: Vec<PartyId> = (1..n + 1).collect();
         let party_points: Vec<T> = (1..n + 1).map(|x| T::from(x.into())).collect();
 
         Ok(RstThreshPlayer {
             party_id: party_id,
-            //n: n,
-            //t: t,
-            tabits: RefCell::new(Vec::new()),
-            input_tabit_idx: RefCell::new(HashMap::new()),
+            n: n,
+            t: t,
+            tabits: Vec::new(),
+            input_tabit_idx: HashMap::new(),
             parties,
             party_points,
             mpc: mpc,
             tabit: tabit,
             cur_mpc_sid: AtomicU64::new(0),
-            delta: RefCell::new(None),
+            sessions: Sessions::default(),
+            state_key: None,
+            store: None,
+            delta: None,
+            epoch: 0,
         })
     }
 }
 
+/// The position of the first bit of each batch of `tabits` counting across all of them
+fn batch_starts<T>(tabits: &[ThreshAbits<T>]) -> Vec<usize> {
+    tabits
+        .iter()
+        .scan(0, |start, ts| {
+            *start += ts.nbits;
+            Some(*start - ts.nbits)
+        })
+        .collect()
+}
+
+/// The (batch, bit) of each input from the position of its bit across all of `tabits`
+fn batch_index<T>(
+    tabits: &[ThreshAbits<T>],
+    flat_idx: &HashMap<InputId, usize>,
+) -> HashMap<InputId, (usize, usize)> {
+    let starts = batch_starts(tabits);
+
+    flat_idx
+        .iter()
+        .map(|(&id, &i)| {
+            let batch = starts.partition_point(|&s| s <= i) - 1;
+            (id, (batch, i - starts[batch]))
+        })
+        .collect()
+}
+
+/// The position of each input's bit across all of `tabits` from its (batch, bit)
+fn flat_index<T>(
+    tabits: &[ThreshAbits<T>],
+    idx: &HashMap<InputId, (usize, usize)>,
+) -> HashMap<InputId, usize> {
+    let starts = batch_starts(tabits);
+
+    idx.iter()
+        .map(|(&id, &(batch, bit))| (id, starts[batch] + bit))
+        .collect()
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
     use crate::{
-        circuits::{arith::sum_circuit, Gate, TCircuit},
+        circuits::{arith::sum_circuit, generic_thresh::tests::copy_circuit, Gate, TCircuit},
         ff2_128::FF2_128,
-        func_abit::tests::build_test_abits,
-        func_com::tests::build_test_coms,
-        func_cote::tests::build_test_cotes,
-        func_mpc::tests::build_test_mpcs,
-        func_mult::tests::build_test_mults,
-        func_net::tests::{build_test_nets, get_test_party_infos},
-        func_rand::tests::build_test_rands,
-        func_thresh_abit::tests::build_test_tabits,
+        func_abit::{tests::build_test_abits, WrkAbitPlayer},
+        func_com::{tests::build_test_coms, FolkloreComPlayer},
+        func_cote::{tests::build_test_cotes, KosCotePlayer},
+        func_mpc::tests::{build_test_mpcs, TestMpc},
+        func_mult::{tests::build_test_mults, DklsMultPlayer},
+        func_net::{
+            tests::{build_test_nets, get_test_party_infos},
+            AsyncNetworkMgr,
+        },
+        func_rand::{tests::build_test_rands, FolkloreRandPlayer},
+        func_thresh::store::MemoryStateStore,
+        func_thresh_abit::{tests::build_test_tabits, RstTabitPlayer},
     };
     use tokio::io;
+    use tokio::net::TcpStream;
     use tokio::task::JoinSet;
 
     pub fn build_test_threshs<FM: AsyncMpc<FF2_128>, FT: AsyncTabit<FF2_128>>(
@@ -405,31 +1025,51 @@ pub mod tests {
             .collect()
     }
 
-    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
-    async fn test_full_execution() -> io::Result<()> {
-        let _ = env_logger::builder().is_test(true).try_init();
-
-        let party_info = get_test_party_infos(3);
+    type TestNet = AsyncNetworkMgr<TcpStream, TcpStream>;
+    type TestCom = FolkloreComPlayer<TestNet>;
+    type TestMult = DklsMultPlayer<FF2_128, TestNet, KosCotePlayer<TestNet>>;
+    type TestTabit = RstTabitPlayer<
+        FF2_128,
+        WrkAbitPlayer<FF2_128>,
+        FolkloreRandPlayer<TestCom>,
+        TestMult,
+        TestCom,
+        TestNet,
+    >;
+    pub type TestThresh = RstThreshPlayer<FF2_128, TestMpc<FF2_128>, TestTabit>;
+
+    /// Build `num` players along with everything they run on
+    pub async fn build_test_stack(num: PartyId) -> Vec<TestThresh> {
+        let party_info = get_test_party_infos(num);
         let nets = build_test_nets(
             &party_info,
             vec![FuncId::Fcom, FuncId::Fcote, FuncId::Fmult, FuncId::Ftabit],
         )
         .await;
-        let mpcs = build_test_mpcs(&party_info);
+        let mpcs = build_test_mpcs::<FF2_128>(&party_info).await;
         let coms = build_test_coms(&nets);
         let rands = build_test_rands(&coms);
         let abits = build_test_abits(&party_info);
         let cotes = build_test_cotes(&nets, &party_info);
         let mults = build_test_mults(&nets, &cotes);
         let tabits = build_test_tabits(&abits, &rands, &mults, &coms, &nets);
-        let threshs = build_test_threshs(&mpcs, &tabits);
+        build_test_threshs(&mpcs, &tabits)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_full_execution() -> io::Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let threshs = build_test_stack(3).await;
+
+        // the players can be shared between the tasks running concurrent sessions
+        fn is_send_sync<T: Send + Sync>(_: &T) {}
+        is_send_sync(&threshs[0]);
 
         let mut js = JoinSet::new();
 
         for (i, t) in threshs.into_iter().enumerate() {
             js.spawn(async move {
-                // can't have a normal reference and await as that will trigger Send errors
-                // but also if I have the mutable reference I don't need refcell at all
                 let mut t = t;
                 t.init().await?;
 
@@ -454,7 +1094,6 @@ pub mod tests {
                 };
 
                 let parties: Vec<_> = (1..=3).collect();
-
                 let out = t.eval(&parties, &out_ids, &c).await;
 
                 out
@@ -468,4 +1107,339 @@ pub mod tests {
 
         Ok(())
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_private_outputs() -> io::Result<()> {
+        let threshs = build_test_stack(3).await;
+
+        let mut js = JoinSet::new();
+
+        for (i, t) in threshs.into_iter().enumerate() {
+            js.spawn(async move {
+                let mut t = t;
+                t.init().await?;
+
+                let mut input = vec![false; 128];
+                input[i] = true;
+
+                let out_ids: Vec<_> = (0..128).collect();
+                let sum_c = sum_circuit::<FF2_128>(3);
+                t.setup(&input, &out_ids, &sum_c).await?;
+
+                // a second batch where bits 3, 4 and 5 are set
+                let mut input = vec![false; 128];
+                input[i + 3] = true;
+                let out_ids: Vec<_> = (128..256).collect();
+                t.setup(&input, &out_ids, &sum_c).await?;
+
+                // ids out of order, repeated, and spliced between the batches
+                let ids = [5, 2, 0, 131, 1, 2, 133, 130, 131];
+                let bits = t.reveal_to(&[1, 2, 3], 2, &ids).await?;
+
+                // only party 3 learns the sum of the two batches
+                let ids: Vec<_> = (0..256).collect();
+                let sum_c = sum_circuit::<FF2_128>(2);
+                let sum = t.eval_to(&[1, 2, 3], &ids, &sum_c, 3).await?;
+                assert_eq!(sum.is_some(), t.party() == 3);
+                let public_sum = t.eval(&[1, 2, 3], &ids, &sum_c).await?;
+                if let Some(sum) = sum {
+                    assert_eq!(sum, public_sum);
+                }
+
+                // the sum is stored as new inputs, then revealed
+                let sum_ids: Vec<_> = (256..384).collect();
+                t.eval_to_state(&[1, 2, 3], &ids, &sum_c, &sum_ids).await?;
+                let sum_bits = t.reveal_to(&[1, 2, 3], 1, &sum_ids[..8]).await?;
+                let expected = [true, true, true, true, true, true, false, false];
+                if t.party() == 1 {
+                    assert_eq!(sum_bits, Some(expected.to_vec()));
+                }
+
+                // and again from a quorum, revealed to the party left out of it
+                let quorum_ids: Vec<_> = (384..512).collect();
+                t.eval_to_state(&[3, 1], &ids, &sum_c, &quorum_ids).await?;
+                let sum_bits = t.reveal_to(&[1, 2, 3], 2, &quorum_ids[..8]).await?;
+                if t.party() == 2 {
+                    assert_eq!(sum_bits, Some(expected.to_vec()));
+                }
+
+                Ok::<_, UnexpectedError>((t.party(), bits))
+            });
+        }
+
+        while let Some(r) = js.join_next().await {
+            let (p, bits) = r.unwrap().unwrap();
+            if p == 2 {
+                assert_eq!(
+                    bits,
+                    Some(vec![false, true, true, true, true, true, true, false, true])
+                );
+            } else {
+                assert_eq!(bits, None);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_sessions() -> io::Result<()> {
+        let threshs = build_test_stack(3).await;
+
+        let mut js = JoinSet::new();
+
+        for (i, t) in threshs.into_iter().enumerate() {
+            js.spawn(async move {
+                let mut t = t;
+                t.init().await?;
+
+                let sum_c = sum_circuit::<FF2_128>(3);
+                for batch in 0..2 {
+                    let mut input = vec![false; 128];
+                    input[i + 3 * batch] = true;
+                    let out_ids: Vec<_> = (128 * batch..128 * (batch + 1)).collect();
+                    t.setup(&input, &out_ids, &sum_c).await?;
+                }
+
+                // two evaluations and a reveal at once, each in its own session
+                let ids: Vec<_> = (0..256).collect();
+                let sum_c = sum_circuit::<FF2_128>(2);
+                let (sum_1, sum_2, bits) = tokio::join!(
+                    t.eval(&[1, 2, 3], &ids, &sum_c),
+                    t.eval(&[1, 2, 3], &ids, &sum_c),
+                    t.reveal_to(&[1, 2, 3], 1, &ids[..4]),
+                );
+                let (sum_1, sum_2, bits) = (sum_1?, sum_2?, bits?);
+                assert_eq!(sum_1, sum_2);
+
+                Ok::<_, UnexpectedError>((t.party(), bits))
+            });
+        }
+
+        while let Some(r) = js.join_next().await {
+            let (p, bits) = r.unwrap().unwrap();
+            if p == 1 {
+                assert_eq!(bits, Some(vec![true, true, true, false]));
+            } else {
+                assert_eq!(bits, None);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_eval_with_inputs() -> io::Result<()> {
+        let threshs = build_test_stack(3).await;
+
+        let mut js = JoinSet::new();
+
+        for (i, t) in threshs.into_iter().enumerate() {
+            js.spawn(async move {
+                let mut t = t;
+                t.init().await?;
+
+                let mut input = vec![false; 128];
+                input[i] = true;
+
+                let out_ids: Vec<_> = (0..128).collect();
+                let sum_c = sum_circuit::<FF2_128>(3);
+                t.setup(&input, &out_ids, &sum_c).await?;
+
+                // add the stored bits to 64 private bits of party 2 and 64 public bits
+                let inputs: Vec<_> = out_ids
+                    .iter()
+                    .map(|&id| EvalInput::Stored(id))
+                    .chain((0..64).map(|i| EvalInput::Private(2, i)))
+                    .chain((0..64).map(|i| EvalInput::Public(i % 4 == 0)))
+                    .collect();
+                let private: Vec<_> = if t.party() == 2 {
+                    (0..64).map(|i| i < 2).collect()
+                } else {
+                    Vec::new()
+                };
+
+                let sum_c = sum_circuit::<FF2_128>(2);
+                t.eval_with_inputs(&[1, 3, 2], &inputs, &private, &sum_c)
+                    .await
+            });
+        }
+
+        while let Some(r) = js.join_next().await {
+            let s = r.unwrap().unwrap();
+            let mut bits = vec![false; 128];
+            s.to_bits(&mut bits);
+            let expected: Vec<_> = (0..128)
+                .map(|i| (i < 3) ^ (i < 2) ^ (i >= 64 && i % 4 == 0))
+                .collect();
+            assert_eq!(bits, expected);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_dealer_setup() -> io::Result<()> {
+        let threshs = build_test_stack(3).await;
+
+        let key = FF2_128::new(0xdead, 0xbeef);
+
+        let mut js = JoinSet::new();
+
+        for t in threshs.into_iter() {
+            js.spawn(async move {
+                let mut t = t;
+                t.init().await?;
+
+                // party 1 imports a key it holds by itself
+                let copy_c = copy_circuit::<FF2_128>();
+                let input = if t.party() == 1 {
+                    let mut bits = vec![false; 128];
+                    key.to_bits(&mut bits);
+                    bits
+                } else {
+                    Vec::new()
+                };
+                let key_ids: Vec<_> = (0..128).collect();
+                t.setup_with_sizes(&input, &[128, 0, 0], &key_ids, &copy_c)
+                    .await?;
+
+                // parties 2 and 3 add in an element each, but party 1 has nothing more
+                let input = if t.party() == 1 {
+                    Vec::new()
+                } else {
+                    let mut bits = vec![false; 128];
+                    bits[t.party() as usize] = true;
+                    bits
+                };
+                let sum_ids: Vec<_> = (128..256).collect();
+                let sum_c = sum_circuit::<FF2_128>(2);
+                t.setup_with_sizes(&input, &[0, 128, 128], &sum_ids, &sum_c)
+                    .await?;
+
+                let ids: Vec<_> = (0..256).collect();
+                t.eval(&[1, 2, 3], &ids, &sum_c).await
+            });
+        }
+
+        while let Some(r) = js.join_next().await {
+            let s = r.unwrap().unwrap();
+            assert_eq!(s, key + FF2_128::new(0, 12));
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_delete() -> io::Result<()> {
+        let threshs = build_test_stack(3).await;
+
+        let mut js = JoinSet::new();
+
+        for (i, t) in threshs.into_iter().enumerate() {
+            js.spawn(async move {
+                let mut t = t;
+                t.init().await?;
+
+                let sum_c = sum_circuit::<FF2_128>(3);
+                let mut input = vec![false; 128];
+                input[i] = true;
+                input[64 + i] = true;
+                let first: Vec<_> = (0..128).collect();
+                t.setup(&input, &first, &sum_c).await?;
+                let second: Vec<_> = (128..256).collect();
+                t.setup(&input, &second, &sum_c).await?;
+
+                // nothing is deleted unless every party deletes the same inputs
+                let skip = (t.party_id != 1) as usize;
+                assert!(t.delete(&second[skip..]).await.is_err());
+                assert_eq!(t.input_tabit_idx.len(), 256);
+
+                // a whole batch goes without moving anything
+                t.delete(&second).await?;
+                assert_eq!(t.tabits.len(), 1);
+
+                // the rest of the element holding these is moved to a new batch
+                t.delete(&first[..64]).await?;
+                assert_eq!(t.tabits.len(), 1);
+                assert_eq!(t.tabits[0].nbits, 64);
+                assert!(t.delete(&first[..1]).await.is_err());
+                let ids: Vec<_> = (64..68).collect();
+                t.reveal_to(&[1, 2, 3], 1, &ids).await
+            });
+        }
+
+        while let Some(r) = js.join_next().await {
+            if let Some(bits) = r.unwrap().unwrap() {
+                assert_eq!(bits, vec![true, true, true, false]);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_refresh() -> io::Result<()> {
+        let threshs = build_test_stack(3).await;
+
+        let mut js = JoinSet::new();
+
+        for (i, t) in threshs.into_iter().enumerate() {
+            js.spawn(async move {
+                let mut t = t;
+                let store = Arc::new(MemoryStateStore::new());
+                t.set_state_store(store.clone(), "rst");
+                t.init().await?;
+
+                let mut input = vec![false; 128];
+                input[i] = true;
+                let out_ids: Vec<_> = (0..128).collect();
+                t.setup(&input, &out_ids, &sum_circuit::<FF2_128>(3))
+                    .await?;
+
+                let state_file = std::env::temp_dir().join(format!(
+                    "rst_thresh_refresh_{}_{i}.json",
+                    std::process::id()
+                ));
+                let state_file = state_file.to_str().unwrap();
+                t.write_state_to_file(state_file)?;
+
+                assert_eq!(t.epoch(), 0);
+                t.refresh().await?;
+                assert_eq!(t.epoch(), 1);
+
+                let id_c: TCircuit<FF2_128, FF2_128> = {
+                    let inputs = (0..128).collect();
+                    let gates = (0..128).map(|i| Gate::Xor(i, i, 128 + i)).collect();
+                    let outputs = (0..128).collect();
+                    TCircuit::from_parts(inputs, gates, outputs)
+                };
+                let parties: Vec<_> = (1..=3).collect();
+                let out = t.eval(&parties, &out_ids, &id_c).await?;
+
+                // the shares from before the refresh are rejected
+                assert!(t.resume_from_state_file(state_file, false).await.is_err());
+
+                // even after a restart, since the store remembers the epoch
+                let mut restarted =
+                    RstThreshPlayer::new(t.party_id, t.n, t.t, t.mpc.clone(), t.tabit.clone())
+                        .unwrap();
+                restarted.set_state_store(store, "rst");
+                assert!(restarted
+                    .resume_from_state_file(state_file, false)
+                    .await
+                    .is_err());
+                let _ = std::fs::remove_file(state_file);
+
+                Ok::<_, UnexpectedError>(out)
+            });
+        }
+
+        while let Some(r) = js.join_next().await {
+            let s = r.unwrap().unwrap();
+            assert_eq!(s, FF2_128::new(0, 7));
+        }
+
+        Ok(())
+    }
 }
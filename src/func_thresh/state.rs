@@ -0,0 +1,379 @@
+use crate::party::PartyId;
+
+use anyhow::{anyhow, bail, Context};
+use argon2::Argon2;
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305,
+};
+use rand::RngCore;
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::{Digest, Sha256};
+
+/// The bytes every binary state file starts with
+pub const STATE_MAGIC: [u8; 4] = *b"TMPC";
+
+/// The version of the binary state format with a checksummed plaintext body.
+/// Version 0 is the JSON state written before the binary format existed.
+pub const STATE_FORMAT_VERSION: u16 = 1;
+
+/// The version of the binary state format with a body encrypted under a `StateKey`
+pub const ENCRYPTED_STATE_FORMAT_VERSION: u16 = 2;
+
+// magic, version, party id, n, t, backend
+const PREFIX_SIZE: usize = 4 + 2 + 2 + 2 + 2 + 1;
+// prefix, checksum, body length
+const HEADER_SIZE: usize = PREFIX_SIZE + 32 + 8;
+// prefix, key derivation, salt, nonce, body length
+const ENCRYPTED_HEADER_SIZE: usize = PREFIX_SIZE + 1 + SALT_SIZE + NONCE_SIZE + 8;
+
+const SALT_SIZE: usize = 16;
+const NONCE_SIZE: usize = 12;
+
+/// The key used to encrypt and authenticate state files at rest
+#[derive(Clone)]
+pub enum StateKey {
+    /// An operator passphrase, stretched with Argon2id and a random salt for each file
+    Passphrase(String),
+    /// A 32-byte key, e.g. read from a key file
+    Key([u8; 32]),
+}
+
+impl StateKey {
+    pub fn from_passphrase(passphrase: &str) -> Self {
+        StateKey::Passphrase(passphrase.to_string())
+    }
+
+    /// Read a key file holding exactly 32 bytes
+    pub fn from_key_file(key_file: &str) -> anyhow::Result<Self> {
+        let bytes = std::fs::read(key_file).context("Failed to read key file")?;
+        let key = bytes
+            .try_into()
+            .map_err(|b: Vec<u8>| anyhow!("Key file must be 32 bytes, not {}", b.len()))?;
+        Ok(StateKey::Key(key))
+    }
+
+    fn kdf_id(&self) -> u8 {
+        match self {
+            StateKey::Key(_) => 0,
+            StateKey::Passphrase(_) => 1,
+        }
+    }
+
+    fn derive(&self, salt: &[u8]) -> anyhow::Result<[u8; 32]> {
+        match self {
+            StateKey::Key(k) => Ok(*k),
+            StateKey::Passphrase(p) => {
+                let mut key = [0; 32];
+                Argon2::default()
+                    .hash_password_into(p.as_bytes(), salt, &mut key)
+                    .map_err(|e| anyhow!("Failed to derive key from passphrase: {e}"))?;
+                Ok(key)
+            }
+        }
+    }
+}
+
+// never print the key material
+impl std::fmt::Debug for StateKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StateKey::Key(_) => write!(f, "StateKey::Key(..)"),
+            StateKey::Passphrase(_) => write!(f, "StateKey::Passphrase(..)"),
+        }
+    }
+}
+
+/// The threshold protocol that wrote a state file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateBackend {
+    Rst = 1,
+    Generic = 2,
+}
+
+impl TryFrom<u8> for StateBackend {
+    type Error = anyhow::Error;
+
+    fn try_from(b: u8) -> Result<Self, Self::Error> {
+        match b {
+            1 => Ok(StateBackend::Rst),
+            2 => Ok(StateBackend::Generic),
+            _ => Err(anyhow!("Unknown state backend {b}")),
+        }
+    }
+}
+
+/// The header of a binary state file, identifying who the state belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StateHeader {
+    pub party_id: PartyId,
+    pub n: u16,
+    pub t: u16,
+    pub backend: StateBackend,
+}
+
+impl StateHeader {
+    pub fn new(party_id: PartyId, n: u16, t: u16, backend: StateBackend) -> Self {
+        StateHeader {
+            party_id,
+            n,
+            t,
+            backend,
+        }
+    }
+
+    /// Check that a state file with this header was written by the same party and
+    /// configuration as `expected`
+    pub fn check(&self, expected: &StateHeader) -> anyhow::Result<()> {
+        if self.backend != expected.backend {
+            bail!(
+                "State file is for the {:?} backend, not {:?}",
+                self.backend,
+                expected.backend
+            );
+        }
+        if self.party_id != expected.party_id {
+            bail!(
+                "State file belongs to party {}, not {}",
+                self.party_id,
+                expected.party_id
+            );
+        }
+        if (self.n, self.t) != (expected.n, expected.t) {
+            bail!(
+                "State file is for (n, t) = ({}, {}), not ({}, {})",
+                self.n,
+                self.t,
+                expected.n,
+                expected.t
+            );
+        }
+        Ok(())
+    }
+
+    fn prefix(&self, version: u16) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(PREFIX_SIZE);
+        bytes.extend_from_slice(&STATE_MAGIC);
+        bytes.extend_from_slice(&version.to_le_bytes());
+        bytes.extend_from_slice(&self.party_id.to_le_bytes());
+        bytes.extend_from_slice(&self.n.to_le_bytes());
+        bytes.extend_from_slice(&self.t.to_le_bytes());
+        bytes.push(self.backend as u8);
+        bytes
+    }
+}
+
+/// Encode `state` as a binary state file, with all integers little-endian.
+/// Without a key this is the header, a SHA-256 checksum of the body and the length-prefixed
+/// body. With a key, the body is instead encrypted with ChaCha20-Poly1305 under a random
+/// nonce, authenticating the header as associated data.
+pub fn encode_state<S: Serialize>(
+    header: &StateHeader,
+    state: &S,
+    key: Option<&StateKey>,
+) -> anyhow::Result<Vec<u8>> {
+    let body = bincode::serialize(state).context("Failed to serialize state")?;
+
+    let Some(key) = key else {
+        let mut bytes = header.prefix(STATE_FORMAT_VERSION);
+        bytes.extend_from_slice(&Sha256::digest(&body));
+        bytes.extend_from_slice(&(body.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&body);
+        return Ok(bytes);
+    };
+
+    let mut salt = [0; SALT_SIZE];
+    let mut nonce = [0; NONCE_SIZE];
+    {
+        let mut rng = rand::thread_rng();
+        rng.fill_bytes(&mut salt);
+        rng.fill_bytes(&mut nonce);
+    }
+
+    let mut bytes = header.prefix(ENCRYPTED_STATE_FORMAT_VERSION);
+    bytes.push(key.kdf_id());
+    bytes.extend_from_slice(&salt);
+    bytes.extend_from_slice(&nonce);
+    // the tag is part of the ciphertext
+    bytes.extend_from_slice(&(body.len() as u64 + 16).to_le_bytes());
+
+    let cipher = ChaCha20Poly1305::new(&key.derive(&salt)?.into());
+    let ct = cipher
+        .encrypt(
+            &nonce.into(),
+            Payload {
+                msg: &body,
+                aad: &bytes,
+            },
+        )
+        .map_err(|_| anyhow!("Failed to encrypt state"))?;
+    bytes.extend_from_slice(&ct);
+
+    Ok(bytes)
+}
+
+/// Decode a state file written by `encode_state`, migrating older versions to the current
+/// state. Legacy JSON state files have no header, so `None` is returned in its place.
+/// If `key` is given the file must be encrypted under it, and otherwise it must not be.
+pub fn decode_state<S: DeserializeOwned>(
+    bytes: &[u8],
+    key: Option<&StateKey>,
+) -> anyhow::Result<(Option<StateHeader>, S)> {
+    if !bytes.starts_with(&STATE_MAGIC) {
+        if key.is_some() {
+            bail!("State file is not encrypted");
+        }
+        let state = serde_json::from_slice(bytes).context("Failed to parse legacy JSON state")?;
+        return Ok((None, state));
+    }
+    if bytes.len() < PREFIX_SIZE {
+        bail!("State file is truncated");
+    }
+
+    let u16_at = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+    let u64_at = |i: usize| u64::from_le_bytes(bytes[i..i + 8].try_into().unwrap());
+    let version = u16_at(4);
+    let header = StateHeader {
+        party_id: u16_at(6),
+        n: u16_at(8),
+        t: u16_at(10),
+        backend: bytes[12].try_into()?,
+    };
+
+    let header_size = match version {
+        STATE_FORMAT_VERSION => HEADER_SIZE,
+        ENCRYPTED_STATE_FORMAT_VERSION => ENCRYPTED_HEADER_SIZE,
+        _ => bail!("Unsupported state format version {version}"),
+    };
+    if bytes.len() < header_size {
+        bail!("State file is truncated");
+    }
+    let body_len = u64_at(header_size - 8);
+    let body = &bytes[header_size..];
+    if body.len() as u64 != body_len {
+        bail!("State body is {} bytes but expected {body_len}", body.len());
+    }
+
+    let state = match (version, key) {
+        (STATE_FORMAT_VERSION, None) => {
+            if Sha256::digest(body).as_slice() != &bytes[PREFIX_SIZE..PREFIX_SIZE + 32] {
+                bail!("State checksum does not match");
+            }
+            bincode::deserialize(body).context("Failed to deserialize state")?
+        }
+        (ENCRYPTED_STATE_FORMAT_VERSION, Some(key)) => {
+            let kdf_id = bytes[PREFIX_SIZE];
+            if kdf_id != key.kdf_id() {
+                bail!("State file was encrypted with a different kind of key");
+            }
+            let salt = &bytes[PREFIX_SIZE + 1..PREFIX_SIZE + 1 + SALT_SIZE];
+            let nonce = &bytes[PREFIX_SIZE + 1 + SALT_SIZE..header_size - 8];
+
+            let cipher = ChaCha20Poly1305::new(&key.derive(salt)?.into());
+            let pt = cipher
+                .decrypt(
+                    nonce.into(),
+                    Payload {
+                        msg: body,
+                        aad: &bytes[..header_size],
+                    },
+                )
+                .map_err(|_| anyhow!("Failed to decrypt state, wrong key or tampered file"))?;
+            bincode::deserialize(&pt).context("Failed to deserialize state")?
+        }
+        (STATE_FORMAT_VERSION, Some(_)) => bail!("State file is not encrypted"),
+        _ => bail!("State file is encrypted but no key was given"),
+    };
+
+    Ok((Some(header), state))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ff2_128::FF2_128,
+        field::{ConstInt, RandElement},
+    };
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct TestState {
+        x: Vec<FF2_128>,
+        #[serde(default)]
+        epoch: u64,
+    }
+
+    #[test]
+    fn test_state_encoding() {
+        let header = StateHeader::new(2, 5, 3, StateBackend::Generic);
+        let mut rng = rand::thread_rng();
+        let state = TestState {
+            x: (0..3).map(|_| FF2_128::rand(&mut rng)).collect(),
+            epoch: 4,
+        };
+
+        let bytes = encode_state(&header, &state, None).unwrap();
+        let (h, s): (_, TestState) = decode_state(&bytes, None).unwrap();
+        assert_eq!(h, Some(header));
+        assert_eq!(s, state);
+        assert!(h.unwrap().check(&header).is_ok());
+
+        let other = StateHeader::new(1, 5, 3, StateBackend::Generic);
+        assert!(h.unwrap().check(&other).is_err());
+
+        // flipping any bit of the body is caught by the checksum
+        let mut bad = bytes.clone();
+        *bad.last_mut().unwrap() ^= 1;
+        assert!(decode_state::<TestState>(&bad, None).is_err());
+        assert!(decode_state::<TestState>(&bytes[..bytes.len() - 1], None).is_err());
+
+        // legacy JSON files are still readable
+        let json = serde_json::to_string(&state.x).unwrap();
+        let json = format!(r#"{{"x":{json}}}"#);
+        let (h, s): (_, TestState) = decode_state(json.as_bytes(), None).unwrap();
+        assert_eq!(h, None);
+        assert_eq!(s.x, state.x);
+        assert_eq!(s.epoch, 0);
+    }
+
+    #[test]
+    fn test_encrypted_state() {
+        let header = StateHeader::new(2, 5, 3, StateBackend::Rst);
+        let mut rng = rand::thread_rng();
+        let state = TestState {
+            x: (0..3).map(|_| FF2_128::rand(&mut rng)).collect(),
+            epoch: 1,
+        };
+
+        for key in [StateKey::Key([7; 32]), StateKey::from_passphrase("hunter2")] {
+            let bytes = encode_state(&header, &state, Some(&key)).unwrap();
+            let (h, s): (_, TestState) = decode_state(&bytes, Some(&key)).unwrap();
+            assert_eq!(h, Some(header));
+            assert_eq!(s, state);
+
+            // the plaintext never appears in the file
+            let mut x0 = [0; 16];
+            state.x[0].to_bytes(&mut x0);
+            assert!(!bytes.windows(16).any(|w| w == x0));
+
+            // encrypted files need the key, and unencrypted files are refused with one
+            assert!(decode_state::<TestState>(&bytes, None).is_err());
+            let plain = encode_state(&header, &state, None).unwrap();
+            assert!(decode_state::<TestState>(&plain, Some(&key)).is_err());
+
+            // changing the party id in the header or the ciphertext is detected
+            let mut bad = bytes.clone();
+            bad[6] ^= 1;
+            assert!(decode_state::<TestState>(&bad, Some(&key)).is_err());
+            let mut bad = bytes.clone();
+            *bad.last_mut().unwrap() ^= 1;
+            assert!(decode_state::<TestState>(&bad, Some(&key)).is_err());
+        }
+
+        let bytes = encode_state(&header, &state, Some(&StateKey::Key([7; 32]))).unwrap();
+        assert!(decode_state::<TestState>(&bytes, Some(&StateKey::Key([8; 32]))).is_err());
+        let bytes = encode_state(&header, &state, Some(&StateKey::from_passphrase("a"))).unwrap();
+        assert!(decode_state::<TestState>(&bytes, Some(&StateKey::from_passphrase("b"))).is_err());
+    }
+}
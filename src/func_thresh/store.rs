@@ -0,0 +1,472 @@
+use crate::func_thresh::state::{decode_state, encode_state, StateHeader, StateKey};
+
+use std::{collections::HashMap, fs, path::PathBuf, sync::Mutex};
+
+use anyhow::{anyhow, bail, Context};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// A blob in a `StateStore` along with how many times its key has been written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionedBlob {
+    pub version: u64,
+    pub data: Vec<u8>,
+}
+
+/// Storage for the state of the thresh players as named blobs.
+/// Every `put` to a key increments its version, starting from 1.
+pub trait StateStore: std::fmt::Debug + Send + Sync {
+    /// Write `data` to `key`, returning its new version
+    fn put(&self, key: &str, data: &[u8]) -> anyhow::Result<u64>;
+
+    /// Read the latest version of `key`, if it exists
+    fn get(&self, key: &str) -> anyhow::Result<Option<VersionedBlob>>;
+
+    /// The keys starting with `prefix`, in sorted order
+    fn list(&self, prefix: &str) -> anyhow::Result<Vec<String>>;
+
+    /// Remove `key` if it exists
+    fn delete(&self, key: &str) -> anyhow::Result<()>;
+}
+
+/// Keeps all blobs in memory, for tests and short-lived deployments
+#[derive(Debug, Default)]
+pub struct MemoryStateStore {
+    blobs: Mutex<HashMap<String, VersionedBlob>>,
+}
+
+impl MemoryStateStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl StateStore for MemoryStateStore {
+    fn put(&self, key: &str, data: &[u8]) -> anyhow::Result<u64> {
+        let mut blobs = self.blobs.lock().unwrap();
+        let version = blobs.get(key).map_or(0, |b| b.version) + 1;
+        blobs.insert(
+            key.to_string(),
+            VersionedBlob {
+                version,
+                data: data.to_vec(),
+            },
+        );
+        Ok(version)
+    }
+
+    fn get(&self, key: &str) -> anyhow::Result<Option<VersionedBlob>> {
+        Ok(self.blobs.lock().unwrap().get(key).cloned())
+    }
+
+    fn list(&self, prefix: &str) -> anyhow::Result<Vec<String>> {
+        let blobs = self.blobs.lock().unwrap();
+        let mut keys: Vec<_> = blobs
+            .keys()
+            .filter(|k| k.starts_with(prefix))
+            .cloned()
+            .collect();
+        keys.sort();
+        Ok(keys)
+    }
+
+    fn delete(&self, key: &str) -> anyhow::Result<()> {
+        self.blobs.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
+
+/// Keeps each blob in its own file in a directory, prefixed by its 8-byte version.
+/// Writes go through a temporary file so a crash never leaves a partial blob behind.
+#[derive(Debug)]
+pub struct FileStateStore {
+    dir: PathBuf,
+}
+
+impl FileStateStore {
+    pub fn new(dir: &str) -> anyhow::Result<Self> {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {dir}"))?;
+        Ok(FileStateStore { dir: dir.into() })
+    }
+
+    fn path(&self, key: &str) -> anyhow::Result<PathBuf> {
+        let valid = |c: char| c.is_ascii_alphanumeric() || "-_.".contains(c);
+        if key.is_empty() || key.starts_with('.') || !key.chars().all(valid) {
+            bail!("Invalid state key {key:?}");
+        }
+        Ok(self.dir.join(key))
+    }
+}
+
+impl StateStore for FileStateStore {
+    fn put(&self, key: &str, data: &[u8]) -> anyhow::Result<u64> {
+        let path = self.path(key)?;
+        let version = self.get(key)?.map_or(0, |b| b.version) + 1;
+
+        let mut bytes = Vec::with_capacity(8 + data.len());
+        bytes.extend_from_slice(&version.to_le_bytes());
+        bytes.extend_from_slice(data);
+
+        // hidden, so it is never listed as a key
+        let tmp = self.dir.join(format!(".{key}.tmp"));
+        fs::write(&tmp, bytes).with_context(|| format!("Failed to write {tmp:?}"))?;
+        fs::rename(&tmp, &path).with_context(|| format!("Failed to write {path:?}"))?;
+
+        Ok(version)
+    }
+
+    fn get(&self, key: &str) -> anyhow::Result<Option<VersionedBlob>> {
+        let path = self.path(key)?;
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {path:?}")),
+        };
+        if bytes.len() < 8 {
+            bail!("{path:?} is truncated");
+        }
+
+        Ok(Some(VersionedBlob {
+            version: u64::from_le_bytes(bytes[..8].try_into().unwrap()),
+            data: bytes[8..].to_vec(),
+        }))
+    }
+
+    fn list(&self, prefix: &str) -> anyhow::Result<Vec<String>> {
+        let mut keys = Vec::new();
+        for entry in
+            fs::read_dir(&self.dir).with_context(|| format!("Failed to list {:?}", self.dir))?
+        {
+            let name = entry?.file_name();
+            let name = name.to_string_lossy();
+            if !name.starts_with('.') && name.starts_with(prefix) {
+                keys.push(name.into_owned());
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+
+    fn delete(&self, key: &str) -> anyhow::Result<()> {
+        let path = self.path(key)?;
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(e).with_context(|| format!("Failed to delete {path:?}"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Keeps all blobs in a single embedded SQLite database file
+#[cfg(feature = "sqlite")]
+#[derive(Debug)]
+pub struct SqliteStateStore {
+    conn: Mutex<rusqlite::Connection>,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteStateStore {
+    /// Open or create the database at `path`, where ":memory:" is a temporary database
+    pub fn new(path: &str) -> anyhow::Result<Self> {
+        let conn =
+            rusqlite::Connection::open(path).with_context(|| format!("Failed to open {path}"))?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS blobs (
+                key TEXT PRIMARY KEY,
+                version INTEGER NOT NULL,
+                data BLOB NOT NULL
+            )",
+            (),
+        )
+        .context("Failed to create blobs table")?;
+
+        Ok(SqliteStateStore {
+            conn: Mutex::new(conn),
+        })
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl StateStore for SqliteStateStore {
+    fn put(&self, key: &str, data: &[u8]) -> anyhow::Result<u64> {
+        let conn = self.conn.lock().unwrap();
+        let version: i64 = conn
+            .query_row(
+                "INSERT INTO blobs (key, version, data) VALUES (?1, 1, ?2)
+                 ON CONFLICT (key) DO UPDATE SET version = version + 1, data = excluded.data
+                 RETURNING version",
+                (key, data),
+                |row| row.get(0),
+            )
+            .with_context(|| format!("Failed to write {key}"))?;
+        Ok(version as u64)
+    }
+
+    fn get(&self, key: &str) -> anyhow::Result<Option<VersionedBlob>> {
+        use rusqlite::OptionalExtension;
+
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT version, data FROM blobs WHERE key = ?1",
+            (key,),
+            |row| {
+                Ok(VersionedBlob {
+                    version: row.get::<_, i64>(0)? as u64,
+                    data: row.get(1)?,
+                })
+            },
+        )
+        .optional()
+        .with_context(|| format!("Failed to read {key}"))
+    }
+
+    fn list(&self, prefix: &str) -> anyhow::Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT key FROM blobs WHERE substr(key, 1, length(?1)) = ?1 ORDER BY key")?;
+        let keys = stmt
+            .query_map((prefix,), |row| row.get(0))?
+            .collect::<Result<_, _>>()
+            .context("Failed to list keys")?;
+        Ok(keys)
+    }
+
+    fn delete(&self, key: &str) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM blobs WHERE key = ?1", (key,))
+            .with_context(|| format!("Failed to delete {key}"))?;
+        Ok(())
+    }
+}
+
+// The index of a player's records, holding the state that is not stored in batches
+// and the range of batches that hold the rest of it
+#[derive(Serialize, Deserialize)]
+struct RecordIndex<B> {
+    base: B,
+    first_batch: u64,
+    end_batch: u64,
+}
+
+/// How a thresh player persists its state to a `StateStore`: an index record holding the
+/// `base` of the state, e.g. the MAC key and epoch, and batches of shares that each
+/// `setup` or `sample` appends to. Every record is encoded with `encode_state`.
+pub(crate) struct StateRecords<'a> {
+    pub store: &'a dyn StateStore,
+    pub prefix: &'a str,
+    pub header: StateHeader,
+    pub key: Option<&'a StateKey>,
+}
+
+impl StateRecords<'_> {
+    fn index_key(&self) -> String {
+        format!("{}.index", self.prefix)
+    }
+
+    fn batch_prefix(&self) -> String {
+        format!("{}.batch-", self.prefix)
+    }
+
+    fn batch_key(&self, i: u64) -> String {
+        format!("{}{i:010}", self.batch_prefix())
+    }
+
+    fn epoch_key(&self) -> String {
+        format!("{}.epoch", self.prefix)
+    }
+
+    fn read<S: DeserializeOwned>(&self, key: &str) -> anyhow::Result<Option<S>> {
+        let Some(blob) = self.store.get(key)? else {
+            return Ok(None);
+        };
+        let (header, state) = decode_state(&blob.data, self.key)?;
+        header
+            .ok_or_else(|| anyhow!("{key} is missing its header"))?
+            .check(&self.header)?;
+        Ok(Some(state))
+    }
+
+    fn write<S: Serialize>(&self, key: &str, state: &S) -> anyhow::Result<()> {
+        let bytes = encode_state(&self.header, state, self.key)?;
+        self.store.put(key, &bytes)?;
+        Ok(())
+    }
+
+    /// Read the base of the state and all of its batches, if any state has been stored
+    pub fn load<B: DeserializeOwned, R: DeserializeOwned>(
+        &self,
+    ) -> anyhow::Result<Option<(B, Vec<R>)>> {
+        let Some(index) = self.read::<RecordIndex<B>>(&self.index_key())? else {
+            return Ok(None);
+        };
+        let batches = (index.first_batch..index.end_batch)
+            .map(|i| {
+                let key = self.batch_key(i);
+                self.read(&key)?.ok_or_else(|| anyhow!("{key} is missing"))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Some((index.base, batches)))
+    }
+
+    /// Add a batch to the stored state, updating its base
+    pub fn append<B: Serialize + DeserializeOwned, R: Serialize>(
+        &self,
+        base: &B,
+        batch: &R,
+    ) -> anyhow::Result<()> {
+        let (first_batch, end_batch) = match self.read::<RecordIndex<B>>(&self.index_key())? {
+            Some(index) => (index.first_batch, index.end_batch),
+            None => (0, 0),
+        };
+        // the batch is only part of the state once the index includes it
+        self.write(&self.batch_key(end_batch), batch)?;
+        self.write(
+            &self.index_key(),
+            &RecordIndex {
+                base,
+                first_batch,
+                end_batch: end_batch + 1,
+            },
+        )
+    }
+
+    /// Replace all of the stored state with `base` and a single `batch`. The old records are
+    /// never read, as they may have been written under another header, e.g. before a reshare.
+    pub fn replace<B: Serialize, R: Serialize>(&self, base: &B, batch: &R) -> anyhow::Result<()> {
+        let batch_prefix = self.batch_prefix();
+        let old = self.store.list(&batch_prefix)?;
+        let end_batch = old
+            .iter()
+            .filter_map(|key| key[batch_prefix.len()..].parse::<u64>().ok())
+            .max()
+            .map_or(0, |i| i + 1);
+
+        self.write(&self.batch_key(end_batch), batch)?;
+        self.write(
+            &self.index_key(),
+            &RecordIndex {
+                base,
+                first_batch: end_batch,
+                end_batch: end_batch + 1,
+            },
+        )?;
+
+        for key in old {
+            self.store.delete(&key)?;
+        }
+        Ok(())
+    }
+
+    /// The highest epoch the state has been persisted at, which is kept apart from the state
+    /// so that resuming from older state cannot roll it back
+    pub fn epoch(&self) -> anyhow::Result<u64> {
+        Ok(self.read(&self.epoch_key())?.unwrap_or(0))
+    }
+
+    /// Record that the state has moved to `epoch`
+    pub fn set_epoch(&self, epoch: u64) -> anyhow::Result<()> {
+        self.write(&self.epoch_key(), &epoch)
+    }
+
+    /// Delete all of the stored state
+    pub fn clear(&self) -> anyhow::Result<()> {
+        for key in self.store.list(&format!("{}.", self.prefix))? {
+            self.store.delete(&key)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::func_thresh::state::StateBackend;
+
+    fn check_store(store: &dyn StateStore) {
+        assert_eq!(store.get("a.x").unwrap(), None);
+        assert_eq!(store.put("a.x", b"one").unwrap(), 1);
+        assert_eq!(store.put("a.x", b"two").unwrap(), 2);
+        assert_eq!(store.put("a.y", b"").unwrap(), 1);
+        assert_eq!(store.put("b.x", b"three").unwrap(), 1);
+
+        let blob = store.get("a.x").unwrap().unwrap();
+        assert_eq!(blob.version, 2);
+        assert_eq!(blob.data, b"two");
+
+        assert_eq!(store.list("a.").unwrap(), ["a.x", "a.y"]);
+        assert_eq!(store.list("").unwrap().len(), 3);
+
+        store.delete("a.x").unwrap();
+        store.delete("a.x").unwrap();
+        assert_eq!(store.get("a.x").unwrap(), None);
+        assert_eq!(store.list("a.").unwrap(), ["a.y"]);
+    }
+
+    #[test]
+    fn test_state_stores() {
+        check_store(&MemoryStateStore::new());
+
+        let dir = std::env::temp_dir().join(format!("thresh_store_{}", rand::random::<u64>()));
+        let dir = dir.to_str().unwrap();
+        check_store(&FileStateStore::new(dir).unwrap());
+        assert!(FileStateStore::new(dir).unwrap().put("../x", b"").is_err());
+        let _ = fs::remove_dir_all(dir);
+
+        #[cfg(feature = "sqlite")]
+        check_store(&SqliteStateStore::new(":memory:").unwrap());
+    }
+
+    #[test]
+    fn test_state_records() {
+        let store = MemoryStateStore::new();
+        let key = StateKey::Key([1; 32]);
+        let records = StateRecords {
+            store: &store,
+            prefix: "p1",
+            header: StateHeader::new(1, 3, 2, StateBackend::Generic),
+            key: Some(&key),
+        };
+
+        assert!(records.load::<u64, Vec<u8>>().unwrap().is_none());
+
+        records.append(&7u64, &vec![1u8, 2]).unwrap();
+        records.append(&8u64, &vec![3u8]).unwrap();
+        let (base, batches) = records.load::<u64, Vec<u8>>().unwrap().unwrap();
+        assert_eq!(base, 8);
+        assert_eq!(batches, [vec![1, 2], vec![3]]);
+
+        assert_eq!(records.epoch().unwrap(), 0);
+        records.set_epoch(2).unwrap();
+
+        records.replace(&9u64, &vec![1u8, 2, 3]).unwrap();
+        let (base, batches) = records.load::<u64, Vec<u8>>().unwrap().unwrap();
+        assert_eq!(base, 9);
+        assert_eq!(batches, [vec![1, 2, 3]]);
+        // only the index, the one batch and the epoch remain
+        assert_eq!(store.list("p1.").unwrap().len(), 3);
+        assert_eq!(records.epoch().unwrap(), 2);
+
+        // another party cannot load these records
+        let other = StateRecords {
+            header: StateHeader::new(2, 3, 2, StateBackend::Generic),
+            ..records
+        };
+        assert!(other.load::<u64, Vec<u8>>().is_err());
+
+        // but they can be replaced after a reshare moves the party to a new committee
+        let reshared = StateRecords {
+            header: StateHeader::new(1, 4, 3, StateBackend::Generic),
+            ..records
+        };
+        reshared.set_epoch(3).unwrap();
+        reshared.replace(&10u64, &vec![4u8]).unwrap();
+        let (base, batches) = reshared.load::<u64, Vec<u8>>().unwrap().unwrap();
+        assert_eq!(base, 10);
+        assert_eq!(batches, [vec![4]]);
+        assert_eq!(store.list("p1.").unwrap().len(), 3);
+        assert!(records.load::<u64, Vec<u8>>().is_err());
+
+        other.clear().unwrap();
+        assert!(store.list("").unwrap().is_empty());
+    }
+}
@@ -1,9 +1,11 @@
 use crate::{
     auth_bits::{Abits, ThreshAbits},
     base_func::{BaseFunc, CheatOrUnexpectedError, FuncId, SessionId, UnexpectedError},
-    circuits::{CircuitCollection, CircuitElement},
-    common_protos::{broadcast_commit_open, open_abits, random_shares},
-    field::{Field, RandElement},
+    circuits::{elements::bit_basis, CircuitCollection, CircuitElement},
+    common_protos::{
+        broadcast_commit_open, open_abits_batched, open_abits_to, random_shares, random_zero_shares,
+    },
+    field::{BinaryField, Field, RandElement},
     func_abit::AsyncAbit,
     func_com::AsyncCom,
     func_mult::AsyncMult,
@@ -18,6 +20,7 @@ use log::trace;
 
 use std::{
     collections::HashMap,
+    future::Future,
     sync::{Arc, RwLock},
 };
 
@@ -50,27 +53,52 @@ impl<T, FA, FR, FM, FC, FN> BaseFunc for RstTabitPlayer<T, FA, FR, FM, FC, FN> {
 }
 
 /// Trait to represent threshold authenticated bit generation
-pub trait AsyncTabit<T> {
+pub trait AsyncTabit<T>: Send + Sync {
     /// Start a new instance with `sid`
-    async fn init(&self, sid: SessionId, delta: T) -> Result<(), UnexpectedError>;
+    fn init(
+        &self,
+        sid: SessionId,
+        delta: T,
+    ) -> impl Future<Output = Result<(), UnexpectedError>> + Send;
 
     /// Sample uniform threshold abits
-    async fn sample(
+    fn sample(
         &self,
         sid: SessionId,
         num_bits: usize,
-    ) -> Result<ThreshAbits<T>, CheatOrUnexpectedError>;
+    ) -> impl Future<Output = Result<ThreshAbits<T>, CheatOrUnexpectedError>> + Send;
 
     /// Reshare existing abits as thresh abits
-    async fn reshare(
+    fn reshare(
+        &self,
+        sid: SessionId,
+        abits: &Abits<T>,
+    ) -> impl Future<Output = Result<ThreshAbits<T>, CheatOrUnexpectedError>> + Send;
+
+    /// Re-randomize the shares of existing thresh abits without changing the bits,
+    /// so that shares from before the refresh are of no use combined with those after
+    fn refresh(
+        &self,
+        sid: SessionId,
+        tabits: &mut [ThreshAbits<T>],
+    ) -> impl Future<Output = Result<(), CheatOrUnexpectedError>> + Send;
+
+    /// Open abits shared among `parties` to `target` only, who checks the MACs of
+    /// every other party. Returns the bits to `target` and `None` to everyone else.
+    /// `sid` is the instance the abits were made under, and `open_sid` names the
+    /// opening itself; openings that run at the same time need distinct `open_sid`s.
+    fn open_to(
         &self,
         sid: SessionId,
+        open_sid: SessionId,
         abits: &Abits<T>,
-    ) -> Result<ThreshAbits<T>, CheatOrUnexpectedError>;
+        parties: &[PartyId],
+        target: PartyId,
+    ) -> impl Future<Output = Result<Option<Vec<bool>>, CheatOrUnexpectedError>> + Send;
 }
 
 impl<
-        T: Field + CircuitElement + RandElement + Copy,
+        T: BinaryField + CircuitElement + RandElement + Copy,
         FA: AsyncAbit<T>,
         FR: AsyncRand,
         FM: AsyncMult<T>,
@@ -108,24 +136,148 @@ impl<
         sid: SessionId,
         num_bits: usize,
     ) -> Result<ThreshAbits<T>, CheatOrUnexpectedError> {
-        // sample random secret shares
-        let num_els = (num_bits + T::BIT_SIZE - 1) / T::BIT_SIZE;
+        self.sample_shares(sid, num_bits, false).await
+    }
 
+    async fn reshare(
+        &self,
+        sid: SessionId,
+        abits: &Abits<T>,
+    ) -> Result<ThreshAbits<T>, CheatOrUnexpectedError> {
+        // resharing abits <x>
         let delta = { self.deltas.read().unwrap()[&sid].clone() };
+        let nbits = abits.len();
+        // sample random thresh abits <<r>>
+        let rs = self.sample(sid, nbits).await?;
+
+        // convert all thresh to abits
+        let all_bits: Vec<usize> = (0..nbits).collect();
 
         let parties: Vec<PartyId> = (1..(self.n + 1) as PartyId).collect();
         let party_points: Vec<_> = (1..self.n + 1).map(|p| T::from(p as u64)).collect();
+        let my_point = party_points[(self.party_id - 1) as usize];
 
-        let my_shares = random_shares::<T, FN>(
-            num_els,
-            self.party_id,
+        // convert <<r>> -> <r>
+        let mut r_a = rs.convert(&my_point, &party_points, &party_points, &all_bits);
+        // calculate and open masked (x+r) = <x> + <r>
+        r_a.add_assign(abits);
+
+        let ssid = sid.derive_ssid(FuncId::Ftabit);
+        let cs = open_abits_batched(
+            &r_a,
+            self.net.clone(),
+            self.rand.as_ref(),
+            &delta,
+            self.party_id.into(),
             &parties,
-            self.t.into(),
-            FuncId::Ftabit,
+            ssid,
+        )
+        .await
+        .with_context(|| self.err(sid, "Failed to open abits"))?;
+
+        // create threshold shares using the masked values, <<x>> = (x+r) + <<r>>
+        let mut tabits = rs;
+        tabits.add_consts(&cs, delta);
+
+        Ok(tabits)
+    }
+
+    async fn refresh(
+        &self,
+        sid: SessionId,
+        tabits: &mut [ThreshAbits<T>],
+    ) -> Result<(), CheatOrUnexpectedError> {
+        // a single batch of zero sharings covering every element of every set of tabits
+        let num_shares: usize = tabits.iter().map(|ts| ts.num_shares()).sum();
+        if num_shares == 0 {
+            return Ok(());
+        }
+        let mut zeros = self
+            .sample_shares(sid, num_shares * T::BIT_SIZE, true)
+            .await?;
+
+        for ts in tabits.iter_mut() {
+            let (zs, rest) = zeros.split(ts.num_shares() * T::BIT_SIZE);
+            ts.refresh(&zs);
+            zeros = rest;
+        }
+
+        Ok(())
+    }
+
+    async fn open_to(
+        &self,
+        sid: SessionId,
+        open_sid: SessionId,
+        abits: &Abits<T>,
+        parties: &[PartyId],
+        target: PartyId,
+    ) -> Result<Option<Vec<bool>>, CheatOrUnexpectedError> {
+        let delta = { self.deltas.read().unwrap()[&sid].clone() };
+
+        let bits = open_abits_to(
+            abits,
             self.net.clone(),
+            &delta,
+            self.party_id,
+            parties,
+            target,
+            open_sid.derive_ssid(FuncId::Ftabit),
         )
         .await
-        .with_context(|| self.err(sid, "Failed to create initial random shares [[r]]"))?;
+        .with_context(|| self.err(open_sid, format!("Failed to open abits to {target}")))?;
+
+        Ok(bits)
+    }
+}
+
+impl<
+        T: Field + CircuitElement + RandElement + Copy,
+        FA: AsyncAbit<T>,
+        FR: AsyncRand,
+        FM: AsyncMult<T>,
+        FC: AsyncCom,
+        FN: AsyncNet,
+    > RstTabitPlayer<T, FA, FR, FM, FC, FN>
+{
+    /// Sample thresh abits of uniform bits, or of all zero bits if `zero` is set
+    async fn sample_shares(
+        &self,
+        sid: SessionId,
+        num_bits: usize,
+        zero: bool,
+    ) -> Result<ThreshAbits<T>, CheatOrUnexpectedError> {
+        // sample random secret shares
+        let num_els = (num_bits + T::BIT_SIZE - 1) / T::BIT_SIZE;
+
+        let delta = { self.deltas.read().unwrap()[&sid].clone() };
+
+        let parties: Vec<PartyId> = (1..(self.n + 1) as PartyId).collect();
+        let party_points: Vec<_> = (1..self.n + 1).map(|p| T::from(p as u64)).collect();
+
+        let my_shares = if zero {
+            random_zero_shares::<T, FN>(
+                num_els,
+                self.party_id,
+                &parties,
+                self.t.into(),
+                FuncId::Ftabit,
+                self.net.clone(),
+            )
+            .await
+            .with_context(|| self.err(sid, "Failed to create random shares of zero [[0]]"))?
+        } else {
+            random_shares::<T, FN>(
+                num_els,
+                self.party_id,
+                &parties,
+                self.t.into(),
+                FuncId::Ftabit,
+                self.net.clone(),
+            )
+            .await
+            .with_context(|| self.err(sid, "Failed to create initial random shares [[r]]"))?
+        };
 
         // authenticate the bits of all of the shares
         let mut bits = vec![false; num_els * T::BIT_SIZE];
@@ -140,10 +292,10 @@ impl<
             .with_context(|| self.err(sid, "Failed to authenticate bit decomp of [[r]]"))?;
 
         // run check
-        // need num_els + n + 1 shared field elements
+        // need num_els + n + 2 shared field elements
         let rand_bytes = self
             .rand
-            .rand(ssid, (num_els + self.n + 1) * T::BYTES)
+            .rand(ssid, (num_els + self.n + 2) * T::BYTES)
             .await?;
         let els: Vec<_> = rand_bytes
             .chunks_exact(T::BYTES)
@@ -152,19 +304,15 @@ impl<
 
         let chis = &els[..num_els];
         let cs = &els[num_els..num_els + self.n];
-        let r = els.last().unwrap();
+        let r = &els[num_els + self.n];
+        let rho = els.last().unwrap();
 
         // Split into two sets, one of size t, and the
         let s1 = &party_points[..self.t];
         let s2_idx = std::cmp::min(self.t, self.n - self.t);
         let s2 = &party_points[s2_idx..];
 
-        let gen = T::gen();
-        let mut pows = Vec::with_capacity(T::BIT_SIZE);
-        pows.push(T::one());
-        for i in 1..T::BIT_SIZE {
-            pows.push(gen * pows[i - 1]);
-        }
+        let pows: Vec<T> = bit_basis();
 
         // row el, col party
         // calculate \macof{j}{\State_a^i}, \keyof{i}{\State_a^j}
@@ -196,10 +344,20 @@ impl<
             .map(|i| lagrange_poly(s2, i, |xj| *r - xj))
             .collect();
 
+        // for sharings of zero also check the first set interpolates to zero at 0,
+        // with the random rho keeping the two checks from cancelling out
+        let lps0: Vec<T> = if zero {
+            s1.iter()
+                .map(|i| *rho * lagrange_poly(s1, i, |xj| T::zero() - xj))
+                .collect()
+        } else {
+            vec![T::zero(); self.t]
+        };
+
         let f = |s: &T, i: usize| {
             let mut acc = T::zero();
             if i <= self.t {
-                acc += lps1[i - 1] * s;
+                acc += (lps1[i - 1] + lps0[i - 1]) * s;
             }
             if i > s2_idx {
                 acc += lps2[i - s2_idx - 1] * s;
@@ -304,47 +462,6 @@ impl<
 
         Ok(tabits)
     }
-
-    async fn reshare(
-        &self,
-        sid: SessionId,
-        abits: &Abits<T>,
-    ) -> Result<ThreshAbits<T>, CheatOrUnexpectedError> {
-        // resharing abits <x>
-        let delta = { self.deltas.read().unwrap()[&sid].clone() };
-        let nbits = abits.len();
-        // sample random thresh abits <<r>>
-        let rs = self.sample(sid, nbits).await?;
-
-        // convert all thresh to abits
-        let all_bits: Vec<usize> = (0..nbits).collect();
-
-        let parties: Vec<PartyId> = (1..(self.n + 1) as PartyId).collect();
-        let party_points: Vec<_> = (1..self.n + 1).map(|p| T::from(p as u64)).collect();
-        let my_point = party_points[(self.party_id - 1) as usize];
-
-        // convert <<r>> -> <r>
-        let mut r_a = rs.convert(&my_point, &party_points, &party_points, &all_bits);
-        // calculate and open masked (x+r) = <x> + <r>
-        r_a.add_assign(abits);
-
-        let cs = open_abits(
-            &r_a,
-            self.net.clone(),
-            &delta,
-            self.party_id.into(),
-            &parties,
-            FuncId::Ftabit,
-        )
-        .await
-        .with_context(|| self.err(sid, "Failed to open abits"))?;
-
-        // create threshold shares using the masked values, <<x>> = (x+r) + <<r>>
-        let mut tabits = rs;
-        tabits.add_consts(&cs, delta);
-
-        Ok(tabits)
-    }
 }
 
 impl<T: Field, FA, FR, FM, FC, FN> RstTabitPlayer<T, FA, FR, FM, FC, FN> {
@@ -681,4 +798,84 @@ pub mod tests {
 
         Ok(())
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_refresh_tabits() -> Result<(), ()> {
+        let n = 3;
+        let party_info = get_test_party_infos(n as PartyId);
+
+        let nets = build_test_nets(
+            &party_info,
+            vec![FuncId::Fcom, FuncId::Fcote, FuncId::Fmult, FuncId::Ftabit],
+        )
+        .await;
+        let abits = build_test_abits(&party_info);
+        let coms = build_test_coms(&nets);
+        let rands = build_test_rands(&coms);
+        let cotes = build_test_cotes(&nets, &party_info);
+        let mults = build_test_mults(&nets, &cotes);
+        let tabits = build_test_tabits(&abits, &rands, &mults, &coms, &nets);
+
+        let mut js = JoinSet::<Result<_, CheatOrUnexpectedError>>::new();
+        let nbits = 200;
+        for (i, tabit) in tabits.into_iter().enumerate() {
+            js.spawn(async move {
+                let delta = {
+                    let mut rng = rand::thread_rng();
+                    FF2_128::rand(&mut rng)
+                };
+                let sid = SessionId::new(FuncId::Ftest);
+                let _ = tabit.init(sid, delta).await?;
+                let first = tabit.sample(sid, nbits).await?;
+                let second = tabit.sample(sid, 10).await?;
+
+                let all_points: Vec<_> = (1..=3).map(|i| FF2_128::from(i)).collect();
+                let my_point = all_points[i];
+                let pair = &all_points[..2];
+                let bit_idx: Vec<usize> = (0..nbits).collect();
+
+                let mut refreshed = vec![first.clone(), second];
+                tabit.refresh(sid, &mut refreshed).await?;
+
+                // only the first two parties take part in the conversion
+                if i == 2 {
+                    return Ok((i + 1, delta, None));
+                }
+                let before = first.convert(&my_point, &all_points, pair, &bit_idx);
+                let after = refreshed[0].convert(&my_point, &all_points, pair, &bit_idx);
+
+                Ok((i + 1, delta, Some((before, after))))
+            });
+        }
+
+        let mut res: HashMap<usize, _> = HashMap::new();
+        while let Some(x) = js.join_next().await {
+            let (i, d, abits) = x.unwrap().unwrap();
+            res.insert(i, (d, abits));
+        }
+
+        let (d1, Some((b1, a1))) = &res[&1] else {
+            panic!("party 1 should have converted abits");
+        };
+        let (d2, Some((b2, a2))) = &res[&2] else {
+            panic!("party 2 should have converted abits");
+        };
+
+        for k in 0..nbits {
+            // the shares change, but the bits do not
+            assert_eq!(b1.bits[k] ^ b2.bits[k], a1.bits[k] ^ a2.bits[k]);
+
+            let c = |x: &Abits<_>, y: &Abits<_>, d: &FF2_128| {
+                let mut emac = y.keys[0][k];
+                if x.bits[k] {
+                    emac += d;
+                }
+                emac == x.macs[0][k]
+            };
+            assert!(c(a1, a2, d2));
+            assert!(c(a2, a1, d1));
+        }
+
+        Ok(())
+    }
 }
@@ -0,0 +1,540 @@
+use crate::{
+    auth_bits::{AuthShare, AuthTriple},
+    base_func::{BaseFunc, CheatOrUnexpectedError, FuncId, SessionId, UnexpectedError},
+    common_protos::broadcast_commit_open,
+    field::{Field, RandElement},
+    func_com::AsyncCom,
+    func_mult::AsyncMult,
+    func_rand::AsyncRand,
+    party::PartyId,
+};
+
+use anyhow::Context;
+use log::trace;
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+};
+
+#[derive(Debug)]
+pub struct SpdzTriplesPlayer<T, FM, FR, FC> {
+    party_id: PartyId,
+    n: usize,
+    mult: Arc<FM>,
+    rand: Arc<FR>,
+    com: Arc<FC>,
+    alphas: RwLock<HashMap<SessionId, T>>,
+    cur_open_sid: AtomicU64,
+}
+
+impl<T, FM, FR, FC> BaseFunc for SpdzTriplesPlayer<T, FM, FR, FC> {
+    const FUNC_ID: FuncId = FuncId::Ftriple;
+    const REQUIRED_FUNCS: &'static [FuncId] = &[FuncId::Fmult, FuncId::Frand, FuncId::Fcom];
+
+    fn party(&self) -> PartyId {
+        self.party_id
+    }
+}
+
+/// Trait to represent the generation of authenticated multiplication triples
+/// over a field T, where each party holds an additive share of the global MAC key
+pub trait AsyncTriples<T>: Send + Sync {
+    /// Start a new instance with `sid` using `alpha` as this party's share of the MAC key
+    fn init(
+        &self,
+        sid: SessionId,
+        alpha: T,
+    ) -> impl Future<Output = Result<(), UnexpectedError>> + Send;
+
+    /// Generate `num` authenticated triples ([a], [b], [ab])
+    fn triples(
+        &self,
+        sid: SessionId,
+        num: usize,
+    ) -> impl Future<Output = Result<Vec<AuthTriple<T>>, CheatOrUnexpectedError>> + Send;
+}
+
+impl<T: Field + RandElement + Copy, FM: AsyncMult<T>, FR: AsyncRand, FC: AsyncCom> AsyncTriples<T>
+    for SpdzTriplesPlayer<T, FM, FR, FC>
+{
+    async fn init(&self, sid: SessionId, alpha: T) -> Result<(), UnexpectedError> {
+        {
+            let mut alphas = self.alphas.write().unwrap();
+            alphas.insert(sid, alpha);
+        }
+
+        let ssid = sid.derive_ssid(FuncId::Ftriple);
+
+        trace!("{}: init ({sid}) triples", self.party_id);
+        // one Fmult session for the products and one for their MACs
+        for mult_sid in [ssid, ssid.next()] {
+            self.mult
+                .init(mult_sid)
+                .await
+                .with_context(|| self.err(sid, "Failed to initialize Fmult"))?;
+        }
+        self.rand
+            .init(ssid)
+            .await
+            .with_context(|| self.err(sid, "Failed to initialize Frand"))?;
+
+        Ok(())
+    }
+
+    async fn triples(
+        &self,
+        sid: SessionId,
+        num: usize,
+    ) -> Result<Vec<AuthTriple<T>>, CheatOrUnexpectedError> {
+        let alpha = { self.alphas.read().unwrap()[&sid] };
+        // Fmult and Frand stay on the sessions set up in init, whose state moves on with each
+        // call, while every call opens its values in Fcom sessions of its own
+        let ssid = sid.derive_ssid(FuncId::Ftriple);
+        let open_sid = self.next_open_sid();
+
+        // sample shares of a, b and a second a' to sacrifice against
+        let (a, a2, b): (Vec<T>, Vec<T>, Vec<T>) = {
+            let mut rng = rand::thread_rng();
+            let mut f = || (0..num).map(|_| T::rand(&mut rng)).collect();
+            (f(), f(), f())
+        };
+
+        // c = ab, c' = a'b
+        let cs = self
+            .mult
+            .mult_many(ssid, [&a[..], &a2].concat(), [&b[..], &b].concat())
+            .await
+            .with_context(|| self.err(sid, "Failed to multiply [a], [b]"))?;
+
+        // authenticate everything with the MAC key in one batch
+        let vals = [&a[..], &b, &cs[..num], &a2, &cs[num..]].concat();
+        let macs = self
+            .mult
+            .mult_many(ssid.next(), vec![alpha; vals.len()], vals.clone())
+            .await
+            .with_context(|| self.err(sid, "Failed to authenticate shares"))?;
+        self.check_key(sid, ssid, open_sid, &alpha, &vals, &macs)
+            .await?;
+
+        let mut shares: Vec<AuthShare<T>> = vals
+            .into_iter()
+            .zip(macs)
+            .map(|(share, mac)| AuthShare { share, mac })
+            .collect();
+
+        // layout is a || b || c || a' || c'
+        let c2s = shares.split_off(4 * num);
+        let a2s = shares.split_off(3 * num);
+        let cs = shares.split_off(2 * num);
+        let bs = shares.split_off(num);
+        let as_ = shares;
+
+        // sacrifice (a', b, c') to check (a, b, c)
+        let r = {
+            let r_bytes = self.rand.rand(ssid, T::BYTES).await?;
+            T::from_bytes(&r_bytes)
+        };
+
+        // open rho = r[a] - [a']
+        let rhos: Vec<AuthShare<T>> = as_
+            .iter()
+            .zip(a2s.iter())
+            .map(|(a, a2)| {
+                let mut rho = *a;
+                rho.scale(&r);
+                rho.sub_assign(a2);
+                rho
+            })
+            .collect();
+        let rho_vals = self.open(sid, open_sid.next(), &rhos).await?;
+
+        // sigma = r[c] - [c'] - rho[b] should be a sharing of zero
+        let sigmas: Vec<AuthShare<T>> = cs
+            .iter()
+            .zip(c2s.iter())
+            .zip(bs.iter().zip(rho_vals.iter()))
+            .map(|((c, c2), (b, rho))| {
+                let mut sigma = *c;
+                sigma.scale(&r);
+                sigma.sub_assign(c2);
+                let mut rb = *b;
+                rb.scale(rho);
+                sigma.sub_assign(&rb);
+                sigma
+            })
+            .collect();
+        let sigma_vals = self.open(sid, open_sid.next().next(), &sigmas).await?;
+
+        let opened: Vec<_> = rhos.into_iter().chain(sigmas).collect();
+        let opened_vals: Vec<_> = rho_vals
+            .into_iter()
+            .chain(sigma_vals.iter().copied())
+            .collect();
+        self.check_macs(
+            sid,
+            ssid,
+            open_sid.next().next().next(),
+            &alpha,
+            &opened,
+            &opened_vals,
+        )
+        .await?;
+
+        if let Some(k) = sigma_vals.iter().position(|s| !s.is_zero()) {
+            return Err(self
+                .cheat(sid, None, format!("Sacrifice of triple {k} failed"))
+                .into());
+        }
+
+        let triples = as_
+            .into_iter()
+            .zip(bs)
+            .zip(cs)
+            .map(|((a, b), c)| AuthTriple { a, b, c })
+            .collect();
+
+        Ok(triples)
+    }
+}
+
+impl<T: Field + RandElement + Copy, FM: AsyncMult<T>, FR: AsyncRand, FC: AsyncCom>
+    SpdzTriplesPlayer<T, FM, FR, FC>
+{
+    pub fn new(
+        party_id: PartyId,
+        n: PartyId,
+        mult: Arc<FM>,
+        rand: Arc<FR>,
+        com: Arc<FC>,
+    ) -> Result<Self, ()> {
+        Ok(SpdzTriplesPlayer {
+            party_id,
+            n: n.into(),
+            mult,
+            rand,
+            com,
+            alphas: RwLock::new(HashMap::new()),
+            cur_open_sid: AtomicU64::new(0),
+        })
+    }
+
+    fn next_open_sid(&self) -> SessionId {
+        let id = self.cur_open_sid.fetch_add(1, Ordering::SeqCst);
+        SessionId {
+            parent: FuncId::Ftriple,
+            id,
+        }
+        .derive_ssid(FuncId::Ftriple)
+    }
+
+    /// Check that every one of `macs` was made with the same share of the MAC key: each party
+    /// supplies its key share to Fmult once per value, so a corrupt party could use a different
+    /// one each time. A random combination of the MACs must match one more product of the key
+    /// with the same combination of the `vals`, which a party that varied its key share cannot
+    /// arrange without knowing the values.
+    async fn check_key(
+        &self,
+        sid: SessionId,
+        ssid: SessionId,
+        open_sid: SessionId,
+        alpha: &T,
+        vals: &[T],
+        macs: &[T],
+    ) -> Result<(), CheatOrUnexpectedError> {
+        let chi: Vec<T> = self
+            .rand
+            .rand(ssid, vals.len() * T::BYTES)
+            .await?
+            .chunks_exact(T::BYTES)
+            .map(T::from_bytes)
+            .collect();
+
+        let y: T = chi.iter().zip(vals.iter()).map(|(c, x)| *c * x).sum();
+        let m: T = chi.iter().zip(macs.iter()).map(|(c, m)| *c * m).sum();
+        let ay = self
+            .mult
+            .mult(ssid.next(), *alpha, y)
+            .await
+            .with_context(|| self.err(sid, "Failed to authenticate the key check"))?;
+
+        let mut d_bytes = vec![0; T::BYTES];
+        (m - ay).to_bytes(&mut d_bytes);
+
+        let parties: Vec<PartyId> = (1..=self.n as PartyId).collect();
+        let others = broadcast_commit_open(
+            open_sid,
+            &d_bytes,
+            self.party_id,
+            &parties,
+            self.com.clone(),
+        )
+        .await
+        .with_context(|| self.err(sid, "Failed to open key check values"))?;
+
+        let test: T = m - ay + others.iter().map(|o| T::from_bytes(o)).sum::<T>();
+
+        if !test.is_zero() {
+            return Err(self
+                .cheat(
+                    sid,
+                    None,
+                    "Shares authenticated under inconsistent MAC keys".into(),
+                )
+                .into());
+        }
+
+        Ok(())
+    }
+
+    /// Open the values of the authenticated shares without checking the MACs
+    async fn open(
+        &self,
+        sid: SessionId,
+        ssid: SessionId,
+        vals: &[AuthShare<T>],
+    ) -> Result<Vec<T>, CheatOrUnexpectedError> {
+        let mut bytes = vec![0; vals.len() * T::BYTES];
+        bytes
+            .chunks_exact_mut(T::BYTES)
+            .zip(vals.iter())
+            .for_each(|(c, v)| {
+                v.share.to_bytes(c);
+            });
+
+        let parties: Vec<PartyId> = (1..=self.n as PartyId).collect();
+        let others = broadcast_commit_open(ssid, &bytes, self.party_id, &parties, self.com.clone())
+            .await
+            .with_context(|| self.err(sid, "Failed to open shares"))?;
+
+        let mut opened: Vec<T> = vals.iter().map(|v| v.share).collect();
+        for o in others.iter() {
+            o.chunks_exact(T::BYTES)
+                .zip(opened.iter_mut())
+                .for_each(|(c, x)| *x += T::from_bytes(c));
+        }
+
+        Ok(opened)
+    }
+
+    /// Check that the MACs of the opened values are consistent with the global key
+    /// by checking a random linear combination, drawn from the Frand instance `ssid`,
+    /// and opened in the Fcom session `open_sid`
+    async fn check_macs(
+        &self,
+        sid: SessionId,
+        ssid: SessionId,
+        open_sid: SessionId,
+        alpha: &T,
+        vals: &[AuthShare<T>],
+        opened: &[T],
+    ) -> Result<(), CheatOrUnexpectedError> {
+        let chi_bytes = self.rand.rand(ssid, vals.len() * T::BYTES).await?;
+
+        // d_i = \sum_k chi_k (m_{k,i} - \alpha_i x_k)
+        let d: T = chi_bytes
+            .chunks_exact(T::BYTES)
+            .zip(vals.iter().zip(opened.iter()))
+            .map(|(c, (v, x))| T::from_bytes(c) * (v.mac - *alpha * x))
+            .sum();
+
+        let mut d_bytes = vec![0; T::BYTES];
+        d.to_bytes(&mut d_bytes);
+
+        let parties: Vec<PartyId> = (1..=self.n as PartyId).collect();
+        let others = broadcast_commit_open(
+            open_sid,
+            &d_bytes,
+            self.party_id,
+            &parties,
+            self.com.clone(),
+        )
+        .await
+        .with_context(|| self.err(sid, "Failed to open MAC check values"))?;
+
+        let test: T = d + others.iter().map(|o| T::from_bytes(o)).sum::<T>();
+
+        if !test.is_zero() {
+            return Err(self
+                .cheat(sid, None, "MAC check on opened values failed".into())
+                .into());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{
+        ff2_128::FF2_128,
+        field::ConstInt,
+        func_com::tests::build_test_coms,
+        func_cote::tests::build_test_cotes,
+        func_mult::tests::{build_ideal_mults, build_test_mults},
+        func_net::tests::{build_test_nets, get_test_party_infos},
+        func_net::AsyncNet,
+        func_rand::tests::build_test_rands,
+    };
+
+    use tokio::task::JoinSet;
+
+    pub fn build_test_triples<FM: AsyncMult<FF2_128>, FR: AsyncRand, FC: AsyncCom>(
+        mults: &[Arc<FM>],
+        rands: &[Arc<FR>],
+        coms: &[Arc<FC>],
+    ) -> Vec<Arc<SpdzTriplesPlayer<FF2_128, FM, FR, FC>>> {
+        let num = mults.len();
+        (1..=num)
+            .map(|i| {
+                Arc::new(
+                    SpdzTriplesPlayer::new(
+                        i as PartyId,
+                        num as PartyId,
+                        mults[i - 1].clone(),
+                        rands[i - 1].clone(),
+                        coms[i - 1].clone(),
+                    )
+                    .unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    /// Generate `num` triples with each of `mults` and an Fcom over `nets`,
+    /// returning each party's result
+    async fn run_triples<FN: AsyncNet + 'static, FM: AsyncMult<FF2_128> + 'static>(
+        nets: &[Arc<FN>],
+        mults: Vec<Arc<FM>>,
+        num: usize,
+    ) -> Vec<Result<(FF2_128, Vec<AuthTriple<FF2_128>>), CheatOrUnexpectedError>> {
+        let coms = build_test_coms(&nets);
+        let rands = build_test_rands(&coms);
+        let triples = build_test_triples(&mults, &rands, &coms);
+
+        let mut js = JoinSet::new();
+        for (i, triple) in triples.into_iter().enumerate() {
+            js.spawn(async move {
+                let alpha = {
+                    let mut rng = rand::thread_rng();
+                    FF2_128::rand(&mut rng)
+                };
+                let sid = SessionId::new(FuncId::Ftest);
+                triple.init(sid, alpha).await?;
+                // a second call must not reuse the sessions of the first
+                triple.triples(sid, num).await?;
+                let ts = triple.triples(sid, num).await?;
+                Ok((i, alpha, ts))
+            });
+        }
+
+        let mut results: Vec<_> = Vec::new();
+        while let Some(r) = js.join_next().await {
+            results.push(r.unwrap());
+        }
+        results.sort_by_key(|r: &Result<_, CheatOrUnexpectedError>| {
+            r.as_ref().map_or(0, |(i, _, _)| *i)
+        });
+        results
+            .into_iter()
+            .map(|r| r.map(|(_, alpha, ts)| (alpha, ts)))
+            .collect()
+    }
+
+    fn check_triples(results: Vec<(FF2_128, Vec<AuthTriple<FF2_128>>)>, num: usize) {
+        let mut alpha = FF2_128::zero();
+        let mut acc = vec![
+            AuthTriple::<FF2_128> {
+                a: AuthShare {
+                    share: FF2_128::zero(),
+                    mac: FF2_128::zero(),
+                },
+                b: AuthShare {
+                    share: FF2_128::zero(),
+                    mac: FF2_128::zero(),
+                },
+                c: AuthShare {
+                    share: FF2_128::zero(),
+                    mac: FF2_128::zero(),
+                },
+            };
+            num
+        ];
+
+        for (a_i, ts) in results {
+            assert_eq!(ts.len(), num);
+            alpha += a_i;
+            acc.iter_mut().zip(ts.iter()).for_each(|(x, t)| {
+                x.a.add_assign(&t.a);
+                x.b.add_assign(&t.b);
+                x.c.add_assign(&t.c);
+            });
+        }
+
+        for t in acc.iter() {
+            assert_eq!(t.a.share * t.b.share, t.c.share);
+            assert_eq!(alpha * t.a.share, t.a.mac);
+            assert_eq!(alpha * t.b.share, t.b.mac);
+            assert_eq!(alpha * t.c.share, t.c.mac);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_triples() {
+        let n = 3;
+        let num = 10;
+        let party_info = get_test_party_infos(n);
+        let nets = build_test_nets(
+            &party_info,
+            vec![FuncId::Fcom, FuncId::Fcote, FuncId::Fmult],
+        )
+        .await;
+        let cotes = build_test_cotes(&nets, &party_info);
+        let mults = build_test_mults(&nets, &cotes);
+
+        let results = run_triples(&nets, mults, num).await;
+        check_triples(results.into_iter().map(|r| r.unwrap()).collect(), num);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_triples_ideal() {
+        let num = 10;
+        let nets = build_test_nets(&get_test_party_infos(3), vec![FuncId::Fcom]).await;
+        let mults = build_ideal_mults(3).into_iter().map(Arc::new).collect();
+
+        let results = run_triples(&nets, mults, num).await;
+        check_triples(results.into_iter().map(|r| r.unwrap()).collect(), num);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_triples_inconsistent_key() {
+        let nets = build_test_nets(&get_test_party_infos(3), vec![FuncId::Fcom]).await;
+        let mut mults = build_ideal_mults::<FF2_128>(3);
+
+        // party 2 shifts its key share for the MAC of one value
+        let mac_sid = SessionId::new(FuncId::Ftest)
+            .derive_ssid(FuncId::Ftriple)
+            .next();
+        mults[1].tamper = Some(Box::new(move |sid, alphas: &mut [FF2_128]| {
+            if sid == mac_sid && alphas.len() > 1 {
+                alphas[3] += FF2_128::one();
+            }
+        }));
+
+        let mults = mults.into_iter().map(Arc::new).collect();
+        let results = run_triples(&nets, mults, 10).await;
+        for r in results {
+            match r {
+                Err(CheatOrUnexpectedError::CheatDetected(e)) => {
+                    assert!(e.to_string().contains("inconsistent MAC keys"));
+                }
+                r => panic!("expected the key check to fail, got {:?}", r.map(|_| ())),
+            }
+        }
+    }
+}
@@ -26,14 +26,19 @@ extern crate tokio;
 mod ffi;
 
 pub mod auth_bits;
+pub mod block;
 pub mod circuits;
 pub mod ff2_128;
+pub mod ff2_64;
+pub mod ff2_8;
 pub mod field;
+pub mod fp127;
 pub mod multibuf;
 pub mod party;
 pub mod polynomial;
 pub mod rr2_128;
 pub mod utils;
+pub mod z2k;
 
 pub mod base_func;
 pub mod func_abit;
@@ -45,5 +50,6 @@ pub mod func_net;
 pub mod func_rand;
 pub mod func_thresh;
 pub mod func_thresh_abit;
+pub mod func_triples;
 
 pub mod common_protos;
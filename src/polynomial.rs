@@ -1,12 +1,17 @@
-use crate::field::{ConstInt, Field, RandElement, Ring};
+use crate::{
+    field::{ConstInt, Field, RandElement, Ring},
+    party::PartyId,
+};
 use rand::Rng;
+use std::ops::{Add, Mul, Sub};
 
 pub trait Polynomial<T: Ring> {
     fn evaluate(&self, p: &T) -> T;
     fn eval_zero(&self) -> T;
 }
 
-#[derive(Clone)]
+/// A polynomial given by its coefficients, lowest degree first
+#[derive(Clone, Debug)]
 pub struct FixedPolynomial<T> {
     coefficients: Vec<T>,
 }
@@ -15,6 +20,166 @@ impl<T: Ring> FixedPolynomial<T> {
     pub fn new(coef: Vec<T>) -> Self {
         FixedPolynomial { coefficients: coef }
     }
+
+    pub fn coefficients(&self) -> &[T] {
+        &self.coefficients
+    }
+
+    /// The degree of the polynomial ignoring leading zero coefficients,
+    /// or None for the zero polynomial
+    pub fn degree(&self) -> Option<usize> {
+        self.coefficients.iter().rposition(|c| !c.is_zero())
+    }
+
+    /// Evaluate the polynomial at each of `points`
+    pub fn evaluate_many(&self, points: &[T]) -> Vec<T> {
+        points.iter().map(|p| self.evaluate(p)).collect()
+    }
+
+    /// Drop leading zero coefficients
+    fn trim(mut self) -> Self {
+        let len = self.degree().map_or(0, |d| d + 1);
+        self.coefficients.truncate(len);
+        self
+    }
+
+    /// Combine the coefficients pairwise, treating missing coefficients as zero
+    fn zip_with<F: Fn(&mut T, &T)>(&self, other: &Self, f: F) -> Self {
+        let len = std::cmp::max(self.coefficients.len(), other.coefficients.len());
+        let mut coefficients = self.coefficients.clone();
+        coefficients.resize(len, T::zero());
+        coefficients
+            .iter_mut()
+            .zip(other.coefficients.iter())
+            .for_each(|(c, o)| f(c, o));
+
+        FixedPolynomial { coefficients }.trim()
+    }
+}
+
+impl<T: Ring> Add for &FixedPolynomial<T> {
+    type Output = FixedPolynomial<T>;
+    fn add(self, other: Self) -> FixedPolynomial<T> {
+        self.zip_with(other, |c, o| *c += o)
+    }
+}
+
+impl<T: Ring> Sub for &FixedPolynomial<T> {
+    type Output = FixedPolynomial<T>;
+    fn sub(self, other: Self) -> FixedPolynomial<T> {
+        self.zip_with(other, |c, o| *c -= o)
+    }
+}
+
+impl<T: Ring> Mul for &FixedPolynomial<T> {
+    type Output = FixedPolynomial<T>;
+    fn mul(self, other: Self) -> FixedPolynomial<T> {
+        let (a, b) = (&self.coefficients, &other.coefficients);
+        if a.is_empty() || b.is_empty() {
+            return FixedPolynomial::new(Vec::new());
+        }
+
+        let mut coefficients = vec![T::zero(); a.len() + b.len() - 1];
+        for (i, x) in a.iter().enumerate() {
+            for (j, y) in b.iter().enumerate() {
+                coefficients[i + j] += x.clone() * y;
+            }
+        }
+
+        FixedPolynomial { coefficients }.trim()
+    }
+}
+
+impl<T: Ring> PartialEq for FixedPolynomial<T> {
+    /// Polynomials are equal if they agree on all coefficients up to leading zeros
+    fn eq(&self, other: &Self) -> bool {
+        (self - other).degree().is_none()
+    }
+}
+
+impl<T: Field + Copy> FixedPolynomial<T> {
+    /// Division with remainder, returns (q, r) with self = q * divisor + r and deg r < deg divisor.
+    /// Returns None if `divisor` is the zero polynomial
+    pub fn divrem(&self, divisor: &Self) -> Option<(Self, Self)> {
+        let d = divisor.degree()?;
+        let lead_inv = divisor.coefficients[d].inv().unwrap();
+
+        let mut r = self.clone().trim().coefficients;
+        if r.len() <= d {
+            return Some((FixedPolynomial::new(Vec::new()), FixedPolynomial::new(r)));
+        }
+
+        let mut q = vec![T::zero(); r.len() - d];
+        for k in (0..q.len()).rev() {
+            // cancel the coefficient of x^{k + d}
+            let c = r[k + d] * lead_inv;
+            q[k] = c;
+            for (rc, dc) in r[k..k + d + 1]
+                .iter_mut()
+                .zip(divisor.coefficients[..d + 1].iter())
+            {
+                *rc -= c * dc;
+            }
+        }
+        r.truncate(d);
+
+        Some((
+            FixedPolynomial { coefficients: q }.trim(),
+            FixedPolynomial { coefficients: r }.trim(),
+        ))
+    }
+
+    /// The unique polynomial of degree < points.len() with p(points[i]) = vals[i]
+    pub fn interpolate(points: &[T], vals: &[T]) -> Result<Self, &'static str> {
+        if points.len() != vals.len() {
+            return Err("vectors must have the same length");
+        }
+        if points
+            .iter()
+            .enumerate()
+            .any(|(i, x)| points[i + 1..].contains(x))
+        {
+            return Err("points must be distinct");
+        }
+        let n = points.len();
+
+        // m(x) = \prod_j (x - x_j)
+        let mut m = vec![T::zero(); n + 1];
+        m[0] = T::one();
+        for (k, xj) in points.iter().enumerate() {
+            for i in (1..k + 2).rev() {
+                let prev = m[i - 1];
+                m[i] = prev - m[i] * xj;
+            }
+            m[0] = T::zero() - m[0] * xj;
+        }
+
+        // denominators \prod_{j != i} (x_i - x_j)
+        let dens: Vec<T> = points
+            .iter()
+            .map(|xi| {
+                points
+                    .iter()
+                    .filter(|xj| *xj != xi)
+                    .map(|xj| *xi - xj)
+                    .product()
+            })
+            .collect();
+        let dens = T::batch_inv(&dens).unwrap();
+
+        let mut coefficients = vec![T::zero(); n];
+        for ((xi, yi), di) in points.iter().zip(vals.iter()).zip(dens.iter()) {
+            // m(x) / (x - x_i) by synthetic division, highest degree first
+            let scale = *yi * di;
+            let mut carry = T::zero();
+            for k in (0..n).rev() {
+                carry = m[k + 1] + carry * xi;
+                coefficients[k] += carry * scale;
+            }
+        }
+
+        Ok(FixedPolynomial { coefficients }.trim())
+    }
 }
 
 impl<T: RandElement> FixedPolynomial<T> {
@@ -39,7 +204,7 @@ impl<T: Ring> Polynomial<T> for FixedPolynomial<T> {
     }
 
     fn eval_zero(&self) -> T {
-        return self.coefficients[0].clone();
+        self.coefficients.first().cloned().unwrap_or(T::zero())
     }
 }
 
@@ -91,6 +256,51 @@ impl<T: Ring + RandElement> InterpolationPolynomial<T> {
     }
 }
 
+impl<T: Field + Copy + RandElement> InterpolationPolynomial<T> {
+    /// Packed Shamir sharing of the k `secrets`, placed at `secret_points`, in a single
+    /// polynomial of degree t + k - 1. Any t - 1 shares are independent of the secrets,
+    /// and t + k shares are needed to reconstruct them.
+    pub fn packed_secret_share<R: Rng>(
+        rng: &mut R,
+        secrets: &[T],
+        t: usize,
+        secret_points: &[T],
+        points: &[T],
+    ) -> Self {
+        let k = secrets.len();
+        assert!(k > 0 && secret_points.len() == k);
+        assert!(points.len() >= t + k);
+        assert!(points.iter().all(|p| !secret_points.contains(p)));
+
+        // f = l + z * r, where l passes through the secrets and z vanishes at all
+        // of the secret points, so f is uniform among the polynomials through the secrets
+        let l = FixedPolynomial::interpolate(secret_points, secrets).unwrap();
+        let z = secret_points
+            .iter()
+            .fold(FixedPolynomial::new(vec![T::one()]), |acc, x| {
+                &acc * &FixedPolynomial::new(vec![T::zero() - x, T::one()])
+            });
+        let r = FixedPolynomial::rand_polynomial(rng, t - 1);
+        let f = &l + &(&z * &r);
+
+        InterpolationPolynomial {
+            points: points.to_vec(),
+            vals: f.evaluate_many(points),
+        }
+    }
+}
+
+/// The points at which k packed secrets are placed for a sharing among `parties`: zero, so
+/// that k = 1 is plain Shamir sharing, followed by the points just above the largest party id
+pub fn packed_secret_points<T: Ring>(parties: &[PartyId], k: usize) -> Vec<T> {
+    let max = parties.iter().copied().max().unwrap_or(0) as u64;
+
+    std::iter::once(T::zero())
+        .chain((1..k as u64).map(|j| T::from(max + j)))
+        .take(k)
+        .collect()
+}
+
 pub fn lagrange_poly<T: Field + Copy, F: Fn(&T) -> T>(points: &[T], xi: &T, num_func: F) -> T {
     let (n, d) = points
         .iter()
@@ -103,6 +313,103 @@ pub fn lagrange_poly<T: Field + Copy, F: Fn(&T) -> T>(points: &[T], xi: &T, num_
     n * d.inv().unwrap()
 }
 
+impl<T: Field + Copy> InterpolationPolynomial<T> {
+    /// Recover the coefficients of the polynomial through all of the points
+    pub fn to_fixed(&self) -> Result<FixedPolynomial<T>, &'static str> {
+        FixedPolynomial::interpolate(&self.points, &self.vals)
+    }
+
+    /// Reed-Solomon decoding using Gao's algorithm. Finds the polynomial of degree < t that
+    /// agrees with all but at most (n - t) / 2 of the n values, and returns it along with
+    /// the indices of the values that do not lie on it.
+    pub fn decode(&self, t: usize) -> Result<(FixedPolynomial<T>, Vec<usize>), &'static str> {
+        let n = self.points.len();
+        if t == 0 || n < t {
+            return Err("not enough values to decode");
+        }
+
+        // g0 = \prod_i (x - x_i), g1 interpolates all the values
+        let g0 = self
+            .points
+            .iter()
+            .fold(FixedPolynomial::new(vec![T::one()]), |acc, x| {
+                &acc * &FixedPolynomial::new(vec![T::zero() - x, T::one()])
+            });
+        let g1 = self.to_fixed()?;
+
+        // partial extended euclid, tracking v with r = u * g0 + v * g1,
+        // until the remainder has degree < (n + t) / 2
+        let small = |p: &FixedPolynomial<T>| p.degree().is_none_or(|d| 2 * d < n + t);
+        let (mut r_prev, mut r) = (g0, g1);
+        let (mut v_prev, mut v) = (
+            FixedPolynomial::new(Vec::new()),
+            FixedPolynomial::new(vec![T::one()]),
+        );
+        while !small(&r) {
+            let (q, rem) = r_prev.divrem(&r).unwrap();
+            let v_next = &v_prev - &(&q * &v);
+            (r_prev, r) = (r, rem);
+            (v_prev, v) = (v, v_next);
+        }
+
+        // v is the error locator, and divides r exactly when decoding succeeds
+        let (f, rem) = r.divrem(&v).ok_or("failed to decode")?;
+        if rem.degree().is_some() || f.degree().is_some_and(|d| d >= t) {
+            return Err("too many errors to decode");
+        }
+
+        let errors: Vec<usize> = self
+            .points
+            .iter()
+            .zip(self.vals.iter())
+            .enumerate()
+            .filter(|(_, (x, y))| f.evaluate(x) != **y)
+            .map(|(i, _)| i)
+            .collect();
+        if 2 * errors.len() > n - t {
+            return Err("too many errors to decode");
+        }
+
+        Ok((f, errors))
+    }
+
+    /// Recover the k packed secrets at `secret_points` from a degree t + k - 1 sharing.
+    /// Fails if the shares are inconsistent, use `decode(t + k)` first to correct errors.
+    pub fn packed_reconstruct(
+        &self,
+        t: usize,
+        secret_points: &[T],
+    ) -> Result<Vec<T>, &'static str> {
+        let n = t + secret_points.len();
+        if self.points.len() < n {
+            return Err("not enough shares to reconstruct");
+        }
+
+        let f = self.to_fixed()?;
+        if f.degree().is_some_and(|d| d >= n) {
+            return Err("shares are inconsistent");
+        }
+
+        Ok(f.evaluate_many(secret_points))
+    }
+}
+
+/// Reconstruct the secret from the shares of `parties` of a degree t - 1 sharing, correcting
+/// errors where possible. Returns the secret and the parties that supplied inconsistent shares.
+pub fn robust_reconstruct<T: Field + Copy>(
+    parties: &[PartyId],
+    shares: &[T],
+    t: usize,
+) -> Result<(T, Vec<PartyId>), &'static str> {
+    let points: Vec<T> = parties.iter().map(|&p| T::from(p.into())).collect();
+    let (f, errors) = InterpolationPolynomial::new(&points, shares)?.decode(t)?;
+
+    Ok((
+        f.eval_zero(),
+        errors.into_iter().map(|i| parties[i]).collect(),
+    ))
+}
+
 impl<T: Field + Copy> Polynomial<T> for InterpolationPolynomial<T> {
     fn evaluate(&self, x: &T) -> T {
         if *x == ConstInt::zero() {
@@ -123,7 +430,7 @@ impl<T: Field + Copy> Polynomial<T> for InterpolationPolynomial<T> {
             .iter()
             .zip(self.vals.iter())
             .map(|(xi, yi)| {
-                let l = lagrange_poly(&self.points, xi, |x| *x);
+                let l = lagrange_poly(&self.points, xi, |x| T::zero() - x);
                 return l * yi;
             })
             .sum()
@@ -133,7 +440,7 @@ impl<T: Field + Copy> Polynomial<T> for InterpolationPolynomial<T> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{ff2_128::FF2_128, field::ConstInt};
+    use crate::{ff2_128::FF2_128, field::ConstInt, fp127::FP127};
 
     use rand;
 
@@ -178,4 +485,184 @@ mod tests {
         let p2 = InterpolationPolynomial::new(&points, &sum_vals).unwrap();
         assert!(p2.eval_zero() == zero);
     }
+
+    #[test]
+    fn test_interpolate_prime_field() {
+        let mut rng = rand::thread_rng();
+
+        let val = FP127::rand(&mut rng);
+        let points: Vec<_> = (1..6).map(|p| FP127::from(p)).collect();
+
+        let shares = InterpolationPolynomial::secret_share(&mut rng, val, 4, &points);
+        assert_eq!(shares.eval_zero(), val);
+
+        let p = InterpolationPolynomial::new(&points[1..], &shares.vals[1..]).unwrap();
+        assert_eq!(p.eval_zero(), val);
+        assert_eq!(p.evaluate(&points[0]), shares.vals[0]);
+    }
+
+    #[test]
+    fn test_poly_arith() {
+        let mut rng = rand::thread_rng();
+        let a = FixedPolynomial::<FP127>::rand_polynomial(&mut rng, 4);
+        let b = FixedPolynomial::<FP127>::rand_polynomial(&mut rng, 2);
+        let x = FP127::rand(&mut rng);
+
+        assert_eq!((&a + &b).evaluate(&x), a.evaluate(&x) + b.evaluate(&x));
+        assert_eq!((&a - &b).evaluate(&x), a.evaluate(&x) - b.evaluate(&x));
+        assert_eq!((&a * &b).evaluate(&x), a.evaluate(&x) * b.evaluate(&x));
+        assert_eq!((&a * &b).degree(), Some(6));
+        assert_eq!((&a - &a).degree(), None);
+
+        // leading zeros do not matter
+        let mut c = a.coefficients().to_vec();
+        c.extend([FP127::zero(); 3]);
+        assert_eq!(FixedPolynomial::new(c), a);
+    }
+
+    #[test]
+    fn test_divrem() {
+        let mut rng = rand::thread_rng();
+        let a = FixedPolynomial::<FF2_128>::rand_polynomial(&mut rng, 7);
+        let b = FixedPolynomial::<FF2_128>::rand_polynomial(&mut rng, 3);
+
+        let (q, r) = a.divrem(&b).unwrap();
+        assert_eq!(&(&q * &b) + &r, a);
+        assert_eq!(q.degree(), Some(4));
+        assert!(r.degree().is_none_or(|d| d < 3));
+
+        // exact division
+        let (q, r) = (&a * &b).divrem(&b).unwrap();
+        assert_eq!(q, a);
+        assert_eq!(r.degree(), None);
+
+        // smaller numerator
+        let (q, r) = b.divrem(&a).unwrap();
+        assert_eq!(q.degree(), None);
+        assert_eq!(r, b);
+
+        assert!(a.divrem(&FixedPolynomial::new(vec![])).is_none());
+    }
+
+    #[test]
+    fn test_interpolate_coefficients() {
+        let mut rng = rand::thread_rng();
+        let a = FixedPolynomial::<FP127>::rand_polynomial(&mut rng, 4);
+        let points: Vec<_> = (1..=7).map(|p| FP127::from(p)).collect();
+        let vals = a.evaluate_many(&points);
+
+        assert_eq!(
+            FixedPolynomial::interpolate(&points[..5], &vals[..5]).unwrap(),
+            a
+        );
+        assert_eq!(FixedPolynomial::interpolate(&points, &vals).unwrap(), a);
+        assert_eq!(
+            InterpolationPolynomial::new(&points, &vals)
+                .unwrap()
+                .to_fixed()
+                .unwrap()
+                .degree(),
+            Some(4)
+        );
+
+        // too few points give a different polynomial through the same points
+        let b = FixedPolynomial::interpolate(&points[..4], &vals[..4]).unwrap();
+        assert_ne!(b, a);
+        assert_eq!(b.evaluate_many(&points[..4]), vals[..4].to_vec());
+
+        let dup = [points[0], points[1], points[0]];
+        assert!(FixedPolynomial::interpolate(&dup, &vals[..3]).is_err());
+    }
+
+    #[test]
+    fn test_robust_reconstruct() {
+        let mut rng = rand::thread_rng();
+        let val = FP127::rand(&mut rng);
+        let parties: Vec<PartyId> = (1..=7).collect();
+        let points: Vec<_> = parties.iter().map(|&p| FP127::from(p as u64)).collect();
+        let t = 3;
+
+        let shares = InterpolationPolynomial::secret_share(&mut rng, val, t, &points);
+
+        // no errors
+        let (v, bad) = robust_reconstruct(&parties, &shares.vals, t).unwrap();
+        assert_eq!(v, val);
+        assert!(bad.is_empty());
+
+        // up to (7 - 3) / 2 = 2 errors are corrected
+        let mut vals = shares.vals.clone();
+        vals[1] += FP127::one();
+        vals[5] = FP127::rand(&mut rng);
+        let (v, bad) = robust_reconstruct(&parties, &vals, t).unwrap();
+        assert_eq!(v, val);
+        assert_eq!(bad, vec![2, 6]);
+
+        // but not more
+        vals[3] += FP127::one();
+        assert!(robust_reconstruct(&parties, &vals, t).is_err());
+
+        // also in characteristic 2
+        let val = FF2_128::rand(&mut rng);
+        let points: Vec<_> = parties.iter().map(|&p| FF2_128::from(p as u64)).collect();
+        let shares = InterpolationPolynomial::secret_share(&mut rng, val, t, &points);
+        let mut vals = shares.vals.clone();
+        vals[0] = FF2_128::rand(&mut rng);
+        let (v, bad) = robust_reconstruct(&parties, &vals, t).unwrap();
+        assert_eq!(v, val);
+        assert_eq!(bad, vec![1]);
+    }
+
+    #[test]
+    fn test_packed_secret_share() {
+        let mut rng = rand::thread_rng();
+        let parties: Vec<PartyId> = (1..=8).collect();
+        let points: Vec<_> = parties.iter().map(|&p| FP127::from(p as u64)).collect();
+        let (t, k) = (2, 3);
+
+        let secret_points = packed_secret_points::<FP127>(&parties, k);
+        assert_eq!(
+            secret_points,
+            vec![FP127::zero(), FP127::from(9), FP127::from(10)]
+        );
+        let secrets: Vec<_> = (0..k).map(|_| FP127::rand(&mut rng)).collect();
+
+        let shares = InterpolationPolynomial::packed_secret_share(
+            &mut rng,
+            &secrets,
+            t,
+            &secret_points,
+            &points,
+        );
+        assert_eq!(shares.to_fixed().unwrap().degree(), Some(t + k - 1));
+        assert_eq!(
+            shares.packed_reconstruct(t, &secret_points),
+            Ok(secrets.clone())
+        );
+
+        // t + k shares are enough, but not fewer
+        let few = InterpolationPolynomial::new(&points[..t + k], &shares.vals[..t + k]).unwrap();
+        assert_eq!(
+            few.packed_reconstruct(t, &secret_points),
+            Ok(secrets.clone())
+        );
+        let few =
+            InterpolationPolynomial::new(&points[..t + k - 1], &shares.vals[..t + k - 1]).unwrap();
+        assert!(few.packed_reconstruct(t, &secret_points).is_err());
+
+        // an inconsistent share is detected, and decoding corrects it
+        let mut bad = shares.clone();
+        bad.vals[4] += FP127::one();
+        assert!(bad.packed_reconstruct(t, &secret_points).is_err());
+        let (f, errors) = bad.decode(t + k).unwrap();
+        assert_eq!(errors, vec![4]);
+        assert_eq!(f.evaluate_many(&secret_points), secrets);
+
+        // k = 1 is plain Shamir sharing of the secret at zero
+        let single = packed_secret_points::<FF2_128>(&parties, 1);
+        let val = FF2_128::rand(&mut rng);
+        let points: Vec<_> = parties.iter().map(|&p| FF2_128::from(p as u64)).collect();
+        let shares =
+            InterpolationPolynomial::packed_secret_share(&mut rng, &[val], 3, &single, &points);
+        assert_eq!(shares.eval_zero(), val);
+    }
 }
@@ -1,50 +1,35 @@
-use core::arch::x86_64::*;
-
 use std::ops::{Add, AddAssign, Mul, MulAssign, Sub, SubAssign};
 
-use crate::field::{
-    impl_arith_assign, impl_sum_prod, Adds, ConstInt, Muls, RandElement, Ring, Subs,
+use crate::{
+    block::Block,
+    field::{impl_arith_assign, impl_sum_prod, Adds, ConstInt, Muls, RandElement, Ring, Subs},
 };
 
 use rand::Rng;
 
 /// An element in the ring {F_2}^128
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct RR2_128 {
-    i: __m128i,
-}
-
-impl PartialEq for RR2_128 {
-    fn eq(&self, other: &Self) -> bool {
-        unsafe {
-            let t = _mm_xor_si128(self.i, other.i);
-            let r = _mm_test_all_zeros(t, t);
-            return r == 1;
-        }
-    }
+    i: Block,
 }
 
 impl RR2_128 {
     pub fn new(high: u64, low: u64) -> Self {
-        unsafe {
-            return Self {
-                i: _mm_set_epi64x(high as i64, low as i64),
-            };
+        Self {
+            i: Block::new(high, low),
         }
     }
 
     pub fn add_assign(&mut self, other: Self) {
-        unsafe { self.i = _mm_xor_si128(self.i, other.i) }
+        self.i = self.i.xor(other.i);
     }
 
     pub fn sub_assign(&mut self, other: Self) {
-        unsafe { self.i = _mm_xor_si128(self.i, other.i) }
+        self.i = self.i.xor(other.i);
     }
 
     pub fn mul_assign(&mut self, other: Self) {
-        unsafe {
-            self.i = _mm_and_si128(self.i, other.i);
-        }
+        self.i = self.i.and(other.i);
     }
 }
 
@@ -75,9 +60,7 @@ impl ConstInt for RR2_128 {
         return Self::new(u64::MAX, u64::MAX);
     }
     fn is_zero(&self) -> bool {
-        unsafe {
-            return _mm_test_all_zeros(self.i, self.i) == 1;
-        }
+        self.i.is_zero()
     }
     fn num_bytes(&self) -> usize {
         return 16;
@@ -86,8 +69,7 @@ impl ConstInt for RR2_128 {
     fn to_bytes(&self, b: &mut [u8]) -> usize {
         assert!(b.len() >= 16);
 
-        let bytes: &[u8; 16] = unsafe { std::mem::transmute(self) };
-        b[..16].copy_from_slice(bytes);
+        b[..16].copy_from_slice(&self.i.to_bytes());
 
         return 16;
     }
@@ -0,0 +1,252 @@
+use std::ops::{Add, AddAssign, Mul, MulAssign, Sub, SubAssign};
+
+use crate::field::{Adds, ConstInt, Muls, RandElement, Ring, Subs};
+use rand::Rng;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// An element in the ring Z / 2^K Z, for 1 <= K <= 128
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct Z2k<const K: usize> {
+    // always kept reduced in [0, 2^K)
+    i: u128,
+}
+
+/// Type alias for the common case of 64-bit integer arithmetic
+pub type Z2_64 = Z2k<64>;
+
+impl<const K: usize> Serialize for Z2k<K> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut bytes = [0; 16];
+        self.to_bytes(&mut bytes);
+        bytes[..Self::BYTES].serialize(serializer)
+    }
+}
+
+impl<'d, const K: usize> Deserialize<'d> for Z2k<K> {
+    fn deserialize<D: Deserializer<'d>>(deserializer: D) -> Result<Self, D::Error> {
+        let bytes = Vec::<u8>::deserialize(deserializer)?;
+        if bytes.len() == Self::BYTES {
+            Ok(Self::from_bytes(&bytes))
+        } else {
+            Err(<D::Error as de::Error>::custom(format!(
+                "byte array not len {}: {}",
+                Self::BYTES,
+                bytes.len()
+            )))
+        }
+    }
+}
+
+impl<const K: usize> std::fmt::Debug for Z2k<K> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Z2k<{}>({:#x})", K, self.i)
+    }
+}
+
+impl<const K: usize> Z2k<K> {
+    const MASK: u128 = {
+        assert!(K >= 1 && K <= 128);
+        if K == 128 {
+            u128::MAX
+        } else {
+            (1 << K) - 1
+        }
+    };
+
+    /// Create an element from an integer, reducing modulo 2^K
+    pub fn new(x: u128) -> Self {
+        Self { i: x & Self::MASK }
+    }
+
+    /// The integer representative in [0, 2^K)
+    pub fn as_u128(&self) -> u128 {
+        self.i
+    }
+
+    pub fn add_assign(&mut self, other: Self) {
+        self.i = self.i.wrapping_add(other.i) & Self::MASK;
+    }
+
+    pub fn sub_assign(&mut self, other: Self) {
+        self.i = self.i.wrapping_sub(other.i) & Self::MASK;
+    }
+
+    pub fn mul_assign(&mut self, other: Self) {
+        self.i = self.i.wrapping_mul(other.i) & Self::MASK;
+    }
+}
+
+// the field macros expect a plain identifier for the type,
+// so implement the operators generically over K here
+macro_rules! impl_z2k_arith {
+    ($trait:ident, $tf:ident, $traitassign:ident, $taf:ident) => {
+        impl<const K: usize> $traitassign for Z2k<K> {
+            fn $taf(&mut self, other: Self) {
+                Z2k::$taf(self, other);
+            }
+        }
+
+        impl<const K: usize> $traitassign<&Z2k<K>> for Z2k<K> {
+            fn $taf(&mut self, other: &Self) {
+                Z2k::$taf(self, *other);
+            }
+        }
+
+        impl<const K: usize> $trait for Z2k<K> {
+            type Output = Self;
+            fn $tf(mut self, other: Self) -> Self {
+                Z2k::$taf(&mut self, other);
+                self
+            }
+        }
+
+        impl<const K: usize> $trait<&Z2k<K>> for Z2k<K> {
+            type Output = Self;
+            fn $tf(mut self, other: &Self) -> Self {
+                Z2k::$taf(&mut self, *other);
+                self
+            }
+        }
+
+        impl<'a, const K: usize> $trait<Z2k<K>> for &'a Z2k<K> {
+            type Output = Z2k<K>;
+            fn $tf(self, other: Z2k<K>) -> Z2k<K> {
+                let mut c = *self;
+                Z2k::$taf(&mut c, other);
+                c
+            }
+        }
+
+        impl<'a, 'b, const K: usize> $trait<&'b Z2k<K>> for &'a Z2k<K> {
+            type Output = Z2k<K>;
+            fn $tf(self, other: &'b Z2k<K>) -> Z2k<K> {
+                let mut c = *self;
+                Z2k::$taf(&mut c, *other);
+                c
+            }
+        }
+    };
+}
+
+impl_z2k_arith!(Add, add, AddAssign, add_assign);
+impl_z2k_arith!(Sub, sub, SubAssign, sub_assign);
+impl_z2k_arith!(Mul, mul, MulAssign, mul_assign);
+
+impl<const K: usize> std::iter::Sum for Z2k<K> {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::zero(), |acc, x| acc + x)
+    }
+}
+
+impl<'a, const K: usize> std::iter::Sum<&'a Z2k<K>> for Z2k<K> {
+    fn sum<I: Iterator<Item = &'a Self>>(iter: I) -> Self {
+        iter.fold(Self::zero(), |acc, x| acc + x)
+    }
+}
+
+impl<const K: usize> std::iter::Product for Z2k<K> {
+    fn product<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::one(), |acc, x| acc * x)
+    }
+}
+
+impl<'a, const K: usize> std::iter::Product<&'a Z2k<K>> for Z2k<K> {
+    fn product<I: Iterator<Item = &'a Self>>(iter: I) -> Self {
+        iter.fold(Self::one(), |acc, x| acc * x)
+    }
+}
+
+impl<const K: usize> Adds for Z2k<K> {}
+
+impl<const K: usize> Subs for Z2k<K> {}
+
+impl<const K: usize> Muls for Z2k<K> {}
+
+impl<const K: usize> From<u64> for Z2k<K> {
+    fn from(other: u64) -> Self {
+        Self::new(other.into())
+    }
+}
+
+impl<const K: usize> ConstInt for Z2k<K> {
+    const BYTES: usize = K.div_ceil(8);
+
+    fn zero() -> Self {
+        Self { i: 0 }
+    }
+
+    fn one() -> Self {
+        Self { i: 1 }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.i == 0
+    }
+
+    fn num_bytes(&self) -> usize {
+        Self::BYTES
+    }
+
+    fn to_bytes(&self, b: &mut [u8]) -> usize {
+        assert!(b.len() >= Self::BYTES);
+        b[..Self::BYTES].copy_from_slice(&self.i.to_le_bytes()[..Self::BYTES]);
+        Self::BYTES
+    }
+
+    /// Reads the first `BYTES` bytes as a little-endian integer and reduces modulo 2^K
+    fn from_bytes(b: &[u8]) -> Self {
+        assert!(b.len() >= Self::BYTES);
+        let mut bytes = [0; 16];
+        bytes[..Self::BYTES].copy_from_slice(&b[..Self::BYTES]);
+        Self::new(u128::from_le_bytes(bytes))
+    }
+}
+
+impl<const K: usize> Ring for Z2k<K> {}
+
+impl<const K: usize> RandElement for Z2k<K> {
+    fn rand<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Self::new(rng.gen::<u128>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand;
+
+    #[test]
+    fn test_wrapping_arith() {
+        let m = Z2_64::new(u64::MAX.into());
+        assert_eq!(m + Z2_64::one(), Z2_64::zero());
+        assert_eq!(Z2_64::zero() - Z2_64::one(), m);
+        assert_eq!(m * m, Z2_64::one());
+
+        let x = 0x1234_5678_9abc_def0u64;
+        let y = 0x0fed_cba9_8765_4321u64;
+        assert_eq!(
+            Z2_64::from(x) * Z2_64::from(y),
+            Z2_64::from(x.wrapping_mul(y))
+        );
+
+        // an odd sized ring
+        let a = Z2k::<13>::from(8000);
+        let b = Z2k::<13>::from(400);
+        assert_eq!(a + b, Z2k::<13>::from(8400 - 8192));
+        assert_eq!(a * b, Z2k::<13>::from((8000 * 400) % 8192));
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let mut rng = rand::thread_rng();
+        let val = Z2_64::rand(&mut rng);
+
+        let mut bytes = [0; 8];
+        assert_eq!(val.to_bytes(&mut bytes), 8);
+        assert_eq!(val, Z2_64::from_bytes(&bytes));
+
+        let s = serde_json::to_string(&val).unwrap();
+        let new_val: Z2_64 = serde_json::from_str(&s).unwrap();
+        assert_eq!(val, new_val);
+    }
+}